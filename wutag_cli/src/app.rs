@@ -1,18 +1,38 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::client::Client;
 use crate::config::Config;
+use crate::exec::{exec_batch, exec_each, CommandTemplate};
 use crate::fmt;
+use crate::input::{path_chunks, STDIN_ARG};
 use crate::opt::{
-    ClearObject, ClearOpts, Command, CpOpts, EditOpts, GetOpts, ListObject, ListOpts, Opts,
-    OutputFormat, RmOpts, SearchOpts, SetOpts,
+    AliasCommand, AliasOpts, AutoTagOpts, ClearObject, ClearOpts, Command, CpOpts, EditOpts,
+    ExportOpts, GetOpts, GlobOpts, ImpliesCommand, ImpliesOpts, ImportOpts, ListObject, ListOpts,
+    LogOpts, Opts, OutputFormat, RedoOpts, RmOpts, RulesCommand, RulesOpts, SavedCommand,
+    SavedOpts, SearchOpts, SetOpts, SuggestOpts, TagCommand, TagOpts, UndoOpts, ViewCommand,
+    ViewOpts,
 };
 use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::Write;
 use thiserror::Error as ThisError;
-use wutag_core::color::{self, parse_color, Color, DEFAULT_COLORS};
-use wutag_core::glob::Glob;
+use wutag_core::audit::{AuditAction, AuditEntry, AuditFilter};
+use wutag_core::color::{self, parse_color, Color, Colorize, DEFAULT_COLORS};
+use wutag_core::filter::FileFilter;
+use wutag_core::glob::{Glob, DEFAULT_MAX_DEPTH};
+use wutag_core::journal::JournalEntry;
+use wutag_core::metadata::{NamePattern, Template};
+use wutag_core::query::Query;
+use wutag_core::registry::{
+    canonical_path, ChangeKind, EffectiveTag, EntryData, SearchMatch, TagChange, TagInfo,
+};
+use wutag_core::sort::Page;
 use wutag_core::tag::Tag;
-use wutag_ipc::{default_socket, Response};
+use wutag_core::view::ViewReport;
+use wutag_ipc::{default_socket, AutoTagOptions, Response, SearchOptions, TagEdit};
 
 #[derive(Debug, ThisError)]
 pub enum AppError {
@@ -20,6 +40,10 @@ pub enum AppError {
     GetCurrentWorkingDirectory(std::io::Error),
     #[error("failed to parse color - {0}")]
     ParseColor(wutag_core::Error),
+    #[error("failed to parse time - {0}")]
+    ParseTime(wutag_core::Error),
+    #[error("{0}")]
+    InvalidAutoTagOption(wutag_core::metadata::MetadataError),
     #[error("failed to list entries - {0}")]
     ListEntries(String),
     #[error("failed to inspect entries - {0}")]
@@ -34,19 +58,232 @@ pub enum AppError {
     SerializeYamlOutput(serde_yaml::Error),
     #[error("failed to serialize output as json - {0}")]
     SerializeJsonOutput(serde_json::Error),
+    #[error("failed to write `{0}` - {1}")]
+    WriteFile(PathBuf, std::io::Error),
+    #[error("failed to write output - {0}")]
+    WriteOutput(std::io::Error),
+    #[error("{0} of {1} commands failed")]
+    Exec(usize, usize),
+    #[error("failed to read `{0}` - {1}")]
+    ReadFile(PathBuf, std::io::Error),
+    #[error("failed to read paths from standard input - {0}")]
+    ReadStdin(std::io::Error),
+    #[error("{failed} of {total} batches of paths failed:{errors}")]
+    Chunks {
+        failed: usize,
+        total: usize,
+        errors: String,
+    },
+    #[error("failed to parse `{0}` - {1}")]
+    ParseImport(PathBuf, serde_yaml::Error),
+    #[error("failed to import tags - {0}")]
+    Import(String),
+    #[error("failed to automatically tag files - {0}")]
+    AutoTag(String),
     #[error("failed to {action} - unexpected response from server {response:?}")]
     UnexpectedResponse { action: String, response: Response },
 }
 
 pub struct App {
     pub base_dir: PathBuf,
+    /// Maximum depth of traversal when globbing, `None` if unlimited
     pub max_depth: Option<usize>,
     pub colors: Vec<Color>,
     pub pretty: bool,
     pub format: OutputFormat,
+    pub dry_run: bool,
+    pub inherit_tags: bool,
     pub client: Client,
 }
 
+#[derive(Debug)]
+/// Map serialized with its keys in the order in which they were inserted so that JSON and YAML
+/// output keeps the order of the results.
+struct OrderedMap<K, V>(Vec<(K, V)>);
+
+impl<K, V> FromIterator<(K, V)> for OrderedMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<K: Serialize, V: Serialize> Serialize for OrderedMap<K, V> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+    }
+}
+
+#[derive(Debug, Serialize)]
+/// Serializable form of an entry with its tags and the times at which they were applied.
+struct EntryOutput<T> {
+    tags: Vec<T>,
+    added: Option<DateTime<Utc>>,
+    tagged: BTreeMap<String, DateTime<Utc>>,
+    expires: BTreeMap<String, DateTime<Utc>>,
+}
+
+impl<T> EntryOutput<T> {
+    fn new(entry: &EntryData, tags: Vec<T>) -> Self {
+        Self {
+            tags,
+            added: entry.added().cloned(),
+            tagged: entry.tagged().clone(),
+            expires: entry.expires().clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+/// Serializable form of the effective tags of an entry.
+struct EffectiveOutput {
+    tags: Vec<String>,
+    /// Directories from which the inherited tags come keyed by the name of the tag.
+    inherited: BTreeMap<String, PathBuf>,
+}
+
+impl From<Vec<EffectiveTag>> for EffectiveOutput {
+    fn from(tags: Vec<EffectiveTag>) -> Self {
+        let mut output = Self {
+            tags: vec![],
+            inherited: BTreeMap::new(),
+        };
+        for tag in tags {
+            match tag.inherited_from {
+                Some(from) => {
+                    output.inherited.insert(tag.tag.into_name(), from);
+                }
+                None => output.tags.push(tag.tag.into_name()),
+            }
+        }
+        output
+    }
+}
+
+#[derive(Debug, Serialize)]
+/// Serializable form of a [SearchMatch](SearchMatch).
+struct SearchOutput {
+    path: PathBuf,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    inherited: BTreeMap<String, PathBuf>,
+}
+
+impl From<SearchMatch> for SearchOutput {
+    fn from(found: SearchMatch) -> Self {
+        Self {
+            path: found.entry.into_path_buf(),
+            inherited: found.inherited,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+/// Serializable form of a [TagChange](TagChange).
+struct ChangeOutput {
+    path: PathBuf,
+    tag: String,
+    change: ChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires: Option<DateTime<Utc>>,
+}
+
+impl From<TagChange> for ChangeOutput {
+    fn from(change: TagChange) -> Self {
+        Self {
+            path: change.path,
+            tag: change.tag.into_name(),
+            change: change.kind,
+            expires: change.expires,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+/// Serializable form of an edit of a tag, implication, alias, saved search or entry of the
+/// registry described by an [AuditEntry](AuditEntry).
+struct EditOutput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    tag: String,
+    action: AuditAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<String>,
+}
+
+impl From<AuditEntry> for EditOutput {
+    fn from(entry: AuditEntry) -> Self {
+        Self {
+            path: entry.path,
+            tag: entry.tag,
+            action: entry.action,
+            details: entry.details,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+/// Serializable form of a [JournalEntry](JournalEntry).
+struct HistoryOutput {
+    timestamp: String,
+    request: String,
+    changes: Vec<ChangeOutput>,
+}
+
+impl From<JournalEntry> for HistoryOutput {
+    fn from(entry: JournalEntry) -> Self {
+        Self {
+            timestamp: entry.timestamp.to_rfc3339(),
+            request: entry.request,
+            changes: entry.changes.into_iter().map(ChangeOutput::from).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+/// Serializable form of a [TagInfo](TagInfo).
+struct TagInfoOutput {
+    name: String,
+    color: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<DateTime<Utc>>,
+    aliases: Vec<String>,
+    implies: Vec<String>,
+    usage: usize,
+    co_occurring: Vec<CoOccurrenceOutput>,
+}
+
+#[derive(Debug, Serialize)]
+struct CoOccurrenceOutput {
+    tag: String,
+    count: usize,
+}
+
+impl From<TagInfo> for TagInfoOutput {
+    fn from(info: TagInfo) -> Self {
+        Self {
+            color: fmt::color(info.tag.color()),
+            description: info.tag.description().map(String::from),
+            icon: info.tag.icon().map(String::from),
+            created: info.tag.created().cloned(),
+            name: info.tag.into_name(),
+            aliases: info.aliases,
+            implies: info.implies,
+            usage: info.usage,
+            co_occurring: info
+                .co_occurring
+                .into_iter()
+                .map(|(tag, count)| CoOccurrenceOutput { tag, count })
+                .collect(),
+        }
+    }
+}
+
 impl App {
     pub fn run(opts: Opts, config: Config) -> Result<()> {
         let mut app = Self::new(&opts, config)?;
@@ -54,7 +291,7 @@ impl App {
     }
     pub fn new(opts: &Opts, config: Config) -> Result<App> {
         let base_dir = if let Some(base_dir) = &opts.dir {
-            base_dir.to_path_buf()
+            std::path::absolute(base_dir).map_err(AppError::GetCurrentWorkingDirectory)?
         } else {
             std::env::current_dir().map_err(AppError::GetCurrentWorkingDirectory)?
         };
@@ -75,14 +312,20 @@ impl App {
 
         Ok(App {
             base_dir,
-            max_depth: if opts.max_depth.is_some() {
-                opts.max_depth
+            max_depth: if opts.unlimited_depth {
+                None
             } else {
-                config.max_depth
+                Some(
+                    opts.max_depth
+                        .or(config.max_depth)
+                        .unwrap_or(DEFAULT_MAX_DEPTH),
+                )
             },
             colors,
             pretty: opts.pretty || config.pretty_output,
             format: opts.output_format,
+            dry_run: opts.dry_run,
+            inherit_tags: config.inherit_tags,
             client,
         })
     }
@@ -100,6 +343,22 @@ impl App {
             Command::Search(opts) => self.search(opts),
             Command::Cp(opts) => self.cp(opts),
             Command::Edit(opts) => self.edit(opts),
+            Command::Tag(opts) => self.tag(opts),
+            Command::Doctor => self.doctor(),
+            Command::Suggest(opts) => self.suggest(opts),
+            Command::Undo(opts) => self.undo(opts),
+            Command::Redo(opts) => self.redo(opts),
+            Command::History => self.history(),
+            Command::Log(opts) => self.log(opts),
+            Command::Rules(opts) => self.rules(opts),
+            Command::Implies(opts) => self.implies(opts),
+            Command::Autotag(opts) => self.autotag(opts),
+            Command::Alias(opts) => self.alias(opts),
+            Command::Saved(opts) => self.saved(opts),
+            Command::Export(opts) => self.export(opts),
+            Command::Import(opts) => self.import(opts),
+            Command::View(opts) => self.view(opts),
+            Command::CompleteTags => self.complete_tags(),
             // This command should be handled in main
             Command::PrintCompletions(_) => unreachable!(),
         }
@@ -119,34 +378,88 @@ impl App {
         Ok(())
     }
 
+    /// Prints the changes that a mutating subcommand would make. Only prints anything when
+    /// running with `--dry-run`.
+    fn print_plan(&self, changes: Vec<TagChange>) -> Result<()> {
+        if self.dry_run {
+            self.print_changes(changes)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn print_changes(&self, changes: Vec<TagChange>) -> Result<()> {
+        match self.format {
+            OutputFormat::Json | OutputFormat::Yaml => {
+                let changes: Vec<_> = changes.into_iter().map(ChangeOutput::from).collect();
+                self.print_serialized(changes)?;
+            }
+            OutputFormat::Default => {
+                for change in changes {
+                    println!("{}", fmt::change(&change));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the edits of tags, implications, aliases or saved searches if this is a dry run.
+    fn print_edit_plan(&self, edits: Vec<AuditEntry>) -> Result<()> {
+        if !self.dry_run {
+            return Ok(());
+        }
+        match self.format {
+            OutputFormat::Json | OutputFormat::Yaml => {
+                let edits: Vec<_> = edits.into_iter().map(EditOutput::from).collect();
+                self.print_serialized(edits)?;
+            }
+            OutputFormat::Default => {
+                for edit in &edits {
+                    println!("{}", fmt::edit(edit));
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn clear_cache(&mut self) -> Result<()> {
-        self.client.clear_cache()
+        let dropped = self.client.clear_cache(self.dry_run)?;
+        self.print_edit_plan(dropped)
     }
 
     fn list(&self, opts: ListOpts) -> Result<()> {
         match opts.object {
-            ListObject::Files { with_tags } => {
-                let entries = self.client.list_files(with_tags)?;
+            ListObject::Files {
+                with_tags,
+                sort,
+                page,
+            } => {
+                let entries = self
+                    .client
+                    .list_files(with_tags, sort.into(), page.into())?;
                 match self.format {
                     OutputFormat::Json | OutputFormat::Yaml => {
-                        let entries: std::collections::HashMap<_, _> = entries
+                        let entries: OrderedMap<_, _> = entries
                             .into_iter()
                             .map(|(e, tags)| {
-                                (
-                                    e.into_path_buf(),
-                                    tags.into_iter().map(Tag::into_name).collect::<Vec<_>>(),
-                                )
+                                let tags = tags.into_iter().map(Tag::into_name).collect();
+                                (e.path().to_path_buf(), EntryOutput::new(&e, tags))
                             })
                             .collect();
                         self.print_serialized(entries)?;
                     }
                     OutputFormat::Default => {
-                        for (entry, mut tags) in entries {
+                        for (entry, tags) in entries {
                             print!("{}", fmt::path(entry.path()));
-                            tags.sort_unstable();
                             let tags = tags
-                                .into_iter()
-                                .map(|t| fmt::tag(&t).to_string())
+                                .iter()
+                                .map(|t| {
+                                    fmt::tagged(
+                                        t,
+                                        entry.tagged_at(t.name()),
+                                        entry.expires_at(t.name()),
+                                    )
+                                })
                                 .collect::<Vec<_>>()
                                 .join(" ");
 
@@ -155,11 +468,11 @@ impl App {
                     }
                 }
             }
-            ListObject::Tags { with_files } => {
-                let tags = self.client.list_tags(with_files)?;
+            ListObject::Tags { with_files, page } => {
+                let tags = self.client.list_tags(with_files, page.into())?;
                 match self.format {
                     OutputFormat::Json | OutputFormat::Yaml => {
-                        let tags: std::collections::HashMap<_, _> = tags
+                        let tags: OrderedMap<_, _> = tags
                             .into_iter()
                             .map(|(t, e)| {
                                 (
@@ -179,9 +492,7 @@ impl App {
                                 }
                             }
                         } else {
-                            let mut tags: Vec<_> = tags.into_iter().map(|(t, _)| t).collect();
-                            tags.sort_unstable();
-                            for tag in tags {
+                            for (tag, _) in tags {
                                 print!("{} ", fmt::tag(&tag));
                             }
                         }
@@ -199,33 +510,73 @@ impl App {
             .map(|t| Tag::random(t, &self.colors))
             .collect();
 
+        let expires = opts
+            .expires
+            .map(|expires| wutag_core::time::parse_expiry(&expires).map_err(AppError::ParseTime))
+            .transpose()?;
+
         if opts.glob {
-            let glob = self.glob(&opts.paths[0])?;
+            let glob = self.glob(opts.paths, opts.glob_opts)?;
+            let changes =
+                self.client
+                    .tag_files_pattern(glob, tags, expires, opts.implied, self.dry_run)?;
+            return self.print_plan(changes);
+        }
+        let (changes, result) = self.in_chunks(opts.paths, opts.stdin, opts.null, |paths| {
             self.client
-                .tag_files_pattern(glob, tags)
-                .map_err(Error::from)
-                .map(|_| ())
+                .tag_files(paths, tags.clone(), expires, opts.implied, self.dry_run)
+        })?;
+        self.print_plan(changes)?;
+        result
+    }
+
+    fn autotag(&mut self, opts: AutoTagOpts) -> Result<()> {
+        for template in &opts.metadata {
+            Template::parse(template).map_err(AppError::InvalidAutoTagOption)?;
+        }
+        if let Some(pattern) = &opts.from_name {
+            NamePattern::parse(pattern).map_err(AppError::InvalidAutoTagOption)?;
+        }
+        let options = AutoTagOptions {
+            by_type: opts.by_type,
+            metadata: opts.metadata,
+            from_name: opts.from_name,
+            colors: self.colors.clone(),
+        };
+
+        let report = if opts.glob {
+            let glob = self.glob(opts.paths, opts.glob_opts)?;
+            self.client.auto_tag_pattern(glob, options, self.dry_run)?
         } else {
-            self.client
-                .tag_files(opts.paths, tags)
-                .map_err(Error::from)
-                .map(|_| ())
+            let paths: Vec<_> = opts.paths.iter().map(|p| self.entry_path(p)).collect();
+            self.client.auto_tag(paths, options, self.dry_run)?
+        };
+        self.print_changes(report.changes)?;
+        if report.errors.is_empty() {
+            return Ok(());
         }
+        // the tags of the other files were applied, only the listed ones failed
+        Err(AppError::AutoTag(error_list(&report.errors)).into())
     }
 
     fn get(&mut self, opts: GetOpts) -> Result<()> {
-        let entries = if opts.glob {
-            let glob = self.glob(&opts.paths[0])?;
-            self.client.inspect_files_pattern(glob)?
+        if opts.effective {
+            return self.get_effective(opts);
+        }
+        let (entries, result) = if opts.glob {
+            let glob = self.glob(opts.paths, opts.glob_opts)?;
+            (self.client.inspect_files_pattern(glob)?, Ok(()))
         } else {
-            self.client.inspect_files(opts.paths)?
+            self.in_chunks(opts.paths, opts.stdin, opts.null, |paths| {
+                self.client.inspect_files(paths)
+            })?
         };
 
         match self.format {
             OutputFormat::Json | OutputFormat::Yaml => {
-                let entries: std::collections::HashMap<_, _> = entries
+                let entries: OrderedMap<_, _> = entries
                     .into_iter()
-                    .map(|(e, tags)| (e.into_path_buf(), tags))
+                    .map(|(e, tags)| (e.path().to_path_buf(), EntryOutput::new(&e, tags)))
                     .collect();
                 self.print_serialized(entries)?;
             }
@@ -234,12 +585,54 @@ impl App {
                     tags.sort_unstable();
                     print!("{}:", fmt::path(entry.path()));
                     for tag in &tags {
-                        print!(" {}", fmt::tag(tag))
+                        print!(
+                            " {}",
+                            fmt::tagged(
+                                tag,
+                                entry.tagged_at(tag.name()),
+                                entry.expires_at(tag.name())
+                            )
+                        )
                     }
+                    println!();
                 }
             }
         }
-        Ok(())
+        result
+    }
+
+    fn get_effective(&mut self, opts: GetOpts) -> Result<()> {
+        let (entries, result) = if opts.glob {
+            let glob = self.glob(opts.paths, opts.glob_opts)?;
+            (self.client.inspect_effective_pattern(glob)?, Ok(()))
+        } else {
+            self.in_chunks(opts.paths, opts.stdin, opts.null, |paths| {
+                self.client.inspect_effective(paths)
+            })?
+        };
+
+        match self.format {
+            OutputFormat::Json | OutputFormat::Yaml => {
+                let entries: OrderedMap<_, _> = entries
+                    .into_iter()
+                    .map(|(path, tags)| (path, EffectiveOutput::from(tags)))
+                    .collect();
+                self.print_serialized(entries)?;
+            }
+            OutputFormat::Default => {
+                for (path, tags) in entries {
+                    print!("{}:", fmt::path(&path));
+                    for tag in &tags {
+                        match &tag.inherited_from {
+                            Some(from) => print!(" {}", fmt::inherited(&tag.tag, from)),
+                            None => print!(" {}", fmt::tag(&tag.tag)),
+                        }
+                    }
+                    println!();
+                }
+            }
+        }
+        result
     }
 
     fn rm(&mut self, opts: RmOpts) -> Result<()> {
@@ -250,31 +643,40 @@ impl App {
             .collect();
 
         if opts.glob {
-            let glob = self.glob(&opts.paths[0])?;
-            self.client
-                .untag_files_pattern(glob, tags)
-                .map_err(Error::from)
-                .map(|_| ())
-        } else {
-            self.client
-                .untag_files(opts.paths, tags)
-                .map_err(Error::from)
-                .map(|_| ())
+            let glob = self.glob(opts.paths, opts.glob_opts)?;
+            let changes = self.client.untag_files_pattern(glob, tags, self.dry_run)?;
+            return self.print_plan(changes);
         }
+        let (changes, result) = self.in_chunks(opts.paths, opts.stdin, opts.null, |paths| {
+            self.client.untag_files(paths, tags.clone(), self.dry_run)
+        })?;
+        self.print_plan(changes)?;
+        result
     }
 
     fn clear(&mut self, opts: ClearOpts) -> Result<()> {
         match opts.object {
-            ClearObject::Files { paths, glob } => {
-                if glob {
-                    let glob = self.glob(&paths[0])?;
-                    self.client.clear_files_pattern(glob)?;
+            ClearObject::Files {
+                paths,
+                glob,
+                glob_opts,
+                stdin,
+                null,
+            } => {
+                let (changes, result) = if glob {
+                    let glob = self.glob(paths, glob_opts)?;
+                    (self.client.clear_files_pattern(glob, self.dry_run)?, Ok(()))
                 } else {
-                    self.client.clear_files(paths)?;
-                }
+                    self.in_chunks(paths, stdin, null, |paths| {
+                        self.client.clear_files(paths, self.dry_run)
+                    })?
+                };
+                self.print_plan(changes)?;
+                result?;
             }
             ClearObject::Tags { names } => {
-                self.client.clear_tags(names)?;
+                let changes = self.client.clear_tags(names, self.dry_run)?;
+                self.print_plan(changes)?;
             }
             ClearObject::Cache => self.clear_cache()?,
         }
@@ -282,15 +684,83 @@ impl App {
     }
 
     fn search(&self, opts: SearchOpts) -> Result<()> {
-        let entries = self.client.search(opts.tags, opts.any)?;
+        let tagged_since = parse_time(opts.tagged_since)?;
+        let tagged_before = parse_time(opts.tagged_before)?;
+        let inherit = opts.inherit || self.inherit_tags;
+        // entries are stored with canonical paths so the directory has to be one as well
+        let under = opts.under.map(|dir| self.entry_path(dir));
+        let options = SearchOptions {
+            any: opts.any,
+            tagged_since,
+            tagged_before,
+            inherit,
+            filter: FileFilter {
+                under,
+                file_type: opts.file_type,
+                size: opts.size,
+                modified: opts.modified,
+                path_regex: opts.path_regex,
+            },
+            sort: opts.sort.into(),
+            page: opts.page.into(),
+        };
+        let found = self.client.search(opts.tags, options)?;
+        let paths = || -> Vec<_> {
+            found
+                .iter()
+                .map(|found| found.entry.path().to_path_buf())
+                .collect()
+        };
+        let summary = if let Some(command) = opts.exec {
+            let jobs = opts.jobs.unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(usize::from)
+                    .unwrap_or(1)
+            });
+            exec_each(&CommandTemplate::new(command), &paths(), jobs)
+        } else if let Some(command) = opts.exec_batch {
+            exec_batch(&CommandTemplate::new(command), &paths())
+        } else {
+            return self.print_search(found, inherit, opts.null);
+        };
+        if summary.failed > 0 {
+            return Err(AppError::Exec(summary.failed, summary.total).into());
+        }
+        Ok(())
+    }
+
+    /// Prints entries found by a search. With `inherit` JSON and YAML output also contains the
+    /// inherited tags of each entry, with `null` paths of the default output are separated with
+    /// NUL characters.
+    fn print_search(&self, found: Vec<SearchMatch>, inherit: bool, null: bool) -> Result<()> {
         match self.format {
+            OutputFormat::Json | OutputFormat::Yaml if inherit => {
+                let found: Vec<_> = found.into_iter().map(SearchOutput::from).collect();
+                self.print_serialized(found)?;
+            }
             OutputFormat::Json | OutputFormat::Yaml => {
-                let entries: Vec<_> = entries.into_iter().map(|e| e.into_path_buf()).collect();
+                let entries: Vec<_> = found
+                    .into_iter()
+                    .map(|found| found.entry.into_path_buf())
+                    .collect();
                 self.print_serialized(entries)?;
             }
+            OutputFormat::Default if null => {
+                let mut stdout = std::io::stdout().lock();
+                for found in found {
+                    stdout
+                        .write_all(&path_bytes(found.entry.path()))
+                        .and_then(|_| stdout.write_all(b"\0"))
+                        .map_err(AppError::WriteOutput)?;
+                }
+            }
             OutputFormat::Default => {
-                for entry in entries {
-                    println!("{}", fmt::path(entry.path()));
+                for found in found {
+                    if self.pretty {
+                        println!("{}", fmt::search_match(&found));
+                    } else {
+                        println!("{}", fmt::path(found.entry.path()));
+                    }
                 }
             }
         }
@@ -299,29 +769,534 @@ impl App {
 
     fn cp(&mut self, opts: CpOpts) -> Result<()> {
         if opts.glob {
-            let glob = self.glob(&opts.paths[0])?;
+            let glob = self.glob(opts.paths, opts.glob_opts)?;
+            let changes = self.client.copy_tags_pattern(
+                self.entry_path(&opts.input_path),
+                glob,
+                self.dry_run,
+            )?;
+            return self.print_plan(changes);
+        }
+        let (changes, result) = self.in_chunks(opts.paths, opts.stdin, opts.null, |paths| {
             self.client
-                .copy_tags_pattern(opts.input_path, glob)
-                .map_err(Error::from)
-                .map(|_| ())
+                .copy_tags(self.entry_path(&opts.input_path), paths, self.dry_run)
+        })?;
+        self.print_plan(changes)?;
+        result
+    }
+
+    fn edit(&mut self, opts: EditOpts) -> Result<()> {
+        let color = opts
+            .color
+            .map(parse_color)
+            .transpose()
+            .map_err(AppError::ParseColor)?;
+        let edit = TagEdit {
+            color,
+            description: opts.description,
+            icon: opts.icon,
+        };
+
+        let edits = self.client.edit_tag(opts.tag, edit, self.dry_run)?;
+        self.print_edit_plan(edits)
+    }
+
+    fn suggest(&self, opts: SuggestOpts) -> Result<()> {
+        let suggestions = self
+            .client
+            .suggest(self.entry_path(opts.path), opts.limit)?;
+        match self.format {
+            OutputFormat::Json | OutputFormat::Yaml => {
+                #[derive(Debug, Serialize)]
+                struct SuggestionOutput {
+                    tag: String,
+                    score: f64,
+                    co_occurrence: f64,
+                    siblings: f64,
+                    extension: f64,
+                }
+                let suggestions: Vec<_> = suggestions
+                    .into_iter()
+                    .map(|s| SuggestionOutput {
+                        tag: s.tag.into_name(),
+                        score: s.score,
+                        co_occurrence: s.co_occurrence,
+                        siblings: s.siblings,
+                        extension: s.extension,
+                    })
+                    .collect();
+                self.print_serialized(suggestions)
+            }
+            OutputFormat::Default => {
+                for suggestion in &suggestions {
+                    println!(
+                        "{} {}",
+                        fmt::tag(&suggestion.tag),
+                        format!("({:.2})", suggestion.score).dimmed()
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn doctor(&self) -> Result<()> {
+        let report = self.client.doctor()?;
+        match self.format {
+            OutputFormat::Json | OutputFormat::Yaml => {
+                #[derive(Debug, Serialize)]
+                struct InvalidName {
+                    tag: String,
+                    reason: String,
+                }
+                #[derive(Debug, Serialize)]
+                struct Report {
+                    near_duplicates: Vec<Vec<String>>,
+                    invalid: Vec<InvalidName>,
+                }
+                self.print_serialized(Report {
+                    near_duplicates: report.near_duplicates,
+                    invalid: report
+                        .invalid
+                        .into_iter()
+                        .map(|(tag, reason)| InvalidName { tag, reason })
+                        .collect(),
+                })
+            }
+            OutputFormat::Default => {
+                if report.near_duplicates.is_empty() && report.invalid.is_empty() {
+                    println!("No problems found");
+                    return Ok(());
+                }
+                for group in &report.near_duplicates {
+                    println!("near duplicates: {}", group.join(", "));
+                    let canonical = &group[0];
+                    for alias in &group[1..] {
+                        println!(
+                            "\t{}",
+                            format!("wutag alias add '{alias}' '{canonical}'").dimmed()
+                        );
+                    }
+                }
+                for (tag, reason) in &report.invalid {
+                    println!("invalid name: {tag} ({reason})");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn tag(&self, opts: TagOpts) -> Result<()> {
+        match opts.cmd {
+            TagCommand::Info { tag, top } => {
+                let info = self.client.tag_info(tag, top)?;
+                match self.format {
+                    OutputFormat::Json | OutputFormat::Yaml => {
+                        self.print_serialized(TagInfoOutput::from(info))
+                    }
+                    OutputFormat::Default => {
+                        println!("{}", fmt::tag_info(&info));
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+
+    fn undo(&mut self, opts: UndoOpts) -> Result<()> {
+        let changes = self.client.undo(opts.count, self.dry_run)?;
+        self.print_changes(changes)
+    }
+
+    fn redo(&mut self, opts: RedoOpts) -> Result<()> {
+        let changes = self.client.redo(opts.count, self.dry_run)?;
+        self.print_changes(changes)
+    }
+
+    fn history(&self) -> Result<()> {
+        let (applied, undone) = self.client.history()?;
+        match self.format {
+            OutputFormat::Json | OutputFormat::Yaml => {
+                #[derive(Debug, Serialize)]
+                struct History {
+                    applied: Vec<HistoryOutput>,
+                    undone: Vec<HistoryOutput>,
+                }
+                let history = History {
+                    applied: applied.into_iter().map(HistoryOutput::from).collect(),
+                    undone: undone.into_iter().map(HistoryOutput::from).collect(),
+                };
+                self.print_serialized(history)?;
+            }
+            OutputFormat::Default => {
+                for (i, entry) in applied.iter().enumerate() {
+                    println!("{}: {} {}", i + 1, entry.request, entry.timestamp);
+                    for change in &entry.changes {
+                        println!("\t{}", fmt::change(change));
+                    }
+                }
+                if !undone.is_empty() {
+                    println!("undone:");
+                    for entry in &undone {
+                        println!("   {} {}", entry.request, entry.timestamp);
+                        for change in &entry.changes {
+                            println!("\t{}", fmt::change(change));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn log(&self, opts: LogOpts) -> Result<()> {
+        let filter = AuditFilter {
+            since: parse_time(opts.since)?,
+            path: opts.path.map(|path| self.entry_path(path)),
+            tag: opts.tag,
+        };
+        let entries = self.client.audit_log(filter)?;
+        match self.format {
+            OutputFormat::Json | OutputFormat::Yaml => self.print_serialized(entries)?,
+            OutputFormat::Default => {
+                for entry in &entries {
+                    println!("{}", fmt::audit_entry(entry));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn rules(&self, opts: RulesOpts) -> Result<()> {
+        match opts.cmd {
+            RulesCommand::Test { path } => {
+                let matches = self.client.test_rules(self.base_dir.join(path))?;
+                match self.format {
+                    OutputFormat::Json | OutputFormat::Yaml => self.print_serialized(matches)?,
+                    OutputFormat::Default => {
+                        for m in &matches {
+                            println!(
+                                "{} rule #{}: {}",
+                                fmt::path(&m.folder),
+                                m.rule + 1,
+                                m.tags.join(" ")
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn implies(&self, opts: ImpliesOpts) -> Result<()> {
+        match opts.cmd {
+            ImpliesCommand::Add { tag, implies } => self
+                .client
+                .add_implication(tag, implies, self.dry_run)
+                .and_then(|edits| self.print_edit_plan(edits)),
+            ImpliesCommand::Rm { tag, implies } => self
+                .client
+                .remove_implication(tag, implies, self.dry_run)
+                .and_then(|edits| self.print_edit_plan(edits)),
+            ImpliesCommand::List => {
+                let implications = self.client.list_implications()?;
+                match self.format {
+                    OutputFormat::Json | OutputFormat::Yaml => {
+                        #[derive(Debug, Serialize)]
+                        struct Implication {
+                            tag: String,
+                            implies: String,
+                        }
+                        let implications: Vec<_> = implications
+                            .into_iter()
+                            .map(|(tag, implies)| Implication { tag, implies })
+                            .collect();
+                        self.print_serialized(implications)
+                    }
+                    OutputFormat::Default => {
+                        for (tag, implies) in implications {
+                            println!("{tag} -> {implies}");
+                        }
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+
+    fn alias(&self, opts: AliasOpts) -> Result<()> {
+        match opts.cmd {
+            AliasCommand::Add { alias, tag } => self
+                .client
+                .add_alias(alias, tag, self.dry_run)
+                .and_then(|changes| self.print_changes(changes)),
+            AliasCommand::Rm { alias } => self
+                .client
+                .remove_alias(alias, self.dry_run)
+                .and_then(|edits| self.print_edit_plan(edits)),
+            AliasCommand::List => {
+                let aliases = self.client.list_aliases()?;
+                match self.format {
+                    OutputFormat::Json | OutputFormat::Yaml => {
+                        #[derive(Debug, Serialize)]
+                        struct Alias {
+                            alias: String,
+                            tag: String,
+                        }
+                        let aliases: Vec<_> = aliases
+                            .into_iter()
+                            .map(|(alias, tag)| Alias { alias, tag })
+                            .collect();
+                        self.print_serialized(aliases)
+                    }
+                    OutputFormat::Default => {
+                        for (alias, tag) in aliases {
+                            println!("{alias} = {tag}");
+                        }
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+
+    fn saved(&self, opts: SavedOpts) -> Result<()> {
+        match opts.cmd {
+            SavedCommand::Add { name, query } => self
+                .client
+                .add_saved_search(name, query, self.dry_run)
+                .and_then(|edits| self.print_edit_plan(edits)),
+            SavedCommand::Run { name, sort, page } => {
+                let query = self
+                    .client
+                    .list_saved_searches()?
+                    .into_iter()
+                    .find(|(saved, _)| *saved == name)
+                    .map(|(_, query)| query)
+                    .ok_or_else(|| AppError::Search(format!("{name} is not a saved search")))?;
+                let inherit = Query::parse(&query)
+                    .map(|query| query.inherit)
+                    .unwrap_or_default();
+                let options = SearchOptions {
+                    sort: sort.into(),
+                    page: page.into(),
+                    ..Default::default()
+                };
+                let found = self.client.search([name], options)?;
+                self.print_search(found, inherit, false)
+            }
+            SavedCommand::Rm { name } => self
+                .client
+                .remove_saved_search(name, self.dry_run)
+                .and_then(|edits| self.print_edit_plan(edits)),
+            SavedCommand::List => {
+                let saved = self.client.list_saved_searches()?;
+                match self.format {
+                    OutputFormat::Json | OutputFormat::Yaml => {
+                        self.print_serialized(saved.into_iter().collect::<OrderedMap<_, _>>())
+                    }
+                    OutputFormat::Default => {
+                        for (name, query) in saved {
+                            println!("{name} = {query}");
+                        }
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+
+    fn export(&self, opts: ExportOpts) -> Result<()> {
+        let export = self.client.export()?;
+        let output = match self.format {
+            OutputFormat::Yaml => {
+                serde_yaml::to_string(&export).map_err(AppError::SerializeYamlOutput)?
+            }
+            OutputFormat::Json | OutputFormat::Default => {
+                serde_json::to_string_pretty(&export).map_err(AppError::SerializeJsonOutput)?
+            }
+        };
+        match opts.path {
+            Some(path) => {
+                std::fs::write(&path, output).map_err(|e| AppError::WriteFile(path, e).into())
+            }
+            None => {
+                println!("{output}");
+                Ok(())
+            }
+        }
+    }
+
+    fn import(&self, opts: ImportOpts) -> Result<()> {
+        let data = std::fs::read_to_string(&opts.path)
+            .map_err(|e| AppError::ReadFile(opts.path.clone(), e))?;
+        // YAML is a superset of JSON so both formats of the export can be parsed as YAML
+        let data = serde_yaml::from_str(&data).map_err(|e| AppError::ParseImport(opts.path, e))?;
+        let report = self.client.import(data, self.dry_run)?;
+        self.print_plan(report.changes)?;
+        if !report.edits.is_empty() {
+            self.print_edit_plan(report.edits)?;
+        }
+        if report.errors.is_empty() {
+            return Ok(());
+        }
+        // the rest of the import was applied, only the listed items failed
+        Err(AppError::Import(error_list(&report.errors)).into())
+    }
+
+    fn view(&self, opts: ViewOpts) -> Result<()> {
+        let report = match opts.cmd {
+            ViewCommand::Materialize { dir, query } => {
+                self.client
+                    .materialize_view(self.base_dir.join(dir), query, self.dry_run)?
+            }
+            ViewCommand::Clean { dir } => self
+                .client
+                .clean_view(self.base_dir.join(dir), self.dry_run)?,
+        };
+        if let OutputFormat::Json | OutputFormat::Yaml = self.format {
+            return self.print_serialized(report);
+        }
+        if self.dry_run {
+            fmt::view_report(&report)
+                .into_iter()
+                .for_each(|line| println!("{line}"));
         } else {
-            self.client
-                .copy_tags(opts.input_path, opts.paths)
-                .map_err(Error::from)
-                .map(|_| ())
+            let skipped = ViewReport {
+                skipped: report.skipped,
+                ..Default::default()
+            };
+            fmt::view_report(&skipped)
+                .into_iter()
+                .for_each(|line| eprintln!("{line}"));
+        }
+        for e in &report.errors {
+            eprintln!("{e}");
         }
+        Ok(())
     }
 
-    fn edit(&mut self, opts: EditOpts) -> Result<()> {
-        let c = parse_color(&opts.color).map_err(AppError::ParseColor)?;
+    fn complete_tags(&self) -> Result<()> {
+        let mut names: Vec<_> = self
+            .client
+            .list_tags(false, Page::default())?
+            .into_iter()
+            .map(|(tag, _)| tag.into_name())
+            .chain(
+                self.client
+                    .list_aliases()?
+                    .into_iter()
+                    .map(|(alias, _)| alias),
+            )
+            .collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            println!("{name}");
+        }
+        Ok(())
+    }
+
+    /// Calls `f` with chunks of the `paths` and of paths read from the standard input if `stdin`
+    /// or `null` is set or one of the paths is `-`. With `null` the read paths are separated by
+    /// NUL characters instead of newlines. Returns results of all chunks together with the result
+    /// of the whole operation which fails if any of the chunks failed. Failed chunks don't stop
+    /// the remaining ones from being processed.
+    fn in_chunks<T>(
+        &self,
+        paths: Vec<String>,
+        stdin: bool,
+        null: bool,
+        mut f: impl FnMut(Vec<PathBuf>) -> Result<Vec<T>>,
+    ) -> Result<(Vec<T>, Result<()>)> {
+        let stdin = stdin || null || paths.iter().any(|path| path == STDIN_ARG);
+        let paths = paths
+            .into_iter()
+            .filter(|path| path != STDIN_ARG)
+            .map(PathBuf::from)
+            .collect();
+        let separator = if null { b'\0' } else { b'\n' };
+        let input = stdin.then(|| std::io::stdin().lock());
+
+        let mut results = Vec::new();
+        let mut errors = Vec::new();
+        let mut total = 0;
+        // all chunks are undone together
+        self.client.in_operation(|| -> Result<()> {
+            for chunk in path_chunks(paths, input, separator) {
+                let chunk = chunk
+                    .map_err(AppError::ReadStdin)?
+                    .into_iter()
+                    .map(|path| self.entry_path(path))
+                    .collect();
+                total += 1;
+                match f(chunk) {
+                    Ok(chunk_results) => results.extend(chunk_results),
+                    Err(e) => errors.push(e),
+                }
+            }
+            Ok(())
+        })?;
+        if total == 0 {
+            // let the daemon report that there is nothing to do
+            return f(Vec::new()).map(|results| (results, Ok(())));
+        }
 
-        self.client
-            .edit_tag(opts.tag, c)
-            .map_err(Error::from)
-            .map(|_| ())
+        let result = match errors.len() {
+            0 => Ok(()),
+            1 if total == 1 => Err(errors.remove(0)),
+            failed => Err(AppError::Chunks {
+                failed,
+                total,
+                errors: error_list(&errors),
+            }
+            .into()),
+        };
+        Ok((results, result))
     }
 
-    fn glob(&self, pattern: impl Into<String>) -> Result<Glob> {
-        Glob::new(pattern.into(), Some(self.base_dir.clone()), self.max_depth).map_err(Error::Glob)
+    /// Returns the path of an entry as stored by the daemon with relative paths resolved against
+    /// the base directory.
+    fn entry_path(&self, path: impl AsRef<Path>) -> PathBuf {
+        canonical_path(self.base_dir.join(path))
+    }
+
+    fn glob(&self, patterns: Vec<String>, opts: GlobOpts) -> Result<Glob> {
+        let glob = Glob::new(patterns, Some(self.base_dir.clone()), self.max_depth)
+            .map_err(Error::Glob)?;
+        Ok(Glob {
+            excludes: opts.exclude,
+            hidden: !opts.no_hidden,
+            ignore_files: opts.ignore_files,
+            follow_links: opts.follow,
+            ..glob
+        })
+    }
+}
+
+/// Formats the `errors` as a list with each error on its own line.
+fn error_list<E: std::fmt::Display>(errors: &[E]) -> String {
+    errors.iter().map(|e| format!("\n - {e}")).collect()
+}
+
+fn parse_time(time: Option<String>) -> Result<Option<DateTime<Utc>>> {
+    time.map(|time| wutag_core::time::parse_time(&time).map_err(|e| AppError::ParseTime(e).into()))
+        .transpose()
+}
+
+/// Returns the raw bytes of the `path` so that paths that aren't valid UTF-8 are printed unchanged.
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+/// Returns the `path` encoded as UTF-8, other platforms don't expose raw bytes of paths.
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        Cow::Borrowed(path) => Cow::Borrowed(path.as_bytes()),
+        Cow::Owned(path) => Cow::Owned(path.into_bytes()),
     }
 }
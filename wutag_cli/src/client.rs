@@ -1,13 +1,24 @@
 #![allow(dead_code)]
 use crate::Result;
-use wutag_core::color::Color;
+use chrono::{DateTime, Utc};
+use wutag_core::audit::{AuditEntry, AuditFilter};
 use wutag_core::glob::Glob;
-use wutag_core::registry::EntryData;
+use wutag_core::journal::JournalEntry;
+use wutag_core::registry::{
+    EffectiveTag, EntryData, RegistryExport, SearchMatch, Suggestion, TagChange, TagInfo,
+};
+use wutag_core::rules::RuleMatch;
+use wutag_core::sort::{Page, Sort};
 use wutag_core::tag::Tag;
-use wutag_ipc::{IpcClient, Request, Response};
+use wutag_core::view::ViewReport;
+use wutag_ipc::{
+    AutoTagOptions, AutoTagReport, DoctorReport, ImportReport, IpcClient, Request, Response,
+    SearchOptions, TagEdit,
+};
 
-use std::collections::HashMap;
-use std::path::Path;
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error as ThisError;
 
 #[derive(Debug, ThisError)]
@@ -36,28 +47,97 @@ pub enum ClientError {
     Ping(String),
     #[error("failed to clear cache - {0}")]
     ClearCache(String),
+    #[error("failed to undo - {0}")]
+    Undo(String),
+    #[error("failed to redo - {0}")]
+    Redo(String),
+    #[error("failed to read history - {0}")]
+    History(String),
+    #[error("failed to read audit log - {0}")]
+    AuditLog(String),
+    #[error("failed to test rules - {0}")]
+    TestRules(String),
+    #[error("failed to automatically tag files - {0}")]
+    AutoTag(String),
+    #[error("failed to add implication - {0}")]
+    AddImplication(String),
+    #[error("failed to remove implication - {0}")]
+    RemoveImplication(String),
+    #[error("failed to list implications - {0}")]
+    ListImplications(String),
+    #[error("failed to add alias - {0}")]
+    AddAlias(String),
+    #[error("failed to remove alias - {0}")]
+    RemoveAlias(String),
+    #[error("failed to list aliases - {0}")]
+    ListAliases(String),
+    #[error("failed to get tag info - {0}")]
+    TagInfo(String),
+    #[error("failed to check tags - {0}")]
+    Doctor(String),
+    #[error("failed to suggest tags - {0}")]
+    Suggest(String),
+    #[error("failed to add saved search - {0}")]
+    AddSavedSearch(String),
+    #[error("failed to remove saved search - {0}")]
+    RemoveSavedSearch(String),
+    #[error("failed to list saved searches - {0}")]
+    ListSavedSearches(String),
+    #[error("failed to export tags - {0}")]
+    Export(String),
+    #[error("failed to import tags - {0}")]
+    Import(String),
+    #[error("failed to materialize view - {0}")]
+    MaterializeView(String),
+    #[error("failed to clean view - {0}")]
+    CleanView(String),
     #[error("unexpected response {0:?}")]
     UnexpectedResponse(HandledResponse),
 }
 
 #[derive(Debug)]
 pub enum HandledResponse {
-    TagFiles,
-    UntagFiles,
-    EditTag,
-    CopyTags,
-    ClearFiles,
-    ClearTags,
-    ListTags(HashMap<Tag, Vec<EntryData>>),
+    TagFiles(Vec<TagChange>),
+    UntagFiles(Vec<TagChange>),
+    EditTag(Vec<AuditEntry>),
+    CopyTags(Vec<TagChange>),
+    ClearFiles(Vec<TagChange>),
+    ClearTags(Vec<TagChange>),
+    ListTags(Vec<(Tag, Vec<EntryData>)>),
     ListFiles(Vec<(EntryData, Vec<Tag>)>),
     InspectFiles(Vec<(EntryData, Vec<Tag>)>),
-    Search(Vec<EntryData>),
+    InspectEffective(Vec<(PathBuf, Vec<EffectiveTag>)>),
+    Search(Vec<SearchMatch>),
     Ping,
-    ClearCache,
+    ClearCache(Vec<AuditEntry>),
+    Undo(Vec<TagChange>),
+    Redo(Vec<TagChange>),
+    History((Vec<JournalEntry>, Vec<JournalEntry>)),
+    AuditLog(Vec<AuditEntry>),
+    TestRules(Vec<RuleMatch>),
+    AutoTag(AutoTagReport),
+    AddImplication(Vec<AuditEntry>),
+    RemoveImplication(Vec<AuditEntry>),
+    ListImplications(Vec<(String, String)>),
+    AddAlias(Vec<TagChange>),
+    RemoveAlias(Vec<AuditEntry>),
+    ListAliases(Vec<(String, String)>),
+    TagInfo(Box<TagInfo>),
+    Doctor(DoctorReport),
+    Suggest(Vec<Suggestion>),
+    AddSavedSearch(Vec<AuditEntry>),
+    RemoveSavedSearch(Vec<AuditEntry>),
+    ListSavedSearches(Vec<(String, String)>),
+    Export(Box<RegistryExport>),
+    Import(ImportReport),
+    MaterializeView(Box<ViewReport>),
+    CleanView(Box<ViewReport>),
 }
 
 pub struct Client {
     client: IpcClient,
+    /// Id of the operation the sent requests are a part of.
+    operation: Cell<Option<u64>>,
 }
 
 fn map_response(response: Response) -> Result<HandledResponse> {
@@ -68,22 +148,22 @@ fn map_response(response: Response) -> Result<HandledResponse> {
     match response {
         Response::TagFiles(inner) => inner
             .to_result(|e| ClientError::TagFiles(format_multiple_errors(e)).into())
-            .map(|_| HandledResponse::TagFiles),
+            .map(HandledResponse::TagFiles),
         Response::UntagFiles(inner) => inner
             .to_result(|e| ClientError::UntagFiles(format_multiple_errors(e)).into())
-            .map(|_| HandledResponse::UntagFiles),
+            .map(HandledResponse::UntagFiles),
         Response::EditTag(inner) => inner
             .to_result(|e| ClientError::EditTag(e).into())
-            .map(|_| HandledResponse::EditTag),
+            .map(HandledResponse::EditTag),
         Response::CopyTags(inner) => inner
             .to_result(|e| ClientError::CopyTags(format_multiple_errors(e)).into())
-            .map(|_| HandledResponse::CopyTags),
+            .map(HandledResponse::CopyTags),
         Response::ClearFiles(inner) => inner
             .to_result(|e| ClientError::ClearFiles(format_multiple_errors(e)).into())
-            .map(|_| HandledResponse::ClearFiles),
+            .map(HandledResponse::ClearFiles),
         Response::ClearTags(inner) => inner
             .to_result(|e| ClientError::ClearTags(format_multiple_errors(e)).into())
-            .map(|_| HandledResponse::ClearTags),
+            .map(HandledResponse::ClearTags),
         Response::ListFiles(inner) => inner
             .to_result(|e| ClientError::ListFiles(e).into())
             .map(HandledResponse::ListFiles),
@@ -93,6 +173,9 @@ fn map_response(response: Response) -> Result<HandledResponse> {
         Response::InspectFiles(inner) => inner
             .to_result(|e| ClientError::InspectFiles(e).into())
             .map(HandledResponse::InspectFiles),
+        Response::InspectEffective(inner) => inner
+            .to_result(|e| ClientError::InspectFiles(e).into())
+            .map(HandledResponse::InspectEffective),
         Response::Search(inner) => inner
             .to_result(|e| ClientError::Search(e).into())
             .map(HandledResponse::Search),
@@ -101,7 +184,73 @@ fn map_response(response: Response) -> Result<HandledResponse> {
             .map(|_| HandledResponse::Ping),
         Response::ClearCache(inner) => inner
             .to_result(|e| ClientError::ClearCache(e).into())
-            .map(|_| HandledResponse::ClearCache),
+            .map(HandledResponse::ClearCache),
+        Response::Undo(inner) => inner
+            .to_result(|e| ClientError::Undo(format_multiple_errors(e)).into())
+            .map(HandledResponse::Undo),
+        Response::Redo(inner) => inner
+            .to_result(|e| ClientError::Redo(format_multiple_errors(e)).into())
+            .map(HandledResponse::Redo),
+        Response::History(inner) => inner
+            .to_result(|e| ClientError::History(e).into())
+            .map(HandledResponse::History),
+        Response::AuditLog(inner) => inner
+            .to_result(|e| ClientError::AuditLog(e).into())
+            .map(HandledResponse::AuditLog),
+        Response::TestRules(inner) => inner
+            .to_result(|e| ClientError::TestRules(e).into())
+            .map(HandledResponse::TestRules),
+        Response::AutoTag(inner) => inner
+            .to_result(|e| ClientError::AutoTag(format_multiple_errors(e)).into())
+            .map(HandledResponse::AutoTag),
+        Response::AddImplication(inner) => inner
+            .to_result(|e| ClientError::AddImplication(e).into())
+            .map(HandledResponse::AddImplication),
+        Response::RemoveImplication(inner) => inner
+            .to_result(|e| ClientError::RemoveImplication(e).into())
+            .map(HandledResponse::RemoveImplication),
+        Response::ListImplications(inner) => inner
+            .to_result(|e| ClientError::ListImplications(e).into())
+            .map(HandledResponse::ListImplications),
+        Response::AddAlias(inner) => inner
+            .to_result(|e| ClientError::AddAlias(format_multiple_errors(e)).into())
+            .map(HandledResponse::AddAlias),
+        Response::RemoveAlias(inner) => inner
+            .to_result(|e| ClientError::RemoveAlias(e).into())
+            .map(HandledResponse::RemoveAlias),
+        Response::ListAliases(inner) => inner
+            .to_result(|e| ClientError::ListAliases(e).into())
+            .map(HandledResponse::ListAliases),
+        Response::TagInfo(inner) => inner
+            .to_result(|e| ClientError::TagInfo(e).into())
+            .map(HandledResponse::TagInfo),
+        Response::Doctor(inner) => inner
+            .to_result(|e| ClientError::Doctor(e).into())
+            .map(HandledResponse::Doctor),
+        Response::Suggest(inner) => inner
+            .to_result(|e| ClientError::Suggest(e).into())
+            .map(HandledResponse::Suggest),
+        Response::MaterializeView(inner) => inner
+            .to_result(|e| ClientError::MaterializeView(e).into())
+            .map(HandledResponse::MaterializeView),
+        Response::CleanView(inner) => inner
+            .to_result(|e| ClientError::CleanView(e).into())
+            .map(HandledResponse::CleanView),
+        Response::AddSavedSearch(inner) => inner
+            .to_result(|e| ClientError::AddSavedSearch(e).into())
+            .map(HandledResponse::AddSavedSearch),
+        Response::RemoveSavedSearch(inner) => inner
+            .to_result(|e| ClientError::RemoveSavedSearch(e).into())
+            .map(HandledResponse::RemoveSavedSearch),
+        Response::ListSavedSearches(inner) => inner
+            .to_result(|e| ClientError::ListSavedSearches(e).into())
+            .map(HandledResponse::ListSavedSearches),
+        Response::Export(inner) => inner
+            .to_result(|e| ClientError::Export(e).into())
+            .map(HandledResponse::Export),
+        Response::Import(inner) => inner
+            .to_result(|e| ClientError::Import(e).into())
+            .map(HandledResponse::Import),
     }
 }
 
@@ -109,66 +258,118 @@ impl Client {
     pub fn new(socket: impl Into<String>) -> Self {
         Self {
             client: IpcClient::new(socket),
+            operation: Cell::new(None),
         }
     }
 
-    fn tag_files_impl(&self, request: Request) -> Result<()> {
+    /// Sends the `request` to the daemon as a part of the current operation if there is one.
+    fn request(&self, request: Request) -> wutag_ipc::Result<Response> {
+        match self.operation.get() {
+            Some(operation) => self.client.request(Request::Part {
+                operation,
+                request: Box::new(request),
+            }),
+            None => self.client.request(request),
+        }
+    }
+
+    /// Sends all requests made by `f` as parts of a single operation so that the daemon records
+    /// the changes they make as a single entry of the history.
+    pub fn in_operation<T>(&self, f: impl FnOnce() -> T) -> T {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let operation = (now.as_nanos() as u64) ^ u64::from(std::process::id());
+        self.operation.set(Some(operation));
+        let result = f();
+        self.operation.set(None);
+        result
+    }
+
+    fn tag_files_impl(&self, request: Request) -> Result<Vec<TagChange>> {
         debug_assert!(matches!(
             request,
             Request::TagFiles { .. } | Request::TagFilesPattern { .. }
         ));
-        self.client
-            .request(request)
+        self.request(request)
             .map_err(|e| ClientError::TagFiles(e.to_string()).into())
             .and_then(map_response)
-            .map(|_| ())
+            .and_then(|r| {
+                if let HandledResponse::TagFiles(changes) = r {
+                    Ok(changes)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
     }
 
     pub fn tag_files<P: AsRef<Path>>(
         &self,
         files: impl IntoIterator<Item = P>,
         tags: impl IntoIterator<Item = Tag>,
-    ) -> Result<()> {
+        expires: Option<DateTime<Utc>>,
+        implied: bool,
+        dry_run: bool,
+    ) -> Result<Vec<TagChange>> {
         self.tag_files_impl(Request::TagFiles {
             files: files
                 .into_iter()
                 .map(|p| p.as_ref().to_path_buf())
                 .collect(),
             tags: tags.into_iter().collect(),
+            expires,
+            implied,
+            dry_run,
         })
     }
 
-    pub fn tag_files_pattern(&self, glob: Glob, tags: impl IntoIterator<Item = Tag>) -> Result<()> {
+    pub fn tag_files_pattern(
+        &self,
+        glob: Glob,
+        tags: impl IntoIterator<Item = Tag>,
+        expires: Option<DateTime<Utc>>,
+        implied: bool,
+        dry_run: bool,
+    ) -> Result<Vec<TagChange>> {
         self.tag_files_impl(Request::TagFilesPattern {
             glob,
-
             tags: tags.into_iter().collect(),
+            expires,
+            implied,
+            dry_run,
         })
     }
 
-    fn untag_files_impl(&self, request: Request) -> Result<()> {
+    fn untag_files_impl(&self, request: Request) -> Result<Vec<TagChange>> {
         debug_assert!(matches!(
             request,
             Request::UntagFiles { .. } | Request::UntagFilesPattern { .. }
         ));
-        self.client
-            .request(request)
+        self.request(request)
             .map_err(|e| ClientError::UntagFiles(e.to_string()).into())
             .and_then(map_response)
-            .map(|_| ())
+            .and_then(|r| {
+                if let HandledResponse::UntagFiles(changes) = r {
+                    Ok(changes)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
     }
 
     pub fn untag_files<P: AsRef<Path>>(
         &self,
         files: impl IntoIterator<Item = P>,
         tags: impl IntoIterator<Item = Tag>,
-    ) -> Result<()> {
+        dry_run: bool,
+    ) -> Result<Vec<TagChange>> {
         self.untag_files_impl(Request::UntagFiles {
             files: files
                 .into_iter()
                 .map(|p| p.as_ref().to_path_buf())
                 .collect(),
             tags: tags.into_iter().collect(),
+            dry_run,
         })
     }
 
@@ -176,96 +377,386 @@ impl Client {
         &self,
         glob: Glob,
         tags: impl IntoIterator<Item = Tag>,
-    ) -> Result<()> {
+        dry_run: bool,
+    ) -> Result<Vec<TagChange>> {
         self.untag_files_impl(Request::UntagFilesPattern {
             glob,
             tags: tags.into_iter().collect(),
+            dry_run,
         })
     }
 
-    pub fn edit_tag(&self, tag: String, color: Color) -> Result<()> {
-        self.client
-            .request(Request::EditTag { tag, color })
+    pub fn edit_tag(&self, tag: String, edit: TagEdit, dry_run: bool) -> Result<Vec<AuditEntry>> {
+        self.request(Request::EditTag { tag, edit, dry_run })
             .map_err(|e| ClientError::EditTag(e.to_string()).into())
             .and_then(map_response)
-            .map(|_| ())
+            .and_then(|r| {
+                if let HandledResponse::EditTag(edits) = r {
+                    Ok(edits)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn add_implication(
+        &self,
+        tag: String,
+        implies: String,
+        dry_run: bool,
+    ) -> Result<Vec<AuditEntry>> {
+        self.request(Request::AddImplication {
+            tag,
+            implies,
+            dry_run,
+        })
+        .map_err(|e| ClientError::AddImplication(e.to_string()).into())
+        .and_then(map_response)
+        .and_then(|r| {
+            if let HandledResponse::AddImplication(edits) = r {
+                Ok(edits)
+            } else {
+                Err(ClientError::UnexpectedResponse(r).into())
+            }
+        })
+    }
+
+    pub fn remove_implication(
+        &self,
+        tag: String,
+        implies: String,
+        dry_run: bool,
+    ) -> Result<Vec<AuditEntry>> {
+        self.request(Request::RemoveImplication {
+            tag,
+            implies,
+            dry_run,
+        })
+        .map_err(|e| ClientError::RemoveImplication(e.to_string()).into())
+        .and_then(map_response)
+        .and_then(|r| {
+            if let HandledResponse::RemoveImplication(edits) = r {
+                Ok(edits)
+            } else {
+                Err(ClientError::UnexpectedResponse(r).into())
+            }
+        })
+    }
+
+    pub fn list_implications(&self) -> Result<Vec<(String, String)>> {
+        self.request(Request::ListImplications)
+            .map_err(|e| ClientError::ListImplications(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::ListImplications(implications) = r {
+                    Ok(implications)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn add_alias(&self, alias: String, tag: String, dry_run: bool) -> Result<Vec<TagChange>> {
+        self.request(Request::AddAlias {
+            alias,
+            tag,
+            dry_run,
+        })
+        .map_err(|e| ClientError::AddAlias(e.to_string()).into())
+        .and_then(map_response)
+        .and_then(|r| {
+            if let HandledResponse::AddAlias(changes) = r {
+                Ok(changes)
+            } else {
+                Err(ClientError::UnexpectedResponse(r).into())
+            }
+        })
+    }
+
+    pub fn remove_alias(&self, alias: String, dry_run: bool) -> Result<Vec<AuditEntry>> {
+        self.request(Request::RemoveAlias { alias, dry_run })
+            .map_err(|e| ClientError::RemoveAlias(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::RemoveAlias(edits) = r {
+                    Ok(edits)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
     }
 
-    fn copy_tags_impl(&self, request: Request) -> Result<()> {
+    pub fn list_aliases(&self) -> Result<Vec<(String, String)>> {
+        self.request(Request::ListAliases)
+            .map_err(|e| ClientError::ListAliases(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::ListAliases(aliases) = r {
+                    Ok(aliases)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    /// Saves the `query` under the `name`.
+    pub fn add_saved_search(
+        &self,
+        name: String,
+        query: String,
+        dry_run: bool,
+    ) -> Result<Vec<AuditEntry>> {
+        self.request(Request::AddSavedSearch {
+            name,
+            query,
+            dry_run,
+        })
+        .map_err(|e| ClientError::AddSavedSearch(e.to_string()).into())
+        .and_then(map_response)
+        .and_then(|r| {
+            if let HandledResponse::AddSavedSearch(edits) = r {
+                Ok(edits)
+            } else {
+                Err(ClientError::UnexpectedResponse(r).into())
+            }
+        })
+    }
+
+    pub fn remove_saved_search(&self, name: String, dry_run: bool) -> Result<Vec<AuditEntry>> {
+        self.request(Request::RemoveSavedSearch { name, dry_run })
+            .map_err(|e| ClientError::RemoveSavedSearch(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::RemoveSavedSearch(edits) = r {
+                    Ok(edits)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn list_saved_searches(&self) -> Result<Vec<(String, String)>> {
+        self.request(Request::ListSavedSearches)
+            .map_err(|e| ClientError::ListSavedSearches(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::ListSavedSearches(saved) = r {
+                    Ok(saved)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn export(&self) -> Result<RegistryExport> {
+        self.request(Request::Export)
+            .map_err(|e| ClientError::Export(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::Export(export) = r {
+                    Ok(*export)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn import(&self, data: RegistryExport, dry_run: bool) -> Result<ImportReport> {
+        self.request(Request::Import { data, dry_run })
+            .map_err(|e| ClientError::Import(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::Import(report) = r {
+                    Ok(report)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn tag_info(&self, tag: String, top: usize) -> Result<TagInfo> {
+        self.request(Request::TagInfo { tag, top })
+            .map_err(|e| ClientError::TagInfo(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::TagInfo(info) = r {
+                    Ok(*info)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn doctor(&self) -> Result<DoctorReport> {
+        self.request(Request::Doctor)
+            .map_err(|e| ClientError::Doctor(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::Doctor(report) = r {
+                    Ok(report)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn suggest<P: AsRef<Path>>(&self, path: P, limit: usize) -> Result<Vec<Suggestion>> {
+        self.request(Request::Suggest {
+            path: path.as_ref().to_path_buf(),
+            limit,
+        })
+        .map_err(|e| ClientError::Suggest(e.to_string()).into())
+        .and_then(map_response)
+        .and_then(|r| {
+            if let HandledResponse::Suggest(suggestions) = r {
+                Ok(suggestions)
+            } else {
+                Err(ClientError::UnexpectedResponse(r).into())
+            }
+        })
+    }
+
+    pub fn materialize_view<P: AsRef<Path>>(
+        &self,
+        path: P,
+        query: Option<String>,
+        dry_run: bool,
+    ) -> Result<ViewReport> {
+        self.request(Request::MaterializeView {
+            path: path.as_ref().to_path_buf(),
+            query,
+            dry_run,
+        })
+        .map_err(|e| ClientError::MaterializeView(e.to_string()).into())
+        .and_then(map_response)
+        .and_then(|r| {
+            if let HandledResponse::MaterializeView(report) = r {
+                Ok(*report)
+            } else {
+                Err(ClientError::UnexpectedResponse(r).into())
+            }
+        })
+    }
+
+    pub fn clean_view<P: AsRef<Path>>(&self, path: P, dry_run: bool) -> Result<ViewReport> {
+        self.request(Request::CleanView {
+            path: path.as_ref().to_path_buf(),
+            dry_run,
+        })
+        .map_err(|e| ClientError::CleanView(e.to_string()).into())
+        .and_then(map_response)
+        .and_then(|r| {
+            if let HandledResponse::CleanView(report) = r {
+                Ok(*report)
+            } else {
+                Err(ClientError::UnexpectedResponse(r).into())
+            }
+        })
+    }
+
+    fn copy_tags_impl(&self, request: Request) -> Result<Vec<TagChange>> {
         debug_assert!(matches!(
             request,
             Request::CopyTags { .. } | Request::CopyTagsPattern { .. }
         ));
-        self.client
-            .request(request)
+        self.request(request)
             .map_err(|e| ClientError::CopyTags(e.to_string()).into())
             .and_then(map_response)
-            .map(|_| ())
+            .and_then(|r| {
+                if let HandledResponse::CopyTags(changes) = r {
+                    Ok(changes)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
     }
 
     pub fn copy_tags<P1: AsRef<Path>, P2: AsRef<Path>>(
         &self,
         source: P1,
         target: impl IntoIterator<Item = P2>,
-    ) -> Result<()> {
-        self.client
-            .request(Request::CopyTags {
-                source: source.as_ref().to_path_buf(),
-                target: target
-                    .into_iter()
-                    .map(|p| p.as_ref().to_path_buf())
-                    .collect(),
-            })
-            .map_err(|e| ClientError::CopyTags(e.to_string()).into())
-            .and_then(map_response)
-            .map(|_| ())
+        dry_run: bool,
+    ) -> Result<Vec<TagChange>> {
+        self.copy_tags_impl(Request::CopyTags {
+            source: source.as_ref().to_path_buf(),
+            target: target
+                .into_iter()
+                .map(|p| p.as_ref().to_path_buf())
+                .collect(),
+            dry_run,
+        })
     }
 
-    pub fn copy_tags_pattern(&self, source: impl AsRef<Path>, glob: Glob) -> Result<()> {
+    pub fn copy_tags_pattern(
+        &self,
+        source: impl AsRef<Path>,
+        glob: Glob,
+        dry_run: bool,
+    ) -> Result<Vec<TagChange>> {
         self.copy_tags_impl(Request::CopyTagsPattern {
             glob,
             source: source.as_ref().to_path_buf(),
+            dry_run,
         })
     }
 
-    fn clear_files_impl(&self, request: Request) -> Result<()> {
+    fn clear_files_impl(&self, request: Request) -> Result<Vec<TagChange>> {
         debug_assert!(matches!(
             request,
             Request::ClearFiles { .. } | Request::ClearFilesPattern { .. }
         ));
-        self.client
-            .request(request)
+        self.request(request)
             .map_err(|e| ClientError::ClearFiles(e.to_string()).into())
             .and_then(map_response)
-            .map(|_| ())
+            .and_then(|r| {
+                if let HandledResponse::ClearFiles(changes) = r {
+                    Ok(changes)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
     }
 
-    pub fn clear_files<P: AsRef<Path>>(&self, files: impl IntoIterator<Item = P>) -> Result<()> {
+    pub fn clear_files<P: AsRef<Path>>(
+        &self,
+        files: impl IntoIterator<Item = P>,
+        dry_run: bool,
+    ) -> Result<Vec<TagChange>> {
         self.clear_files_impl(Request::ClearFiles {
             files: files
                 .into_iter()
                 .map(|p| p.as_ref().to_path_buf())
                 .collect(),
+            dry_run,
         })
     }
 
-    pub fn clear_files_pattern(&self, glob: Glob) -> Result<()> {
-        self.clear_files_impl(Request::ClearFilesPattern { glob })
+    pub fn clear_files_pattern(&self, glob: Glob, dry_run: bool) -> Result<Vec<TagChange>> {
+        self.clear_files_impl(Request::ClearFilesPattern { glob, dry_run })
     }
 
-    pub fn clear_tags<T: AsRef<str>>(&self, tags: impl IntoIterator<Item = T>) -> Result<()> {
-        self.client
-            .request(Request::ClearTags {
-                tags: tags.into_iter().map(|t| t.as_ref().to_string()).collect(),
-            })
-            .map_err(|e| ClientError::ClearTags(e.to_string()).into())
-            .and_then(map_response)
-            .map(|_| ())
+    pub fn clear_tags<T: AsRef<str>>(
+        &self,
+        tags: impl IntoIterator<Item = T>,
+        dry_run: bool,
+    ) -> Result<Vec<TagChange>> {
+        self.request(Request::ClearTags {
+            tags: tags.into_iter().map(|t| t.as_ref().to_string()).collect(),
+            dry_run,
+        })
+        .map_err(|e| ClientError::ClearTags(e.to_string()).into())
+        .and_then(map_response)
+        .and_then(|r| {
+            if let HandledResponse::ClearTags(changes) = r {
+                Ok(changes)
+            } else {
+                Err(ClientError::UnexpectedResponse(r).into())
+            }
+        })
     }
 
-    pub fn list_tags(&self, with_files: bool) -> Result<HashMap<Tag, Vec<EntryData>>> {
-        self.client
-            .request(Request::ListTags { with_files })
+    pub fn list_tags(&self, with_files: bool, page: Page) -> Result<Vec<(Tag, Vec<EntryData>)>> {
+        self.request(Request::ListTags { with_files, page })
             .map_err(|e| ClientError::ListTags(e.to_string()).into())
             .and_then(map_response)
             .and_then(|r| {
@@ -277,18 +768,26 @@ impl Client {
             })
     }
 
-    pub fn list_files(&self, with_tags: bool) -> Result<Vec<(EntryData, Vec<Tag>)>> {
-        self.client
-            .request(Request::ListFiles { with_tags })
-            .map_err(|e| ClientError::ListFiles(e.to_string()).into())
-            .and_then(map_response)
-            .and_then(|r| {
-                if let HandledResponse::ListFiles(files) = r {
-                    Ok(files)
-                } else {
-                    Err(ClientError::UnexpectedResponse(r).into())
-                }
-            })
+    pub fn list_files(
+        &self,
+        with_tags: bool,
+        sort: Sort,
+        page: Page,
+    ) -> Result<Vec<(EntryData, Vec<Tag>)>> {
+        self.request(Request::ListFiles {
+            with_tags,
+            sort,
+            page,
+        })
+        .map_err(|e| ClientError::ListFiles(e.to_string()).into())
+        .and_then(map_response)
+        .and_then(|r| {
+            if let HandledResponse::ListFiles(files) = r {
+                Ok(files)
+            } else {
+                Err(ClientError::UnexpectedResponse(r).into())
+            }
+        })
     }
 
     fn inspect_files_impl(&self, request: Request) -> Result<Vec<(EntryData, Vec<Tag>)>> {
@@ -296,8 +795,7 @@ impl Client {
             request,
             Request::InspectFiles { files: _ } | Request::InspectFilesPattern { .. }
         ));
-        self.client
-            .request(request)
+        self.request(request)
             .map_err(|e| ClientError::InspectFiles(e.to_string()).into())
             .and_then(map_response)
             .and_then(|r| {
@@ -325,20 +823,19 @@ impl Client {
         self.inspect_files_impl(Request::InspectFilesPattern { glob })
     }
 
-    pub fn search<S: Into<String>>(
+    fn inspect_effective_impl(
         &self,
-        tags: impl IntoIterator<Item = S>,
-        any: bool,
-    ) -> Result<Vec<EntryData>> {
-        self.client
-            .request(Request::Search {
-                tags: tags.into_iter().map(S::into).collect(),
-                any,
-            })
-            .map_err(|e| ClientError::Search(e.to_string()).into())
+        request: Request,
+    ) -> Result<Vec<(PathBuf, Vec<EffectiveTag>)>> {
+        debug_assert!(matches!(
+            request,
+            Request::InspectEffective { .. } | Request::InspectEffectivePattern { .. }
+        ));
+        self.request(request)
+            .map_err(|e| ClientError::InspectFiles(e.to_string()).into())
             .and_then(map_response)
             .and_then(|r| {
-                if let HandledResponse::Search(files) = r {
+                if let HandledResponse::InspectEffective(files) = r {
                     Ok(files)
                 } else {
                     Err(ClientError::UnexpectedResponse(r).into())
@@ -346,19 +843,175 @@ impl Client {
             })
     }
 
+    pub fn inspect_effective<P: AsRef<Path>>(
+        &self,
+        files: impl IntoIterator<Item = P>,
+    ) -> Result<Vec<(PathBuf, Vec<EffectiveTag>)>> {
+        self.inspect_effective_impl(Request::InspectEffective {
+            files: files
+                .into_iter()
+                .map(|p| p.as_ref().to_path_buf())
+                .collect(),
+        })
+    }
+
+    pub fn inspect_effective_pattern(
+        &self,
+        glob: Glob,
+    ) -> Result<Vec<(PathBuf, Vec<EffectiveTag>)>> {
+        self.inspect_effective_impl(Request::InspectEffectivePattern { glob })
+    }
+
+    pub fn search<S: Into<String>>(
+        &self,
+        tags: impl IntoIterator<Item = S>,
+        options: SearchOptions,
+    ) -> Result<Vec<SearchMatch>> {
+        self.request(Request::Search {
+            tags: tags.into_iter().map(S::into).collect(),
+            options,
+        })
+        .map_err(|e| ClientError::Search(e.to_string()).into())
+        .and_then(map_response)
+        .and_then(|r| {
+            if let HandledResponse::Search(files) = r {
+                Ok(files)
+            } else {
+                Err(ClientError::UnexpectedResponse(r).into())
+            }
+        })
+    }
+
     pub fn ping(&self) -> Result<()> {
-        self.client
-            .request(Request::Ping)
+        self.request(Request::Ping)
             .map_err(|e| ClientError::Ping(e.to_string()).into())
             .and_then(map_response)
             .map(|_| ())
     }
 
-    pub fn clear_cache(&self) -> Result<()> {
-        self.client
-            .request(Request::ClearCache)
+    pub fn clear_cache(&self, dry_run: bool) -> Result<Vec<AuditEntry>> {
+        self.request(Request::ClearCache { dry_run })
             .map_err(|e| ClientError::ClearCache(e.to_string()).into())
             .and_then(map_response)
-            .map(|_| ())
+            .and_then(|r| {
+                if let HandledResponse::ClearCache(dropped) = r {
+                    Ok(dropped)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn undo(&self, count: usize, dry_run: bool) -> Result<Vec<TagChange>> {
+        self.request(Request::Undo { count, dry_run })
+            .map_err(|e| ClientError::Undo(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::Undo(changes) = r {
+                    Ok(changes)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn redo(&self, count: usize, dry_run: bool) -> Result<Vec<TagChange>> {
+        self.request(Request::Redo { count, dry_run })
+            .map_err(|e| ClientError::Redo(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::Redo(changes) = r {
+                    Ok(changes)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn history(&self) -> Result<(Vec<JournalEntry>, Vec<JournalEntry>)> {
+        self.request(Request::History)
+            .map_err(|e| ClientError::History(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::History(history) = r {
+                    Ok(history)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn audit_log(&self, filter: AuditFilter) -> Result<Vec<AuditEntry>> {
+        self.request(Request::AuditLog { filter })
+            .map_err(|e| ClientError::AuditLog(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::AuditLog(entries) = r {
+                    Ok(entries)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn test_rules<P: AsRef<Path>>(&self, path: P) -> Result<Vec<RuleMatch>> {
+        self.request(Request::TestRules {
+            path: path.as_ref().to_path_buf(),
+        })
+        .map_err(|e| ClientError::TestRules(e.to_string()).into())
+        .and_then(map_response)
+        .and_then(|r| {
+            if let HandledResponse::TestRules(matches) = r {
+                Ok(matches)
+            } else {
+                Err(ClientError::UnexpectedResponse(r).into())
+            }
+        })
+    }
+
+    fn auto_tag_impl(&self, request: Request) -> Result<AutoTagReport> {
+        debug_assert!(matches!(
+            request,
+            Request::AutoTagFiles { .. } | Request::AutoTagFilesPattern { .. }
+        ));
+        self.request(request)
+            .map_err(|e| ClientError::AutoTag(e.to_string()).into())
+            .and_then(map_response)
+            .and_then(|r| {
+                if let HandledResponse::AutoTag(report) = r {
+                    Ok(report)
+                } else {
+                    Err(ClientError::UnexpectedResponse(r).into())
+                }
+            })
+    }
+
+    pub fn auto_tag<P: AsRef<Path>>(
+        &self,
+        files: impl IntoIterator<Item = P>,
+        options: AutoTagOptions,
+        dry_run: bool,
+    ) -> Result<AutoTagReport> {
+        self.auto_tag_impl(Request::AutoTagFiles {
+            files: files
+                .into_iter()
+                .map(|p| p.as_ref().to_path_buf())
+                .collect(),
+            options,
+            dry_run,
+        })
+    }
+
+    pub fn auto_tag_pattern(
+        &self,
+        glob: Glob,
+        options: AutoTagOptions,
+        dry_run: bool,
+    ) -> Result<AutoTagReport> {
+        self.auto_tag_impl(Request::AutoTagFilesPattern {
+            glob,
+            options,
+            dry_run,
+        })
     }
 }
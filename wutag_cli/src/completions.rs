@@ -0,0 +1,141 @@
+//! Shell completions generated by clap extended with completion of tag names and aliases. The
+//! names are listed by the hidden `wutag complete-tags` subcommand at completion time.
+use crate::opt::{Opts, Shell, APP_NAME};
+use clap::CommandFactory;
+use clap_complete::{
+    generate,
+    shells::{Bash, Elvish, Fish, PowerShell, Zsh},
+};
+
+/// Names of positional arguments that take tags.
+const TAG_POSITIONALS: &[&str] = &["tags", "names", "tag", "implies", "alias"];
+
+const ZSH_TAGS: &str = r#"
+_wutag_tags() {
+    local -a tags
+    tags=(${(f)"$(wutag complete-tags 2>/dev/null)"})
+    compadd -a tags
+}
+"#;
+
+const BASH_TAGS: &str = r#"
+_wutag_tags() {
+    COMPREPLY=($(compgen -W "$(wutag complete-tags 2>/dev/null)" -- "${COMP_WORDS[COMP_CWORD]}"))
+}
+
+_wutag_with_tags() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}" cmd="" sub="" i
+    for (( i = 1; i < COMP_CWORD; i++ )); do
+        if [[ -z "${cmd}" ]]; then
+            case "${COMP_WORDS[i]}" in
+                set|rm|search|edit|clear|log|implies|alias) cmd="${COMP_WORDS[i]}" ;;
+            esac
+        elif [[ -z "${sub}" && "${COMP_WORDS[i]}" != -* ]]; then
+            sub="${COMP_WORDS[i]}"
+        fi
+    done
+
+    if [[ "${cur}" != -* ]]; then
+        case "${cmd}" in
+            set|rm)
+                for (( i = COMP_CWORD - 1; i > 0; i-- )); do
+                    case "${COMP_WORDS[i]}" in
+                        -t|--tags) _wutag_tags; return 0 ;;
+                        -*) break ;;
+                    esac
+                done
+                ;;
+            log)
+                if [[ "${prev}" == "-t" || "${prev}" == "--tag" ]]; then
+                    _wutag_tags; return 0
+                fi
+                ;;
+            search|edit)
+                case "${prev}" in
+                    --tagged-since|--tagged-before|-c|--color) ;;
+                    *) _wutag_tags; return 0 ;;
+                esac
+                ;;
+            clear)
+                if [[ "${sub}" == "tags" ]]; then
+                    _wutag_tags; return 0
+                fi
+                ;;
+            implies|alias)
+                if [[ "${sub}" == "add" || "${sub}" == "rm" ]]; then
+                    _wutag_tags; return 0
+                fi
+                ;;
+        esac
+    fi
+    _wutag "$@"
+}
+
+if [[ "${BASH_VERSINFO[0]}" -eq 4 && "${BASH_VERSINFO[1]}" -ge 4 || "${BASH_VERSINFO[0]}" -gt 4 ]]; then
+    complete -F _wutag_with_tags -o nosort -o bashdefault -o default wutag
+else
+    complete -F _wutag_with_tags -o bashdefault -o default wutag
+fi
+"#;
+
+const FISH_TAGS: &str = r#"
+function __fish_wutag_tags
+    wutag complete-tags 2>/dev/null
+end
+
+complete -c wutag -n "__fish_wutag_using_subcommand set rm" -s t -l tags -f -a "(__fish_wutag_tags)"
+complete -c wutag -n "__fish_wutag_using_subcommand log" -s t -l tag -f -a "(__fish_wutag_tags)"
+complete -c wutag -n "__fish_wutag_using_subcommand search edit" -f -a "(__fish_wutag_tags)"
+complete -c wutag -n "__fish_wutag_using_subcommand clear; and __fish_seen_subcommand_from tags" -f -a "(__fish_wutag_tags)"
+complete -c wutag -n "__fish_wutag_using_subcommand implies alias; and __fish_seen_subcommand_from add rm" -f -a "(__fish_wutag_tags)"
+"#;
+
+/// Returns the completion script for the `shell`. Scripts for bash, fish and zsh also complete
+/// names of tags and their aliases.
+pub fn script(shell: Shell) -> String {
+    let mut app = Opts::command();
+    let mut buf = vec![];
+    match shell {
+        Shell::Bash => generate(Bash, &mut app, APP_NAME, &mut buf),
+        Shell::Elvish => generate(Elvish, &mut app, APP_NAME, &mut buf),
+        Shell::Fish => generate(Fish, &mut app, APP_NAME, &mut buf),
+        Shell::PowerShell => generate(PowerShell, &mut app, APP_NAME, &mut buf),
+        Shell::Zsh => generate(Zsh, &mut app, APP_NAME, &mut buf),
+    }
+    let script = String::from_utf8_lossy(&buf).into_owned();
+
+    match shell {
+        Shell::Bash => script + BASH_TAGS,
+        Shell::Fish => script + FISH_TAGS,
+        Shell::Zsh => zsh_with_tags(&script),
+        Shell::Elvish | Shell::PowerShell => script,
+    }
+}
+
+/// Makes arguments of the zsh script that take tags complete them with `_wutag_tags`.
+fn zsh_with_tags(script: &str) -> String {
+    let mut lines = script.lines();
+    let mut out = String::new();
+    // `#compdef` has to stay on the first line
+    if let Some(first) = lines.next() {
+        out.push_str(first);
+        out.push('\n');
+        out.push_str(ZSH_TAGS);
+    }
+    for line in lines {
+        let name = line
+            .trim_start_matches(['\'', '*', ':'])
+            .split([' ', ':'])
+            .next()
+            .unwrap_or_default();
+        let takes_tags = line.contains(":TAG:_default'")
+            || (line.starts_with('\'') && TAG_POSITIONALS.contains(&name));
+        if takes_tags && line.contains(":_default'") {
+            out.push_str(&line.replace(":_default'", ":_wutag_tags'"));
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -23,6 +23,9 @@ pub struct Config {
     pub colors: Option<Vec<String>>,
     #[serde(default)]
     pub pretty_output: bool,
+    #[serde(default)]
+    /// Whether tags of directories apply to all files below them when searching.
+    pub inherit_tags: bool,
 }
 
 impl Config {
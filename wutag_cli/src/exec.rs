@@ -0,0 +1,274 @@
+//! Running commands with paths of entries found by a search as arguments. Arguments are passed to
+//! the program directly without a shell so paths with spaces or newlines stay a single argument.
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Maximum total size of arguments of a single command run with `--exec-batch`, more paths are
+/// split into several runs. Kept well below the usual `ARG_MAX` to leave room for the environment.
+const MAX_BATCH_ARGS_SIZE: usize = 1024 * 1024;
+
+const PLACEHOLDERS: [&str; 5] = ["{//}", "{/.}", "{/}", "{.}", "{}"];
+
+#[derive(Clone, Debug)]
+/// A command with placeholders replaced by a path of an entry:
+///  - `{}` the path
+///  - `{/}` the file name
+///  - `{//}` the parent directory
+///  - `{.}` the path without extension
+///  - `{/.}` the file name without extension
+///
+/// Without placeholders the path is appended as the last argument.
+pub struct CommandTemplate {
+    args: Vec<String>,
+}
+
+impl CommandTemplate {
+    pub fn new(args: Vec<String>) -> Self {
+        Self { args }
+    }
+
+    fn has_placeholders(&self) -> bool {
+        self.args[1..]
+            .iter()
+            .any(|arg| PLACEHOLDERS.iter().any(|p| arg.contains(p)))
+    }
+
+    fn program(&self) -> &str {
+        &self.args[0]
+    }
+
+    /// Returns the command run for the single `path`.
+    fn command(&self, path: &Path) -> Command {
+        let mut command = Command::new(self.program());
+        if self.has_placeholders() {
+            command.args(self.args[1..].iter().map(|arg| render(arg, path)));
+        } else {
+            command.args(&self.args[1..]).arg(path);
+        }
+        command
+    }
+
+    /// Returns the command run for all of the `paths` at once. Arguments with placeholders are
+    /// repeated for each path.
+    fn batch_command(&self, paths: &[PathBuf]) -> Command {
+        let mut command = Command::new(self.program());
+        if self.has_placeholders() {
+            for arg in &self.args[1..] {
+                if PLACEHOLDERS.iter().any(|p| arg.contains(p)) {
+                    command.args(paths.iter().map(|path| render(arg, path)));
+                } else {
+                    command.arg(arg);
+                }
+            }
+        } else {
+            command.args(&self.args[1..]).args(paths);
+        }
+        command
+    }
+}
+
+/// Replaces placeholders in the `arg` with parts of the `path`.
+fn render(arg: &str, path: &Path) -> OsString {
+    let mut rendered = OsString::new();
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        rendered.push(&rest[..start]);
+        rest = &rest[start..];
+        match PLACEHOLDERS.iter().find(|p| rest.starts_with(*p)) {
+            Some(placeholder) => {
+                rendered.push(placeholder_value(placeholder, path));
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                rendered.push("{");
+                rest = &rest[1..];
+            }
+        }
+    }
+    rendered.push(rest);
+    rendered
+}
+
+fn placeholder_value(placeholder: &str, path: &Path) -> OsString {
+    let without_extension = |path: &Path| match path.extension() {
+        Some(_) => path.with_extension("").into_os_string(),
+        None => path.as_os_str().to_os_string(),
+    };
+    let file_name = || Path::new(path.file_name().unwrap_or(path.as_os_str()));
+    match placeholder {
+        "{}" => path.as_os_str().to_os_string(),
+        "{/}" => file_name().as_os_str().to_os_string(),
+        "{//}" => path
+            .parent()
+            .map(|parent| parent.as_os_str().to_os_string())
+            .unwrap_or_else(|| OsString::from("/")),
+        "{.}" => without_extension(path),
+        "{/.}" => without_extension(file_name()),
+        _ => unreachable!("unknown placeholder {placeholder}"),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Number of commands that were run and that failed.
+pub struct ExecSummary {
+    pub total: usize,
+    pub failed: usize,
+}
+
+/// Runs the command once for each of the `paths` with at most `jobs` commands running at the same
+/// time. When more than one command runs at a time output of each command is printed after it
+/// finishes so that outputs of different commands don't mix.
+pub fn exec_each(template: &CommandTemplate, paths: &[PathBuf], jobs: usize) -> ExecSummary {
+    let next = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    let jobs = jobs.clamp(1, paths.len().max(1));
+    std::thread::scope(|s| {
+        for _ in 0..jobs {
+            s.spawn(|| {
+                while let Some(path) = paths.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let mut command = template.command(path);
+                    let success = if jobs == 1 {
+                        command.status().map(|status| (status, None))
+                    } else {
+                        command.output().map(|output| (output.status, Some(output)))
+                    };
+                    if !report(template, path.as_os_str(), success) {
+                        failed.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    ExecSummary {
+        total: paths.len(),
+        failed: failed.into_inner(),
+    }
+}
+
+/// Runs the command with all of the `paths` as arguments, split into several runs if they don't
+/// fit into a single command line.
+pub fn exec_batch(template: &CommandTemplate, paths: &[PathBuf]) -> ExecSummary {
+    let mut summary = ExecSummary::default();
+    for batch in batches(paths, MAX_BATCH_ARGS_SIZE) {
+        let status = template.batch_command(batch).status();
+        let description = format!("{} paths", batch.len());
+        summary.total += 1;
+        if !report(
+            template,
+            OsStr::new(&description),
+            status.map(|status| (status, None)),
+        ) {
+            summary.failed += 1;
+        }
+    }
+    summary
+}
+
+/// Splits the `paths` into batches with total size of arguments of at most `max_size` bytes. A
+/// path larger than that gets a batch of its own.
+fn batches(paths: &[PathBuf], max_size: usize) -> Vec<&[PathBuf]> {
+    let mut batches = vec![];
+    let mut start = 0;
+    while start < paths.len() {
+        let mut size = paths[start].as_os_str().len() + 1;
+        let mut end = start + 1;
+        while end < paths.len() && size + paths[end].as_os_str().len() < max_size {
+            size += paths[end].as_os_str().len() + 1;
+            end += 1;
+        }
+        batches.push(&paths[start..end]);
+        start = end;
+    }
+    batches
+}
+
+/// Prints captured output of a command and reports its failure. Returns whether the command
+/// succeeded.
+fn report(
+    template: &CommandTemplate,
+    target: &OsStr,
+    result: io::Result<(std::process::ExitStatus, Option<Output>)>,
+) -> bool {
+    let target = Path::new(target).display();
+    match result {
+        Ok((status, output)) => {
+            if let Some(output) = output {
+                // a single lock so the output isn't interleaved with output of other commands
+                let _ = io::stdout().lock().write_all(&output.stdout);
+                let _ = io::stderr().lock().write_all(&output.stderr);
+            }
+            if !status.success() {
+                eprintln!("`{}` failed for {target} - {status}", template.program());
+            }
+            status.success()
+        }
+        Err(e) => {
+            eprintln!("failed to run `{}` for {target} - {e}", template.program());
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_placeholders() {
+        let path = Path::new("/photos/2021/beach.tar.gz");
+        assert_eq!(render("{}", path), "/photos/2021/beach.tar.gz");
+        assert_eq!(render("{/}", path), "beach.tar.gz");
+        assert_eq!(render("{//}", path), "/photos/2021");
+        assert_eq!(render("{.}", path), "/photos/2021/beach.tar");
+        assert_eq!(render("{/.}", path), "beach.tar");
+        assert_eq!(
+            render("--out={//}/{/.}.png", path),
+            "--out=/photos/2021/beach.tar.png"
+        );
+        assert_eq!(render("{x} {} {", path), "{x} /photos/2021/beach.tar.gz {");
+        assert_eq!(render("plain", path), "plain");
+    }
+
+    #[test]
+    fn returns_placeholder_values_of_unusual_paths() {
+        let path = Path::new("/");
+        assert_eq!(placeholder_value("{}", path), "/");
+        assert_eq!(placeholder_value("{/}", path), "/");
+        assert_eq!(placeholder_value("{//}", path), "/");
+
+        let path = Path::new("notes");
+        assert_eq!(placeholder_value("{//}", path), "");
+        assert_eq!(placeholder_value("{.}", path), "notes");
+        assert_eq!(placeholder_value("{/.}", path), "notes");
+
+        let path = Path::new("/home/user/.bashrc");
+        assert_eq!(placeholder_value("{.}", path), "/home/user/.bashrc");
+        assert_eq!(placeholder_value("{/.}", path), ".bashrc");
+    }
+
+    #[test]
+    fn splits_batches_by_size_of_arguments() {
+        let paths: Vec<_> = ["aaaa", "bbbb", "cccc", "dddddddddddd", "ee"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        let sizes = |max_size| -> Vec<usize> {
+            batches(&paths, max_size)
+                .into_iter()
+                .map(|batch| batch.len())
+                .collect()
+        };
+        assert_eq!(sizes(1024), vec![5]);
+        // each path takes its length plus a byte for the separator
+        assert_eq!(sizes(10), vec![2, 1, 1, 1]);
+        assert_eq!(sizes(11), vec![2, 1, 1, 1]);
+        assert_eq!(sizes(15), vec![3, 1, 1]);
+        assert_eq!(sizes(16), vec![3, 2]);
+        // paths larger than the limit still get a batch
+        assert_eq!(sizes(1), vec![1, 1, 1, 1, 1]);
+        assert!(batches(&[], 10).is_empty());
+    }
+}
@@ -1,5 +1,9 @@
-use wutag_core::color::{ColoredString, Colorize};
+use chrono::{DateTime, SecondsFormat, Utc};
+use wutag_core::audit::{AuditAction, AuditEntry};
+use wutag_core::color::{Color, ColoredString, Colorize};
+use wutag_core::registry::{ChangeKind, SearchMatch, TagChange, TagInfo};
 use wutag_core::tag::Tag;
+use wutag_core::view::ViewReport;
 
 use std::path::Path;
 
@@ -14,3 +18,173 @@ pub fn tag(tag: &Tag) -> ColoredString {
         tag.name().color(*tag.color()).bold()
     }
 }
+
+/// Formats the `tag` followed by the directory from which it is inherited.
+pub fn inherited<P: AsRef<Path>>(t: &Tag, from: P) -> String {
+    format!(
+        "{} {}",
+        tag(t),
+        format!("(from {})", from.as_ref().display()).dimmed()
+    )
+}
+
+/// Formats the path of the `found` entry followed by the tags that it inherited.
+pub fn search_match(found: &SearchMatch) -> String {
+    if found.inherited.is_empty() {
+        return path(found.entry.path()).to_string();
+    }
+    let inherited: Vec<_> = found
+        .inherited
+        .iter()
+        .map(|(tag, from)| format!("{tag} from {}", from.display()))
+        .collect();
+    format!(
+        "{} {}",
+        path(found.entry.path()),
+        format!("(inherited {})", inherited.join(", ")).dimmed()
+    )
+}
+
+/// Formats the `tag` followed by the time at which it was applied and at which it expires if
+/// known.
+pub fn tagged(t: &Tag, time: Option<&DateTime<Utc>>, expires: Option<&DateTime<Utc>>) -> String {
+    let time = time.map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true));
+    let expires = expires.map(|expires| {
+        format!(
+            "expires {}",
+            expires.to_rfc3339_opts(SecondsFormat::Secs, true)
+        )
+    });
+    let info: Vec<_> = time.into_iter().chain(expires).collect();
+    if info.is_empty() {
+        tag(t).to_string()
+    } else {
+        format!("{} {}", tag(t), format!("({})", info.join(", ")).dimmed())
+    }
+}
+
+pub fn change(change: &TagChange) -> String {
+    let sign = match change.kind {
+        ChangeKind::Add => "+".green(),
+        ChangeKind::Remove => "-".red(),
+    };
+    match &change.expires {
+        Some(expires) => format!(
+            "{sign} {}: {} {}",
+            path(&change.path),
+            tag(&change.tag),
+            format!(
+                "(expires {})",
+                expires.to_rfc3339_opts(SecondsFormat::Secs, true)
+            )
+            .dimmed()
+        ),
+        None => format!("{sign} {}: {}", path(&change.path), tag(&change.tag)),
+    }
+}
+
+/// Formats the changes made to a view, one line per symlink.
+pub fn view_report(report: &ViewReport) -> Vec<String> {
+    let mut lines = vec![];
+    for (link, target) in &report.created {
+        lines.push(format!(
+            "{} {} -> {}",
+            "+".green(),
+            path(link),
+            path(target)
+        ));
+    }
+    for link in &report.removed {
+        lines.push(format!("{} {}", "-".red(), path(link)));
+    }
+    for link in &report.skipped {
+        lines.push(format!(
+            "{} {} {}",
+            "!".yellow(),
+            path(link),
+            "(already exists, not created by wutag)".dimmed()
+        ));
+    }
+    lines
+}
+
+/// Formats an edit of a tag, implication, alias or saved search.
+pub fn edit(entry: &AuditEntry) -> String {
+    let sign = match entry.action {
+        AuditAction::Add => "+".green(),
+        AuditAction::Remove => "-".red(),
+        AuditAction::Edit => "~".yellow(),
+    };
+    let target = match &entry.path {
+        Some(p) => format!("{}: {}", path(p), entry.tag.bold()),
+        None => entry.tag.bold().to_string(),
+    };
+    let details = entry
+        .details
+        .as_ref()
+        .map(|details| format!(" ({details})"))
+        .unwrap_or_default();
+    format!("{sign} {target}{details}")
+}
+
+pub fn audit_entry(entry: &AuditEntry) -> String {
+    let client = match (entry.uid, entry.pid) {
+        (Some(uid), Some(pid)) => format!(" uid={uid} pid={pid}"),
+        (Some(uid), None) => format!(" uid={uid}"),
+        (None, Some(pid)) => format!(" pid={pid}"),
+        (None, None) => String::new(),
+    };
+    format!(
+        "{} {}{client} {}",
+        entry.timestamp.to_rfc3339(),
+        entry.request,
+        edit(entry)
+    )
+}
+
+/// Returns the hex notation of true colors like `#1f1f1f` or the name of basic colors like
+/// `BrightRed`.
+pub fn color(color: &Color) -> String {
+    match color {
+        Color::TrueColor { r, g, b } => format!("#{r:02x}{g:02x}{b:02x}"),
+        color => format!("{color:?}"),
+    }
+}
+
+/// Formats details of a tag, one per line, omitting the missing ones.
+pub fn tag_info(info: &TagInfo) -> String {
+    let t = &info.tag;
+    let mut lines = vec![match t.icon() {
+        Some(icon) => format!("{icon} {}", tag(t)),
+        None => tag(t).to_string(),
+    }];
+    let mut field = |name: &str, value: String| {
+        lines.push(format!("  {} {value}", format!("{name}:").dimmed()));
+    };
+    if let Some(description) = t.description() {
+        field("description", description.to_string());
+    }
+    field("color", color(t.color()));
+    if let Some(created) = t.created() {
+        field(
+            "created",
+            created.to_rfc3339_opts(SecondsFormat::Secs, true),
+        );
+    }
+    if !info.aliases.is_empty() {
+        field("aliases", info.aliases.join(", "));
+    }
+    if !info.implies.is_empty() {
+        field("implies", info.implies.join(", "));
+    }
+    field("entries", info.usage.to_string());
+    if !info.co_occurring.is_empty() {
+        let co_occurring: Vec<_> = info
+            .co_occurring
+            .iter()
+            .map(|(tag, count)| format!("{tag} ({count})"))
+            .collect();
+        field("often with", co_occurring.join(", "));
+    }
+    lines.join("\n")
+}
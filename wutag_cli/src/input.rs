@@ -0,0 +1,150 @@
+//! Reading paths of entries from the standard input so that output of tools like `fd` or `find`
+//! can be piped to commands without hitting limits on the number of arguments.
+use std::io::{self, BufRead};
+use std::path::PathBuf;
+
+/// Number of paths sent to the daemon in a single request.
+pub const CHUNK_SIZE: usize = 1000;
+
+/// A path argument that stands for paths read from the standard input.
+pub const STDIN_ARG: &str = "-";
+
+/// Splits the `paths` passed as arguments into chunks of at most [CHUNK_SIZE](CHUNK_SIZE) paths
+/// followed by chunks of paths read from the `input` if there is one. Paths in the input are
+/// terminated by the `separator`, either a newline or a NUL byte. The input is read lazily so each
+/// chunk can be sent before the rest is read.
+pub fn path_chunks<R: BufRead>(
+    paths: Vec<PathBuf>,
+    input: Option<R>,
+    separator: u8,
+) -> impl Iterator<Item = io::Result<Vec<PathBuf>>> {
+    let mut paths = paths.into_iter().peekable();
+    let mut input = input.map(|input| PathReader { input, separator });
+    std::iter::from_fn(move || {
+        if paths.peek().is_some() {
+            return Some(Ok(paths.by_ref().take(CHUNK_SIZE).collect()));
+        }
+        let reader = input.as_mut()?;
+        let mut chunk = Vec::new();
+        while chunk.len() < CHUNK_SIZE {
+            match reader.next_path() {
+                Ok(Some(path)) => chunk.push(path),
+                Ok(None) => break,
+                Err(e) => {
+                    input = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+        if chunk.is_empty() {
+            input = None;
+            return None;
+        }
+        Some(Ok(chunk))
+    })
+}
+
+/// Reads paths separated by newlines or NUL bytes. Newline separated paths may end with a `\r`
+/// which is dropped. Empty paths are skipped.
+struct PathReader<R> {
+    input: R,
+    separator: u8,
+}
+
+impl<R: BufRead> PathReader<R> {
+    fn next_path(&mut self) -> io::Result<Option<PathBuf>> {
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            if self.input.read_until(self.separator, &mut buf)? == 0 {
+                return Ok(None);
+            }
+            if buf.last() == Some(&self.separator) {
+                buf.pop();
+            }
+            if self.separator == b'\n' {
+                if buf.last() == Some(&b'\r') {
+                    buf.pop();
+                }
+                if buf.contains(&b'\0') {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "input contains NUL characters, use `--null` to read NUL separated paths",
+                    ));
+                }
+            }
+            if !buf.is_empty() {
+                return path_from_bytes(buf).map(Some);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> io::Result<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    Ok(std::ffi::OsString::from_vec(bytes).into())
+}
+
+/// Other platforms don't allow creating paths from raw bytes so only UTF-8 paths are accepted.
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> io::Result<PathBuf> {
+    String::from_utf8(bytes)
+        .map(PathBuf::from)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    fn read(input: &[u8], separator: u8) -> io::Result<Vec<Vec<PathBuf>>> {
+        path_chunks(Vec::new(), Some(input), separator).collect()
+    }
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn reads_newline_separated_paths() {
+        let chunks = read(b"/tmp/a\n/tmp/b c\n\n/tmp/d", b'\n').unwrap();
+        assert_eq!(chunks, vec![paths(&["/tmp/a", "/tmp/b c", "/tmp/d"])]);
+
+        let chunks = read(b"/tmp/a\r\n/tmp/b\r\n", b'\n').unwrap();
+        assert_eq!(chunks, vec![paths(&["/tmp/a", "/tmp/b"])]);
+
+        assert!(read(b"/tmp/a\0/tmp/b\0", b'\n').is_err());
+        assert!(read(b"", b'\n').unwrap().is_empty());
+    }
+
+    #[test]
+    fn reads_nul_separated_paths() {
+        let chunks = read(b"/tmp/a\n b\0/tmp/c\r\0\0/tmp/d\0", b'\0').unwrap();
+        assert_eq!(chunks, vec![paths(&["/tmp/a\n b", "/tmp/c\r", "/tmp/d"])]);
+    }
+
+    #[test]
+    fn reads_paths_longer_than_buffer() {
+        let long = "a".repeat(100);
+        let input = format!("{long}\n/tmp/b\n{long}");
+        let reader = BufReader::with_capacity(16, input.as_bytes());
+        let chunks: Vec<_> = path_chunks(Vec::new(), Some(reader), b'\n')
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(chunks, vec![paths(&[&long, "/tmp/b", &long])]);
+    }
+
+    #[test]
+    fn splits_paths_into_chunks() {
+        let arguments = paths(&["/tmp/1", "/tmp/2"]);
+        let input: String = (0..CHUNK_SIZE + 1).map(|i| format!("/tmp/{i}\0")).collect();
+        let chunks: Vec<_> = path_chunks(arguments, Some(input.as_bytes()), b'\0')
+            .collect::<io::Result<_>>()
+            .unwrap();
+        let sizes: Vec<_> = chunks.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![2, CHUNK_SIZE, 1]);
+        assert_eq!(chunks[2], paths(&[&format!("/tmp/{CHUNK_SIZE}")]));
+    }
+}
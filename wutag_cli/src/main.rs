@@ -1,15 +1,17 @@
 mod app;
 mod client;
+mod completions;
 mod config;
+mod exec;
 mod fmt;
+mod input;
 mod opt;
 
-use clap::{CommandFactory, Parser};
+use clap::Parser;
 
 use app::App;
 use config::Config;
-use opt::{Command, CompletionsOpts, Opts, Shell, APP_NAME};
-use std::io;
+use opt::{Command, CompletionsOpts, Opts};
 use thiserror::Error as ThisError;
 
 #[derive(Debug, ThisError)]
@@ -31,20 +33,7 @@ pub enum Error {
 pub type Result<T> = std::result::Result<T, Error>;
 
 fn print_completions(opts: &CompletionsOpts) -> Result<()> {
-    use clap_complete::{
-        generate,
-        shells::{Bash, Elvish, Fish, PowerShell, Zsh},
-    };
-
-    let mut app = Opts::command();
-
-    match opts.shell {
-        Shell::Bash => generate(Bash, &mut app, APP_NAME, &mut io::stdout()),
-        Shell::Elvish => generate(Elvish, &mut app, APP_NAME, &mut io::stdout()),
-        Shell::Fish => generate(Fish, &mut app, APP_NAME, &mut io::stdout()),
-        Shell::PowerShell => generate(PowerShell, &mut app, APP_NAME, &mut io::stdout()),
-        Shell::Zsh => generate(Zsh, &mut app, APP_NAME, &mut io::stdout()),
-    }
+    print!("{}", completions::script(opts.shell));
     Ok(())
 }
 
@@ -63,5 +52,6 @@ fn main() {
 
     if let Err(e) = App::run(opts, config) {
         eprintln!("Execution failed, reason: {}", e);
+        std::process::exit(1);
     }
 }
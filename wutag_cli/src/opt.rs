@@ -2,6 +2,8 @@
 use std::{path::PathBuf, str::FromStr};
 
 use clap::Parser;
+use wutag_core::filter::{FileType, ModifiedFilter, SizeFilter};
+use wutag_core::sort::{Page, Sort, SortKey};
 
 pub const APP_NAME: &str = "wutag";
 pub const APP_VERSION: &str = "0.5.0";
@@ -17,14 +19,17 @@ pub const APP_ABOUT: &str = "Tool to tag and manage tags of files.";
 pub struct Opts {
     #[arg(short, long)]
     /// When this parameter is specified the program will look for files starting from provided
-    /// path, otherwise defaults to current directory. Only applies to subcommands that take a
-    /// pattern as a positional argument.
+    /// path and resolve relative paths against it, otherwise defaults to current directory.
     pub dir: Option<PathBuf>,
     #[arg(long, short)]
     /// If provided increase maximum recursion depth of filesystem traversal to specified value,
     /// otherwise default depth is 2. Only applies to subcommands that take a pattern as a
     /// positional argument.
     pub max_depth: Option<usize>,
+    #[arg(long, conflicts_with = "max_depth")]
+    /// Don't limit the recursion depth of filesystem traversal. Only applies to subcommands that
+    /// take a pattern as a positional argument.
+    pub unlimited_depth: bool,
     /// Make the output pretty (add color and reorder things). This is not recommended when using
     /// wutag in scripts.
     #[arg(long, short)]
@@ -33,6 +38,9 @@ pub struct Opts {
     #[clap(default_value = "default")]
     /// Change the output format to `json` or `yaml`
     pub output_format: OutputFormat,
+    #[arg(long, short = 'n')]
+    /// Don't modify any files or tags, only print the changes that the subcommand would make.
+    pub dry_run: bool,
     #[clap(subcommand)]
     pub cmd: Command,
 }
@@ -63,14 +71,77 @@ pub enum ListObject {
         #[arg(long, short = 'f')]
         /// Should all entries tagged with a tag be shown
         with_files: bool,
+        #[command(flatten)]
+        page: PageOpts,
     },
     Files {
         #[arg(long, short = 't')]
         /// Should the tags of the entry be display.
         with_tags: bool,
+        #[command(flatten)]
+        sort: SortOpts,
+        #[command(flatten)]
+        page: PageOpts,
     },
 }
 
+#[derive(Parser)]
+pub struct SortOpts {
+    #[arg(long = "sort", default_value = "path", value_name = "KEY")]
+    /// Sort results by `path`, number of `tags`, time they were last `tagged`, `size` or
+    /// `modified` time of the file. Results with equal keys are sorted by path.
+    pub key: SortKey,
+    #[arg(long)]
+    /// Reverse the order of results
+    pub reverse: bool,
+}
+
+impl From<SortOpts> for Sort {
+    fn from(opts: SortOpts) -> Self {
+        Sort {
+            key: opts.key,
+            reverse: opts.reverse,
+        }
+    }
+}
+
+#[derive(Parser)]
+pub struct PageOpts {
+    #[arg(long)]
+    /// Return at most this many results
+    pub limit: Option<usize>,
+    #[arg(long, default_value = "0")]
+    /// Skip this many results. Together with `--limit` allows paging through the results.
+    pub offset: usize,
+}
+
+impl From<PageOpts> for Page {
+    fn from(opts: PageOpts) -> Self {
+        Page {
+            offset: opts.offset,
+            limit: opts.limit,
+        }
+    }
+}
+
+#[derive(Parser)]
+pub struct GlobOpts {
+    #[arg(long, short = 'E', requires = "glob", value_name = "PATTERN")]
+    /// Skip entries matching this glob pattern and everything below them. Can be passed multiple
+    /// times.
+    pub exclude: Vec<String>,
+    #[arg(long, requires = "glob")]
+    /// Skip hidden entries and don't look into hidden directories
+    pub no_hidden: bool,
+    #[arg(long, requires = "glob")]
+    /// Skip entries ignored by `.gitignore` and `.ignore` files, `.git/info/exclude` and the
+    /// global git excludes file
+    pub ignore_files: bool,
+    #[arg(long, short = 'L', requires = "glob")]
+    /// Follow symbolic links to directories
+    pub follow: bool,
+}
+
 #[derive(Parser)]
 pub struct ListOpts {
     #[clap(subcommand)]
@@ -83,12 +154,29 @@ pub struct SetOpts {
     /// A list of entries to tag
     pub paths: Vec<String>,
     #[arg(short, long)]
-    /// Treat the first path as a glob pattern
+    /// Treat the paths as glob patterns
     pub glob: bool,
+    #[command(flatten)]
+    pub glob_opts: GlobOpts,
+    #[arg(long, conflicts_with = "glob")]
+    /// Also read paths of entries from the standard input, one per line. Same as passing `-` as
+    /// one of the paths
+    pub stdin: bool,
+    #[arg(long, short = '0', conflicts_with = "glob")]
+    /// Read paths from the standard input separated by NUL characters instead of newlines, for
+    /// use with `find -print0` or `fd -0`. Implies `--stdin`
+    pub null: bool,
     #[clap(required = true)]
-    #[arg(short, long, action = clap::ArgAction::Append, num_args = 0..)]
+    #[arg(short, long, action = clap::ArgAction::Append, num_args = 0.., value_name = "TAG")]
     /// List of tags to tag the entries with
     pub tags: Vec<String>,
+    #[arg(long, short)]
+    /// Remove the added tags automatically after this time. Accepts a duration like `12h` or `7d`
+    /// counted from now, a date like `2021-03-14` or a RFC 3339 timestamp.
+    pub expires: Option<String>,
+    #[arg(long, short)]
+    /// Also add all tags implied by the added tags to the files, see `wutag implies`
+    pub implied: bool,
 }
 
 #[derive(Parser)]
@@ -96,8 +184,21 @@ pub struct GetOpts {
     /// A list of entries to retrieve tags from
     pub paths: Vec<String>,
     #[arg(short, long)]
-    /// Treat the first path as a glob pattern
+    /// Treat the paths as glob patterns
     pub glob: bool,
+    #[command(flatten)]
+    pub glob_opts: GlobOpts,
+    #[arg(long, conflicts_with = "glob")]
+    /// Also read paths of entries from the standard input, one per line. Same as passing `-` as
+    /// one of the paths
+    pub stdin: bool,
+    #[arg(long, short = '0', conflicts_with = "glob")]
+    /// Read paths from the standard input separated by NUL characters instead of newlines, for
+    /// use with `find -print0` or `fd -0`. Implies `--stdin`
+    pub null: bool,
+    #[arg(short, long)]
+    /// Also show tags inherited from tagged parent directories of the entries
+    pub effective: bool,
 }
 
 #[derive(Parser)]
@@ -105,10 +206,20 @@ pub struct RmOpts {
     /// A list of entries to tag
     pub paths: Vec<String>,
     #[arg(short, long)]
-    /// Treat the first path as a glob pattern
+    /// Treat the paths as glob patterns
     pub glob: bool,
+    #[command(flatten)]
+    pub glob_opts: GlobOpts,
+    #[arg(long, conflicts_with = "glob")]
+    /// Also read paths of entries from the standard input, one per line. Same as passing `-` as
+    /// one of the paths
+    pub stdin: bool,
+    #[arg(long, short = '0', conflicts_with = "glob")]
+    /// Read paths from the standard input separated by NUL characters instead of newlines, for
+    /// use with `find -print0` or `fd -0`. Implies `--stdin`
+    pub null: bool,
     #[clap(required = true)]
-    #[arg(short, long, action = clap::ArgAction::Append, num_args = 0..)]
+    #[arg(short, long, action = clap::ArgAction::Append, num_args = 0.., value_name = "TAG")]
     pub tags: Vec<String>,
 }
 
@@ -124,9 +235,20 @@ pub enum ClearObject {
         /// A list of entries to tag
         paths: Vec<String>,
         #[arg(short, long)]
-        /// Treat the first path as a glob pattern
+        /// Treat the paths as glob patterns
         glob: bool,
+        #[command(flatten)]
+        glob_opts: GlobOpts,
+        #[arg(long, conflicts_with = "glob")]
+        /// Also read paths of entries from the standard input, one per line. Same as passing `-`
+        /// as one of the paths
+        stdin: bool,
+        #[arg(long, short = '0', conflicts_with = "glob")]
+        /// Read paths from the standard input separated by NUL characters instead of newlines,
+        /// for use with `find -print0` or `fd -0`. Implies `--stdin`
+        null: bool,
     },
+    /// Remove all entries from the registry, the tags saved in the files are kept
     Cache,
 }
 
@@ -144,29 +266,336 @@ pub struct SearchOpts {
     #[arg(long, short)]
     /// If set to 'true' all entries containing any of provided tags will be returned
     pub any: bool,
+    #[arg(long)]
+    /// Only return entries that were tagged with the searched tags at or after this point in
+    /// time. Accepts the same values as `log --since`.
+    pub tagged_since: Option<String>,
+    #[arg(long)]
+    /// Only return entries that were tagged with the searched tags before this point in time.
+    /// For example `--tagged-before 30d` returns entries tagged more than 30 days ago.
+    pub tagged_before: Option<String>,
+    #[arg(long, short)]
+    /// Treat tags of directories as tags of all files and directories up to 8 levels below them.
+    /// Can be enabled by default with `inherit_tags: true` in the configuration.
+    pub inherit: bool,
+    #[arg(long, value_name = "DIR")]
+    /// Only return entries located in this directory or any of its subdirectories
+    pub under: Option<PathBuf>,
+    #[arg(long = "type", value_name = "f|d|l")]
+    /// Only return regular files (`f`), directories (`d`) or symbolic links (`l`)
+    pub file_type: Option<FileType>,
+    #[arg(long, allow_hyphen_values = true)]
+    /// Only return entries with size bigger (`+10M`), smaller (`-10M`) or equal (`10M`) to the
+    /// given size. Sizes are rounded up to whole units, supported units are `k`, `M`, `G` and
+    /// `T`, bytes are used when no unit is given. Can be repeated.
+    pub size: Vec<SizeFilter>,
+    #[arg(long, allow_hyphen_values = true)]
+    /// Only return entries modified after (`-7d`) or before (`+7d`) a point in time. Accepts the
+    /// same values as `log --since` prefixed with `-` or `+`. Can be repeated.
+    pub modified: Vec<ModifiedFilter>,
+    #[arg(long, value_name = "REGEX")]
+    /// Only return entries whose whole path matches this regular expression
+    pub path_regex: Option<String>,
+    #[command(flatten)]
+    pub sort: SortOpts,
+    #[command(flatten)]
+    pub page: PageOpts,
+    #[arg(
+        long,
+        short = 'x',
+        num_args = 1..,
+        allow_hyphen_values = true,
+        value_terminator = ";",
+        value_name = "CMD",
+        conflicts_with = "exec_batch"
+    )]
+    /// Run a command for each found entry instead of printing it. The command and its arguments
+    /// end at `;` or at the end of the command line and are passed to the program without a
+    /// shell. `{}` is replaced by the path, `{/}` by the file name, `{//}` by the parent
+    /// directory, `{.}` and `{/.}` by the path and file name without extension. Without
+    /// placeholders the path is appended as the last argument.
+    pub exec: Option<Vec<String>>,
+    #[arg(
+        long,
+        short = 'X',
+        num_args = 1..,
+        allow_hyphen_values = true,
+        value_terminator = ";",
+        value_name = "CMD"
+    )]
+    /// Run a command once with all found entries as arguments. Placeholders work like with
+    /// `--exec` and an argument containing one is repeated for each entry.
+    pub exec_batch: Option<Vec<String>>,
+    #[arg(long, short, value_name = "N", requires = "exec")]
+    /// Number of commands run by `--exec` at the same time, defaults to the number of CPUs
+    pub jobs: Option<usize>,
+    #[arg(long, short = '0', conflicts_with_all = ["exec", "exec_batch"])]
+    /// Separate printed paths with a NUL character instead of a newline, for use with
+    /// `xargs -0`
+    pub null: bool,
 }
 
 #[derive(Parser)]
 pub struct CpOpts {
     #[arg(short, long)]
-    /// Treat the first path as a glob pattern
+    /// Treat the paths as glob patterns
     pub glob: bool,
+    #[command(flatten)]
+    pub glob_opts: GlobOpts,
+    #[arg(long, conflicts_with = "glob")]
+    /// Also read paths of entries from the standard input, one per line. Same as passing `-` as
+    /// one of the paths
+    pub stdin: bool,
+    #[arg(long, short = '0', conflicts_with = "glob")]
+    /// Read paths from the standard input separated by NUL characters instead of newlines, for
+    /// use with `find -print0` or `fd -0`. Implies `--stdin`
+    pub null: bool,
     /// Path to the file from which to copy tags from
     pub input_path: PathBuf,
-    #[clap(required = true, last = true)]
+    #[clap(required_unless_present_any = ["stdin", "null"], last = true)]
     /// A list of entries to tag
     pub paths: Vec<String>,
 }
 
 #[derive(Parser)]
+#[clap(group(clap::ArgGroup::new("edit").required(true).multiple(true)))]
 pub struct EditOpts {
     /// The tag to edit
     pub tag: String,
-    #[arg(long, short)]
+    #[arg(long, short, group = "edit")]
     /// Set the color of the tag to the specified color. Accepted values are hex colors like
     /// `0x000000` or `#1F1F1F` or just plain `ff000a`. The colors are case insensitive meaning
     /// `1f1f1f` is equivalent to `1F1F1F`.
-    pub color: String,
+    pub color: Option<String>,
+    #[arg(long, group = "edit")]
+    /// Set the description of the tag explaining what it means. An empty description removes it.
+    pub description: Option<String>,
+    #[arg(long, group = "edit")]
+    /// Set an icon or emoji displayed next to the tag. An empty icon removes it.
+    pub icon: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct UndoOpts {
+    #[clap(default_value = "1")]
+    /// How many of the most recent operations to undo
+    pub count: usize,
+}
+
+#[derive(Parser)]
+pub struct RedoOpts {
+    #[clap(default_value = "1")]
+    /// How many of the most recently undone operations to redo
+    pub count: usize,
+}
+
+#[derive(Parser)]
+pub struct LogOpts {
+    #[arg(long, short)]
+    /// Only show changes made since this point in time. Accepts a RFC 3339 timestamp like
+    /// `2021-03-14T12:00:00Z`, a date like `2021-03-14` or a duration like `30m`, `12h` or `7d`
+    /// meaning that long ago.
+    pub since: Option<String>,
+    #[arg(long, short)]
+    /// Only show changes of this path or of paths below it
+    pub path: Option<PathBuf>,
+    #[arg(long, short, value_name = "TAG")]
+    /// Only show changes of this tag
+    pub tag: Option<String>,
+}
+
+#[derive(Parser)]
+pub enum RulesCommand {
+    /// Shows which rules of folders watched by the daemon match the file and what tags they
+    /// would apply to it.
+    Test {
+        /// Path of the file to test
+        path: PathBuf,
+    },
+}
+
+#[derive(Parser)]
+pub struct RulesOpts {
+    #[clap(subcommand)]
+    pub cmd: RulesCommand,
+}
+
+#[derive(Parser)]
+pub enum ImpliesCommand {
+    /// Adds a rule that anything tagged with `tag` is also tagged with `implies`.
+    Add {
+        /// The implying tag
+        tag: String,
+        /// The implied tag
+        implies: String,
+    },
+    /// Removes a rule that `tag` implies `implies`.
+    Rm {
+        /// The implying tag
+        tag: String,
+        /// The implied tag
+        implies: String,
+    },
+    /// Lists all implication rules.
+    List,
+}
+
+#[derive(Parser)]
+pub struct ImpliesOpts {
+    #[clap(subcommand)]
+    pub cmd: ImpliesCommand,
+}
+
+#[derive(Parser)]
+pub struct SuggestOpts {
+    /// The file for which to suggest tags
+    pub path: PathBuf,
+    #[arg(long, short, default_value = "10")]
+    /// Maximum number of suggested tags
+    pub limit: usize,
+}
+
+#[derive(Parser)]
+pub enum TagCommand {
+    /// Shows the description, icon, creation time, aliases and usage of a tag together with the
+    /// tags most often applied to the same files.
+    Info {
+        /// The tag to show
+        tag: String,
+        #[arg(long, default_value = "5")]
+        /// How many of the most frequently co-occurring tags to show
+        top: usize,
+    },
+}
+
+#[derive(Parser)]
+pub struct TagOpts {
+    #[clap(subcommand)]
+    pub cmd: TagCommand,
+}
+
+#[derive(Parser)]
+pub enum AliasCommand {
+    /// Makes `alias` an alias of `tag`. Files already tagged with `alias` are retagged with `tag`.
+    Add {
+        /// The alias
+        alias: String,
+        /// The canonical tag
+        tag: String,
+    },
+    /// Removes an alias.
+    Rm {
+        /// The alias to remove
+        alias: String,
+    },
+    /// Lists all aliases.
+    List,
+}
+
+#[derive(Parser)]
+pub struct AliasOpts {
+    #[clap(subcommand)]
+    pub cmd: AliasCommand,
+}
+
+#[derive(Parser)]
+pub enum SavedCommand {
+    /// Saves a search query under a name. The query takes the same arguments as `search`, for
+    /// example `"invoice --any --tagged-since 30d"`. Saving under an existing name replaces its
+    /// query.
+    Add {
+        /// Name of the saved search
+        name: String,
+        /// The search arguments
+        query: String,
+    },
+    /// Runs a saved search.
+    Run {
+        /// Name of the saved search
+        name: String,
+        #[command(flatten)]
+        sort: SortOpts,
+        #[command(flatten)]
+        page: PageOpts,
+    },
+    /// Removes a saved search.
+    Rm {
+        /// Name of the saved search
+        name: String,
+    },
+    /// Lists all saved searches.
+    List,
+}
+
+#[derive(Parser)]
+pub struct SavedOpts {
+    #[clap(subcommand)]
+    pub cmd: SavedCommand,
+}
+
+#[derive(Parser)]
+pub struct ExportOpts {
+    /// The file to write the export to, prints it to stdout if not provided
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+pub struct ImportOpts {
+    /// A file created by `wutag export`
+    pub path: PathBuf,
+}
+
+#[derive(Parser)]
+pub enum ViewCommand {
+    /// Builds a directory of symlinks with a subdirectory for each tag or, with `--query`, with
+    /// symlinks to the files found by the query. Running it again updates the directory.
+    Materialize {
+        /// The directory of the view, created if it doesn't exist
+        dir: PathBuf,
+        #[clap(long, short)]
+        /// Search arguments like `"invoice --tagged-since 30d"` selecting the linked files
+        query: Option<String>,
+    },
+    /// Removes the symlinks and directories created in a view. Anything else in the directory is
+    /// left in place.
+    Clean {
+        /// The directory of the view
+        dir: PathBuf,
+    },
+}
+
+#[derive(Parser)]
+pub struct ViewOpts {
+    #[clap(subcommand)]
+    pub cmd: ViewCommand,
+}
+
+#[derive(Parser)]
+pub struct AutoTagOpts {
+    /// A list of entries to tag
+    pub paths: Vec<String>,
+    #[arg(short, long)]
+    /// Treat the paths as glob patterns
+    pub glob: bool,
+    #[command(flatten)]
+    pub glob_opts: GlobOpts,
+    #[arg(long = "type")]
+    /// Tag the files with their content type detected from the data of the file like
+    /// `type/image`, `type/pdf` or `type/source`.
+    pub by_type: bool,
+    #[arg(long, short = 'M', action = clap::ArgAction::Append)]
+    /// Tag the files with a tag rendered from a template like `year={exif.year}` using metadata
+    /// of the files. Files missing any of the fields used in the template are not tagged with it.
+    /// Available fields are `exif.make`, `exif.model`, `exif.lens`, `exif.date`, `exif.year`,
+    /// `exif.month`, `exif.day` for photos and `audio.artist`, `audio.album_artist`,
+    /// `audio.album`, `audio.title`, `audio.genre`, `audio.year`, `audio.track` for MP3 and FLAC
+    /// files.
+    pub metadata: Vec<String>,
+    #[arg(long)]
+    /// Tag the files with parts of their names captured by a regular expression. Named groups
+    /// like `(?P<client>[a-z]+)` yield tags like `client=acme`, unnamed groups yield just the
+    /// captured text. Combine with `--dry-run` to preview the extracted tags.
+    pub from_name: Option<String>,
 }
 
 #[derive(Parser, Clone, Copy)]
@@ -216,8 +645,48 @@ pub enum Command {
     Search(SearchOpts),
     /// Copies tags from the specified file to files that match a pattern.
     Cp(CpOpts),
-    /// Edits a tag.
+    /// Edits the color, description or icon of a tag.
     Edit(EditOpts),
+    /// Shows details of tags.
+    Tag(TagOpts),
+    /// Suggests tags for a file based on tags applied together with its tags and tags of files in
+    /// the same directory or with the same extension.
+    Suggest(SuggestOpts),
+    /// Checks tags in the registry for names that are easy to confuse with each other or that
+    /// break the naming rules configured for the daemon.
+    Doctor,
+    /// Reverts the most recent operations that modified tags of files.
+    Undo(UndoOpts),
+    /// Applies again operations reverted with `undo`.
+    Redo(RedoOpts),
+    /// Lists recent operations that modified tags of files.
+    History,
+    /// Shows the audit log of all changes made to tags.
+    Log(LogOpts),
+    /// Manages rules that automatically tag new files in folders watched by the daemon.
+    Rules(RulesOpts),
+    /// Manages rules that make tags imply other tags. Searching for an implied tag also finds
+    /// entries tagged with tags that imply it.
+    Implies(ImpliesOpts),
+    /// Tags files with tags derived from the files themselves.
+    Autotag(AutoTagOpts),
+    /// Manages aliases of tags. An alias can be used anywhere in place of its canonical tag.
+    Alias(AliasOpts),
+    /// Manages saved searches. The name of a saved search can be used in place of a tag in
+    /// `search`.
+    Saved(SavedOpts),
+    /// Exports all tags, tagged entries, implication rules, aliases and saved searches as JSON or
+    /// with `--output-format yaml` as YAML.
+    Export(ExportOpts),
+    /// Imports tags, tagged entries, implication rules, aliases and saved searches exported with
+    /// `wutag export`. Files are tagged with the imported tags.
+    Import(ImportOpts),
+    /// Manages views of tags on disk made of symlinks to tagged files that can be browsed with
+    /// any file manager.
+    View(ViewOpts),
+    /// Lists names of all tags and aliases, used by shell completions.
+    #[clap(hide = true)]
+    CompleteTags,
     /// Prints completions for the specified shell to stdout.
     PrintCompletions(CompletionsOpts),
 }
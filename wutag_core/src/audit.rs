@@ -0,0 +1,234 @@
+//! Append-only log of changes made to tags stored as JSON lines.
+use crate::registry::{ChangeKind, TagChange};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("Failed to read audit log - {0}")]
+    ReadLog(io::Error),
+    #[error("Failed to write audit log - {0}")]
+    WriteLog(io::Error),
+    #[error("Failed to serialize audit log entry - {0}")]
+    SerializeEntry(serde_json::Error),
+}
+
+type Result<T> = std::result::Result<T, AuditError>;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Add,
+    Remove,
+    Edit,
+}
+
+impl From<ChangeKind> for AuditAction {
+    fn from(kind: ChangeKind) -> Self {
+        match kind {
+            ChangeKind::Add => AuditAction::Add,
+            ChangeKind::Remove => AuditAction::Remove,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+/// A single change of a tag recorded in the audit log.
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// Kind of the request that made the change, for example `tag_files`.
+    pub request: String,
+    /// Id of the client process if known.
+    pub pid: Option<u32>,
+    /// Id of the user running the client process if known.
+    pub uid: Option<u32>,
+    pub action: AuditAction,
+    /// Path of the entry that was changed, not set when the tag itself was edited.
+    pub path: Option<PathBuf>,
+    pub tag: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Additional information about the change, like the new color of an edited tag.
+    pub details: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new<S, T>(request: S, action: AuditAction, path: Option<PathBuf>, tag: T) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        Self {
+            timestamp: Utc::now(),
+            request: request.into(),
+            pid: None,
+            uid: None,
+            action,
+            path,
+            tag: tag.into(),
+            details: None,
+        }
+    }
+
+    /// Creates an entry describing the `change` made by the `request`.
+    pub fn change<S: Into<String>>(request: S, change: &TagChange) -> Self {
+        Self::new(
+            request,
+            change.kind.into(),
+            Some(change.path.clone()),
+            change.tag.name(),
+        )
+    }
+
+    /// Sets the credentials of the client that made the change.
+    pub fn with_client(mut self, pid: Option<u32>, uid: Option<u32>) -> Self {
+        self.pid = pid;
+        self.uid = uid;
+        self
+    }
+
+    pub fn with_details<S: Into<String>>(mut self, details: S) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// Criteria used to select entries of the audit log.
+pub struct AuditFilter {
+    /// Only entries recorded at or after this point in time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only entries of this path or of paths below it.
+    pub path: Option<PathBuf>,
+    /// Only entries concerning this tag.
+    pub tag: Option<String>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        if let Some(since) = &self.since {
+            if entry.timestamp < *since {
+                return false;
+            }
+        }
+        if let Some(path) = &self.path {
+            match &entry.path {
+                Some(entry_path) if entry_path.starts_with(path) => {}
+                _ => return false,
+            }
+        }
+        if let Some(tag) = &self.tag {
+            if &entry.tag != tag {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Handle to the audit log file. New entries are only ever appended to the file.
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Appends the `entries` to the end of the log with a single write.
+    pub fn append(&self, entries: &[AuditEntry]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut buf = vec![];
+        for entry in entries {
+            serde_json::to_writer(&mut buf, entry).map_err(AuditError::SerializeEntry)?;
+            buf.push(b'\n');
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(&buf))
+            .map_err(AuditError::WriteLog)
+    }
+
+    /// Reads entries of the log that match the `filter` in the order in which they were recorded.
+    /// Lines that can't be parsed are skipped.
+    pub fn read(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>> {
+        let data = match fs::read_to_string(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(AuditError::ReadLog(e)),
+        };
+
+        Ok(data
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .filter(|entry| filter.matches(entry))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag::Tag;
+    use colored::Color::*;
+
+    #[test]
+    fn appends_and_filters_entries() {
+        let tmp_dir = tempdir::TempDir::new("audit-test").unwrap();
+        let log = AuditLog::new(tmp_dir.path().join("wutag.audit"));
+        let tag = Tag::new("code", Red);
+
+        log.append(&[
+            AuditEntry::change("tag_files", &TagChange::add("/tmp/src/lib.rs", tag.clone()))
+                .with_client(Some(1), Some(1000)),
+            AuditEntry::change("untag_files", &TagChange::remove("/tmp/README.md", tag)),
+        ])
+        .unwrap();
+        log.append(&[
+            AuditEntry::new("edit_tag", AuditAction::Edit, None, "code").with_details("#ff0000")
+        ])
+        .unwrap();
+
+        let all = log.read(&AuditFilter::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].pid, Some(1));
+        assert_eq!(all[0].uid, Some(1000));
+        assert_eq!(all[2].details.as_deref(), Some("#ff0000"));
+
+        let under_src = log
+            .read(&AuditFilter {
+                path: Some("/tmp/src".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(under_src.len(), 1);
+        assert_eq!(under_src[0].action, AuditAction::Add);
+
+        let code = log
+            .read(&AuditFilter {
+                tag: Some("code".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(code.len(), 3);
+
+        let future = log
+            .read(&AuditFilter {
+                since: Some(Utc::now() + chrono::Duration::days(1)),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(future.is_empty());
+    }
+}
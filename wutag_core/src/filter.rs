@@ -0,0 +1,365 @@
+//! Filters narrowing down search results by location, type, size and modification time of files.
+use crate::time::parse_time;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum FilterError {
+    #[error("`{0}` is not a valid file type, expected one of `f`, `d` or `l`")]
+    InvalidType(String),
+    #[error(
+        "`{0}` is not a valid size, expected a number with an optional sign and unit like `+10M`"
+    )]
+    InvalidSize(String),
+    #[error("`{0}` is not a valid modification time, expected a time prefixed with `-` or `+` like `-7d`")]
+    InvalidModified(String),
+    #[error("invalid regex `{0}` - {1}")]
+    InvalidRegex(String, regex::Error),
+}
+
+type Result<T> = std::result::Result<T, FilterError>;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+/// Type of a file as reported without following symbolic links.
+pub enum FileType {
+    File,
+    Dir,
+    Symlink,
+}
+
+impl FileType {
+    fn matches(&self, metadata: &Metadata) -> bool {
+        let file_type = metadata.file_type();
+        match self {
+            FileType::File => file_type.is_file(),
+            FileType::Dir => file_type.is_dir(),
+            FileType::Symlink => file_type.is_symlink(),
+        }
+    }
+}
+
+impl FromStr for FileType {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "f" | "file" => Ok(FileType::File),
+            "d" | "dir" => Ok(FileType::Dir),
+            "l" | "symlink" => Ok(FileType::Symlink),
+            _ => Err(FilterError::InvalidType(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Comparison {
+    Less,
+    Equal,
+    Greater,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+/// Condition on the size of a file parsed from values like `+10M`, `-512k` or `4096`. Like in
+/// `find -size` the size of the file is rounded up to whole `unit`s before comparing it with the
+/// `value`, so `-1M` only matches empty files.
+pub struct SizeFilter {
+    pub comparison: Comparison,
+    pub value: u64,
+    /// Size of the unit in bytes.
+    pub unit: u64,
+}
+
+impl SizeFilter {
+    pub fn matches(&self, size: u64) -> bool {
+        let units = size.div_ceil(self.unit);
+        match self.comparison {
+            Comparison::Less => units < self.value,
+            Comparison::Equal => units == self.value,
+            Comparison::Greater => units > self.value,
+        }
+    }
+}
+
+impl FromStr for SizeFilter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || FilterError::InvalidSize(s.to_string());
+        let s = s.trim();
+        let (comparison, rest) = if let Some(rest) = s.strip_prefix('+') {
+            (Comparison::Greater, rest)
+        } else if let Some(rest) = s.strip_prefix('-') {
+            (Comparison::Less, rest)
+        } else {
+            (Comparison::Equal, s)
+        };
+        let unit_start = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (value, unit) = rest.split_at(unit_start);
+        let value = value.parse().map_err(|_| invalid())?;
+        let unit = match unit {
+            "" | "b" | "B" | "c" => 1,
+            "k" | "K" => 1 << 10,
+            "M" => 1 << 20,
+            "G" => 1 << 30,
+            "T" => 1 << 40,
+            _ => return Err(invalid()),
+        };
+
+        Ok(SizeFilter {
+            comparison,
+            value,
+            unit,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+/// Condition on the modification time of a file parsed from values like `-7d` meaning modified
+/// in the last 7 days or `+2021-03-14` meaning modified before that date.
+pub enum ModifiedFilter {
+    /// Modified at or after this time.
+    Since(DateTime<Utc>),
+    /// Modified before this time.
+    Before(DateTime<Utc>),
+}
+
+impl ModifiedFilter {
+    pub fn matches(&self, modified: &DateTime<Utc>) -> bool {
+        match self {
+            ModifiedFilter::Since(since) => modified >= since,
+            ModifiedFilter::Before(before) => modified < before,
+        }
+    }
+}
+
+impl FromStr for ModifiedFilter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || FilterError::InvalidModified(s.to_string());
+        let s = s.trim();
+        if let Some(time) = s.strip_prefix('-') {
+            parse_time(time)
+                .map(ModifiedFilter::Since)
+                .map_err(|_| invalid())
+        } else if let Some(time) = s.strip_prefix('+') {
+            parse_time(time)
+                .map(ModifiedFilter::Before)
+                .map_err(|_| invalid())
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// Conditions on the files themselves that all have to match. Conditions that are not set always
+/// match.
+pub struct FileFilter {
+    /// Only files in this directory or any of its subdirectories.
+    pub under: Option<PathBuf>,
+    pub file_type: Option<FileType>,
+    pub size: Vec<SizeFilter>,
+    pub modified: Vec<ModifiedFilter>,
+    /// Regular expression matched against the whole path.
+    pub path_regex: Option<String>,
+}
+
+impl FileFilter {
+    /// Checks whether the filter has no conditions.
+    pub fn is_empty(&self) -> bool {
+        self == &FileFilter::default()
+    }
+
+    /// Compiles the filter into a matcher that can be applied to many paths.
+    pub fn matcher(&self) -> Result<FileMatcher<'_>> {
+        let regex = self
+            .path_regex
+            .as_ref()
+            .map(|regex| Regex::new(regex).map_err(|e| FilterError::InvalidRegex(regex.clone(), e)))
+            .transpose()?;
+        Ok(FileMatcher {
+            filter: self,
+            regex,
+        })
+    }
+}
+
+pub struct FileMatcher<'a> {
+    filter: &'a FileFilter,
+    regex: Option<Regex>,
+}
+
+impl FileMatcher<'_> {
+    /// Checks whether the file at `path` matches all conditions. Conditions on the path are checked
+    /// first so that metadata is only read for files that can still match. Files whose metadata
+    /// can't be read don't match conditions that need it.
+    pub fn matches(&self, path: &Path) -> bool {
+        let filter = self.filter;
+        if let Some(under) = &filter.under {
+            if !path.starts_with(under) {
+                return false;
+            }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&path.to_string_lossy()) {
+                return false;
+            }
+        }
+        if filter.file_type.is_none() && filter.size.is_empty() && filter.modified.is_empty() {
+            return true;
+        }
+
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(_) => return false,
+        };
+        if let Some(file_type) = &filter.file_type {
+            if !file_type.matches(&metadata) {
+                return false;
+            }
+        }
+        if !filter.size.iter().all(|size| size.matches(metadata.len())) {
+            return false;
+        }
+        if !filter.modified.is_empty() {
+            let modified = match metadata.modified() {
+                Ok(modified) => DateTime::<Utc>::from(modified),
+                Err(_) => return false,
+            };
+            if !filter.modified.iter().all(|m| m.matches(&modified)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn parses_sizes() {
+        let size: SizeFilter = "+10M".parse().unwrap();
+        assert_eq!(size.comparison, Comparison::Greater);
+        assert_eq!((size.value, size.unit), (10, 1 << 20));
+        let size: SizeFilter = "-512k".parse().unwrap();
+        assert_eq!(size.comparison, Comparison::Less);
+        assert_eq!((size.value, size.unit), (512, 1 << 10));
+        let size: SizeFilter = "4096".parse().unwrap();
+        assert_eq!(size.comparison, Comparison::Equal);
+        assert_eq!((size.value, size.unit), (4096, 1));
+        assert!("+10X".parse::<SizeFilter>().is_err());
+        assert!("M".parse::<SizeFilter>().is_err());
+        assert!("".parse::<SizeFilter>().is_err());
+
+        let size: SizeFilter = "+1M".parse().unwrap();
+        assert!(!size.matches(1 << 20));
+        assert!(size.matches((1 << 20) + 1));
+        let size: SizeFilter = "-1M".parse().unwrap();
+        assert!(size.matches(0));
+        assert!(!size.matches(1));
+    }
+
+    #[test]
+    fn parses_modification_times() {
+        let now = Utc::now();
+        match "-7d".parse().unwrap() {
+            ModifiedFilter::Since(since) => {
+                assert!(since > now - Duration::days(8) && since < now - Duration::days(6))
+            }
+            other => panic!("unexpected filter {other:?}"),
+        }
+        assert!(matches!(
+            "+2021-03-14".parse().unwrap(),
+            ModifiedFilter::Before(_)
+        ));
+        assert!("7d".parse::<ModifiedFilter>().is_err());
+        assert!("-7x".parse::<ModifiedFilter>().is_err());
+
+        let week_ago = ModifiedFilter::Since(now - Duration::days(7));
+        assert!(week_ago.matches(&now));
+        assert!(!week_ago.matches(&(now - Duration::days(8))));
+    }
+
+    #[test]
+    fn matches_files() {
+        let tmp_dir = tempdir::TempDir::new("filter-test").unwrap();
+        let dir = tmp_dir.path();
+        let small = dir.join("small.txt");
+        let big = dir.join("big.bin");
+        let sub = dir.join("sub");
+        fs::write(&small, b"hello").unwrap();
+        fs::write(&big, vec![0; 2048]).unwrap();
+        fs::create_dir(&sub).unwrap();
+
+        let all = [small.clone(), big.clone(), sub.clone()];
+        let matching = |filter: FileFilter| -> Vec<PathBuf> {
+            let matcher = filter.matcher().unwrap();
+            all.iter().filter(|p| matcher.matches(p)).cloned().collect()
+        };
+
+        assert_eq!(matching(FileFilter::default()), all.to_vec());
+        assert_eq!(
+            matching(FileFilter {
+                file_type: Some(FileType::Dir),
+                ..Default::default()
+            }),
+            vec![sub.clone()]
+        );
+        assert_eq!(
+            matching(FileFilter {
+                file_type: Some(FileType::File),
+                size: vec!["+1k".parse().unwrap()],
+                ..Default::default()
+            }),
+            vec![big.clone()]
+        );
+        assert_eq!(
+            matching(FileFilter {
+                path_regex: Some(r"\.txt$".into()),
+                ..Default::default()
+            }),
+            vec![small.clone()]
+        );
+        assert_eq!(
+            matching(FileFilter {
+                under: Some(sub.clone()),
+                ..Default::default()
+            }),
+            vec![sub.clone()]
+        );
+        assert_eq!(
+            matching(FileFilter {
+                modified: vec!["+1d".parse().unwrap()],
+                ..Default::default()
+            }),
+            Vec::<PathBuf>::new()
+        );
+        assert!(!FileFilter {
+            size: vec!["-1M".parse().unwrap()],
+            ..Default::default()
+        }
+        .matcher()
+        .unwrap()
+        .matches(&dir.join("missing")));
+        assert!(FileFilter {
+            path_regex: Some("(".into()),
+            ..Default::default()
+        }
+        .matcher()
+        .is_err());
+    }
+}
@@ -1,21 +1,38 @@
 use crate::{Error, Result};
-use globwalk::{GlobWalker, GlobWalkerBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::WalkBuilder;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
-/// Default max depth passed to [GlobWalker](globwalker::GlobWalker)
+/// Default max depth of the traversal of the base directory
 pub const DEFAULT_MAX_DEPTH: usize = 2;
 
 #[derive(Debug, Deserialize, Serialize)]
+/// Patterns matched against paths of entries below the base directory. The patterns use the syntax
+/// of `.gitignore` files, patterns without a `/` match the name of an entry at any depth.
 pub struct Glob {
-    pub pattern: String,
+    /// Entries matching any of these patterns are selected
+    pub patterns: Vec<String>,
+    /// Entries matching any of these patterns are skipped together with everything below them
+    pub excludes: Vec<String>,
     pub base_dir: PathBuf,
-    pub max_depth: usize,
+    /// How deep below the base directory to look for entries, `None` means there is no limit
+    pub max_depth: Option<usize>,
+    /// Whether to select entries with names starting with a `.` and look into such directories
+    pub hidden: bool,
+    /// Whether to skip entries ignored by `.gitignore` and `.ignore` files, `.git/info/exclude`
+    /// and the global git excludes file
+    pub ignore_files: bool,
+    /// Whether to follow symbolic links to directories
+    pub follow_links: bool,
 }
 
 impl Glob {
+    /// Creates a glob selecting entries matching any of the `patterns` with hidden entries
+    /// included, ignore files not respected and links not followed. If `base_dir` is not provided
+    /// the current working directory is used.
     pub fn new(
-        pattern: String,
+        patterns: Vec<String>,
         base_dir: Option<PathBuf>,
         max_depth: Option<usize>,
     ) -> Result<Self> {
@@ -23,42 +40,145 @@ impl Glob {
             .or_else(|| std::env::current_dir().ok())
             .ok_or(Error::GetCurrentWorkingDir)?;
         Ok(Self {
-            pattern,
+            patterns,
+            excludes: Vec::new(),
             base_dir,
-            max_depth: max_depth.unwrap_or(DEFAULT_MAX_DEPTH),
+            max_depth,
+            hidden: true,
+            ignore_files: false,
+            follow_links: false,
         })
     }
 
+    /// Returns paths of all entries below the base directory matching the patterns.
     pub fn glob_paths(&self) -> Result<Vec<PathBuf>> {
-        paths(&self.pattern, &self.base_dir, Some(self.max_depth))
+        let overrides = self.overrides()?;
+        let root = self.base_dir.clone();
+        let excludes = overrides.clone();
+
+        let mut walker = WalkBuilder::new(&self.base_dir);
+        walker
+            .standard_filters(self.ignore_files)
+            .require_git(false)
+            .hidden(!self.hidden)
+            .follow_links(self.follow_links)
+            .max_depth(self.max_depth)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                !(is_dir
+                    && excludes
+                        .matched(relative(entry.path(), &root), true)
+                        .is_ignore())
+            });
+
+        Ok(walker
+            .build()
+            .flatten()
+            .filter(|entry| entry.depth() > 0)
+            .filter(|entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                overrides
+                    .matched(relative(entry.path(), &self.base_dir), is_dir)
+                    .is_whitelist()
+            })
+            .map(|entry| entry.into_path())
+            .collect())
+    }
+
+    fn overrides(&self) -> Result<Override> {
+        let mut builder = OverrideBuilder::new(&self.base_dir);
+        for pattern in &self.patterns {
+            builder.add(pattern)?;
+        }
+        for pattern in &self.excludes {
+            builder.add(&format!("!{pattern}"))?;
+        }
+        builder.build().map_err(Error::from)
     }
 }
 
-/// Returns a GlobWalker instance with base path set to `base_path` and pattern to `pattern`. If
-/// max_depth is specified the GlobWalker will have it's max depth set to its value, otherwise max
-/// depth will be [DEFAULT_MAX_DEPTH](DEFAULT_MAX_DEPTH).
-pub fn walker<S>(dir: S, pattern: S, max_depth: Option<usize>) -> Result<GlobWalker>
-where
-    S: AsRef<str>,
-{
-    let mut builder = GlobWalkerBuilder::new(dir.as_ref(), pattern.as_ref());
+fn relative<'a>(path: &'a Path, root: &Path) -> &'a Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
 
-    if let Some(max_depth) = max_depth {
-        builder = builder.max_depth(max_depth);
-    } else {
-        builder = builder.max_depth(DEFAULT_MAX_DEPTH);
+    fn glob_names(glob: &Glob) -> Vec<String> {
+        let mut names: Vec<_> = glob
+            .glob_paths()
+            .unwrap()
+            .into_iter()
+            .map(|path| {
+                path.strip_prefix(&glob.base_dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        names.sort();
+        names
     }
-    builder.build().map_err(Error::from)
-}
 
-pub fn paths<P>(pattern: &str, base_path: P, max_depth: Option<usize>) -> Result<Vec<PathBuf>>
-where
-    P: AsRef<Path>,
-{
-    let base_path = base_path.as_ref().to_string_lossy().to_string();
+    #[test]
+    fn globs_paths() {
+        let dir = TempDir::new("wutag_glob").unwrap();
+        let root = dir.path();
+        for path in [
+            "a.jpg",
+            "b.png",
+            "c.txt",
+            ".hidden.jpg",
+            "photos/d.jpg",
+            "photos/2021/e.jpg",
+            "photos/2021/raw/f.jpg",
+            "build/g.jpg",
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+        fs::write(root.join(".gitignore"), "build/\n").unwrap();
+
+        let patterns = vec!["*.jpg".to_string(), "*.png".to_string()];
+        let mut glob = Glob::new(patterns, Some(root.to_path_buf()), Some(2)).unwrap();
+        assert_eq!(
+            glob_names(&glob),
+            vec![
+                ".hidden.jpg",
+                "a.jpg",
+                "b.png",
+                "build/g.jpg",
+                "photos/d.jpg"
+            ]
+        );
+
+        glob.max_depth = None;
+        glob.excludes = vec!["raw".into()];
+        assert_eq!(
+            glob_names(&glob),
+            vec![
+                ".hidden.jpg",
+                "a.jpg",
+                "b.png",
+                "build/g.jpg",
+                "photos/2021/e.jpg",
+                "photos/d.jpg"
+            ]
+        );
 
-    Ok(walker(base_path.as_str(), pattern, max_depth)?
-        .flatten()
-        .map(|entry| entry.into_path())
-        .collect())
+        glob.hidden = false;
+        glob.ignore_files = true;
+        assert_eq!(
+            glob_names(&glob),
+            vec!["a.jpg", "b.png", "photos/2021/e.jpg", "photos/d.jpg"]
+        );
+
+        glob.patterns = vec!["photos/*".into()];
+        glob.excludes = vec!["*.png".into()];
+        assert_eq!(glob_names(&glob), vec!["photos/2021", "photos/d.jpg"]);
+    }
 }
@@ -0,0 +1,253 @@
+//! Bounded history of changes applied to tags of files that allows undoing and redoing them.
+use crate::registry::TagChange;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Default maximum number of entries kept in the journal.
+pub const DEFAULT_HISTORY_SIZE: usize = 100;
+
+#[derive(Debug, Error)]
+pub enum JournalError {
+    #[error("Failed to load journal - {0}")]
+    LoadJournal(io::Error),
+    #[error("Failed to deserialize journal - {0}")]
+    DeserializeJournal(serde_cbor::Error),
+    #[error("Failed to save journal - {0}")]
+    SaveJournal(io::Error),
+    #[error("Failed to serialize journal - {0}")]
+    SerializeJournal(serde_cbor::Error),
+}
+
+type Result<T> = std::result::Result<T, JournalError>;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+/// Changes applied while handling a single request.
+pub struct JournalEntry {
+    pub timestamp: DateTime<Utc>,
+    /// Kind of the request that made the changes, for example `tag_files`.
+    pub request: String,
+    pub changes: Vec<TagChange>,
+    #[serde(default)]
+    /// Id of the operation the request was a part of. Entries of an operation split into multiple
+    /// requests are merged into one.
+    pub operation: Option<u64>,
+}
+
+impl JournalEntry {
+    pub fn new<S: Into<String>>(request: S, changes: Vec<TagChange>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            request: request.into(),
+            changes,
+            operation: None,
+        }
+    }
+
+    /// Marks the entry as a part of the `operation`.
+    pub fn with_operation(mut self, operation: Option<u64>) -> Self {
+        self.operation = operation;
+        self
+    }
+
+    /// Returns changes that revert this entry in the order in which they should be applied.
+    pub fn inverse_changes(&self) -> Vec<TagChange> {
+        self.changes.iter().rev().map(TagChange::inverse).collect()
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Journal {
+    /// Applied entries, the most recent one is at the back.
+    applied: VecDeque<JournalEntry>,
+    /// Undone entries that can be redone, the most recently undone is at the back.
+    undone: Vec<JournalEntry>,
+    max_entries: usize,
+    path: PathBuf,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self {
+            applied: VecDeque::new(),
+            undone: vec![],
+            max_entries: DEFAULT_HISTORY_SIZE,
+            path: PathBuf::new(),
+        }
+    }
+}
+
+impl Journal {
+    /// Creates a new empty instance of `Journal` with a `path` without loading it.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    /// Loads a journal from the specified `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = fs::read(path.as_ref()).map_err(JournalError::LoadJournal)?;
+
+        serde_cbor::from_slice(&data).map_err(JournalError::DeserializeJournal)
+    }
+
+    /// Saves the journal serialized to the path from which it was loaded.
+    pub fn save(&self) -> Result<()> {
+        let serialized = serde_cbor::to_vec(&self).map_err(JournalError::SerializeJournal)?;
+        fs::write(&self.path, serialized).map_err(JournalError::SaveJournal)
+    }
+
+    /// Records a newly applied entry. This drops all undone entries as they can no longer be
+    /// redone and the oldest entries if the journal is full. If the most recent entry is a part of
+    /// the same operation made by the same request the changes are appended to it instead.
+    pub fn record(&mut self, entry: JournalEntry) {
+        self.undone.clear();
+        if let Some(last) = self.applied.back_mut() {
+            if entry.operation.is_some()
+                && last.operation == entry.operation
+                && last.request == entry.request
+            {
+                last.changes.extend(entry.changes);
+                return;
+            }
+        }
+        self.push_applied(entry);
+    }
+
+    fn push_applied(&mut self, entry: JournalEntry) {
+        self.applied.push_back(entry);
+        while self.applied.len() > self.max_entries {
+            self.applied.pop_front();
+        }
+    }
+
+    /// Takes the most recently applied entry so that it can be undone.
+    pub fn pop_applied(&mut self) -> Option<JournalEntry> {
+        self.applied.pop_back()
+    }
+
+    /// Marks the `entry` as undone so that it can be redone later.
+    pub fn push_undone(&mut self, entry: JournalEntry) {
+        self.undone.push(entry);
+    }
+
+    /// Takes the most recently undone entry so that it can be redone.
+    pub fn pop_undone(&mut self) -> Option<JournalEntry> {
+        self.undone.pop()
+    }
+
+    /// Marks the `entry` as applied again after redoing it.
+    pub fn push_redone(&mut self, entry: JournalEntry) {
+        self.push_applied(entry);
+    }
+
+    /// Lists applied entries starting with the most recent one.
+    pub fn list_applied(&self) -> impl Iterator<Item = &JournalEntry> {
+        self.applied.iter().rev()
+    }
+
+    /// Lists undone entries starting with the most recently undone one.
+    pub fn list_undone(&self) -> impl Iterator<Item = &JournalEntry> {
+        self.undone.iter().rev()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag::Tag;
+    use colored::Color::*;
+
+    fn entry(path: &str) -> JournalEntry {
+        JournalEntry::new(
+            "tag_files",
+            vec![TagChange::add(path, Tag::new("test", Red))],
+        )
+    }
+
+    #[test]
+    fn undoes_and_redoes_entries() {
+        let mut journal = Journal::default();
+        journal.record(entry("/tmp/1"));
+        journal.record(entry("/tmp/2"));
+
+        let undone = journal.pop_applied().unwrap();
+        assert_eq!(undone.changes, entry("/tmp/2").changes);
+        journal.push_undone(undone);
+        assert_eq!(journal.list_applied().count(), 1);
+        assert_eq!(journal.list_undone().count(), 1);
+
+        let redone = journal.pop_undone().unwrap();
+        journal.push_redone(redone);
+        assert_eq!(journal.list_applied().count(), 2);
+        assert!(journal.pop_undone().is_none());
+
+        let undone = journal.pop_applied().unwrap();
+        journal.push_undone(undone);
+        journal.record(entry("/tmp/3"));
+        assert!(journal.pop_undone().is_none());
+    }
+
+    #[test]
+    fn merges_entries_of_an_operation() {
+        let mut journal = Journal::default();
+        journal.record(entry("/tmp/1").with_operation(Some(1)));
+        journal.record(entry("/tmp/2").with_operation(Some(1)));
+        journal.record(entry("/tmp/3").with_operation(Some(2)));
+        journal.record(entry("/tmp/4"));
+        journal.record(entry("/tmp/5"));
+        assert_eq!(journal.list_applied().count(), 4);
+
+        let paths: Vec<_> = journal
+            .list_applied()
+            .last()
+            .unwrap()
+            .changes
+            .iter()
+            .map(|change| change.path.clone())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("/tmp/1"), PathBuf::from("/tmp/2")]
+        );
+    }
+
+    #[test]
+    fn drops_oldest_entries() {
+        let mut journal = Journal::default();
+        for i in 0..DEFAULT_HISTORY_SIZE + 5 {
+            journal.record(entry(&format!("/tmp/{i}")));
+        }
+        assert_eq!(journal.list_applied().count(), DEFAULT_HISTORY_SIZE);
+        assert_eq!(
+            journal.list_applied().last().unwrap().changes[0].path,
+            PathBuf::from("/tmp/5")
+        );
+    }
+
+    #[test]
+    fn inverses_changes_in_reverse_order() {
+        let tag = Tag::new("test", Red);
+        let entry = JournalEntry::new(
+            "copy_tags",
+            vec![
+                TagChange::add("/tmp/1", tag.clone()),
+                TagChange::remove("/tmp/2", tag.clone()),
+            ],
+        );
+        assert_eq!(
+            entry.inverse_changes(),
+            vec![
+                TagChange::add("/tmp/2", tag.clone()),
+                TagChange::remove("/tmp/1", tag),
+            ]
+        );
+    }
+}
@@ -1,7 +1,17 @@
+pub mod audit;
 pub mod color;
+pub mod filter;
 pub mod glob;
+pub mod journal;
+pub mod metadata;
+pub mod mime;
+pub mod query;
 pub mod registry;
+pub mod rules;
+pub mod sort;
 pub mod tag;
+pub mod time;
+pub mod view;
 pub mod xattr;
 
 use std::{ffi, io, string};
@@ -38,9 +48,13 @@ pub enum Error {
     #[error("xattributes limit reached on the file - `{0}`")]
     TagListFull(io::Error),
     #[error("failed to glob pattern - {0}")]
-    Glob(#[from] globwalk::GlobError),
+    Glob(#[from] ignore::Error),
     #[error("failed to determine current working directory")]
     GetCurrentWorkingDir,
+    #[error("`{0}` is not a valid time or duration")]
+    InvalidTime(String),
+    #[error("invalid tag name `{0}` - {1}")]
+    InvalidTagName(String, String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,547 @@
+//! Extraction of metadata fields like the camera model of a photo or the artist of a song and
+//! templates that turn them into tags, as well as patterns extracting tags from names of files.
+use crate::mime::{self, Kind};
+use id3::TagLike;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MetadataError {
+    #[error("failed to read `{0}` - {1}")]
+    Read(PathBuf, io::Error),
+    #[error("failed to read EXIF data of `{0}` - {1}")]
+    Exif(PathBuf, exif::Error),
+    #[error("failed to read ID3 tag of `{0}` - {1}")]
+    Id3(PathBuf, id3::Error),
+    #[error("invalid FLAC metadata in `{0}`")]
+    Flac(PathBuf),
+    #[error("invalid template `{0}` - {1}")]
+    InvalidTemplate(String, String),
+    #[error("invalid name pattern `{0}` - {1}")]
+    InvalidNamePattern(String, String),
+}
+
+type Result<T> = std::result::Result<T, MetadataError>;
+
+/// Fields that can be extracted from files and used in templates.
+pub const FIELDS: &[&str] = &[
+    "exif.make",
+    "exif.model",
+    "exif.lens",
+    "exif.date",
+    "exif.year",
+    "exif.month",
+    "exif.day",
+    "audio.artist",
+    "audio.album_artist",
+    "audio.album",
+    "audio.title",
+    "audio.genre",
+    "audio.year",
+    "audio.track",
+];
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// Metadata fields of a file mapped by their names from [FIELDS](FIELDS).
+pub struct Metadata(BTreeMap<String, String>);
+
+impl Metadata {
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.0.get(field).map(String::as_str)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Sets the `field` to the `value` without surrounding whitespace unless the value is blank.
+    fn insert(&mut self, field: &str, value: impl AsRef<str>) {
+        let value = value
+            .as_ref()
+            .trim_matches(|c: char| c.is_whitespace() || c == '\0');
+        if !value.is_empty() {
+            self.0.insert(field.to_string(), value.to_string());
+        }
+    }
+
+    fn insert_date(&mut self, prefix: &str, year: i32, month: Option<u8>, day: Option<u8>) {
+        self.insert(&format!("{prefix}.year"), year.to_string());
+        if let Some(month) = month {
+            self.insert(&format!("{prefix}.month"), format!("{month:02}"));
+            if let Some(day) = day {
+                self.insert(&format!("{prefix}.day"), format!("{day:02}"));
+                self.insert(
+                    &format!("{prefix}.date"),
+                    format!("{year:04}-{month:02}-{day:02}"),
+                );
+            }
+        }
+    }
+}
+
+/// Reads the metadata of the file at `path`. The format of the metadata is chosen by the detected
+/// content type of the file. Files without any known metadata yield empty metadata.
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Metadata> {
+    let path = path.as_ref();
+    let content = mime::detect(path).map_err(|e| MetadataError::Read(path.to_path_buf(), e))?;
+    match (content.kind, content.mime) {
+        (_, "audio/mpeg") => read_id3(path),
+        (_, "audio/flac") => read_flac(path),
+        (Kind::Image, _) => read_exif(path),
+        _ => Ok(Metadata::default()),
+    }
+}
+
+fn exif_ascii(exif: &exif::Exif, tag: exif::Tag) -> Option<Vec<u8>> {
+    match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+        exif::Value::Ascii(values) => values.first().cloned(),
+        _ => None,
+    }
+}
+
+fn read_exif(path: &Path) -> Result<Metadata> {
+    let file = File::open(path).map_err(|e| MetadataError::Read(path.to_path_buf(), e))?;
+    let exif = match exif::Reader::new().read_from_container(&mut BufReader::new(file)) {
+        Ok(exif) => exif,
+        Err(exif::Error::NotFound(_) | exif::Error::BlankValue(_)) => {
+            return Ok(Metadata::default())
+        }
+        Err(e) => return Err(MetadataError::Exif(path.to_path_buf(), e)),
+    };
+
+    let mut metadata = Metadata::default();
+    for (field, tag) in [
+        ("exif.make", exif::Tag::Make),
+        ("exif.model", exif::Tag::Model),
+        ("exif.lens", exif::Tag::LensModel),
+    ] {
+        if let Some(value) = exif_ascii(&exif, tag) {
+            metadata.insert(field, String::from_utf8_lossy(&value));
+        }
+    }
+
+    let taken = exif_ascii(&exif, exif::Tag::DateTimeOriginal)
+        .or_else(|| exif_ascii(&exif, exif::Tag::DateTime))
+        .and_then(|value| exif::DateTime::from_ascii(&value).ok());
+    if let Some(taken) = taken {
+        metadata.insert_date(
+            "exif",
+            taken.year.into(),
+            Some(taken.month),
+            Some(taken.day),
+        );
+    }
+
+    Ok(metadata)
+}
+
+fn read_id3(path: &Path) -> Result<Metadata> {
+    let tag = match id3::no_tag_ok(id3::Tag::read_from_path(path)) {
+        Ok(Some(tag)) => tag,
+        Ok(None) => return Ok(Metadata::default()),
+        Err(e) => return Err(MetadataError::Id3(path.to_path_buf(), e)),
+    };
+
+    let mut metadata = Metadata::default();
+    for (field, value) in [
+        ("audio.artist", tag.artist()),
+        ("audio.album_artist", tag.album_artist()),
+        ("audio.album", tag.album()),
+        ("audio.title", tag.title()),
+    ] {
+        if let Some(value) = value {
+            metadata.insert(field, value);
+        }
+    }
+    if let Some(genre) = tag.genre_parsed() {
+        metadata.insert("audio.genre", genre);
+    }
+    if let Some(track) = tag.track() {
+        metadata.insert("audio.track", track.to_string());
+    }
+    if let Some(recorded) = tag.date_recorded() {
+        metadata.insert("audio.year", recorded.year.to_string());
+    } else if let Some(year) = tag.year() {
+        metadata.insert("audio.year", year.to_string());
+    }
+
+    Ok(metadata)
+}
+
+/// Type of the metadata block of a FLAC file that contains Vorbis comments.
+const FLAC_VORBIS_COMMENT: u8 = 4;
+
+fn read_flac(path: &Path) -> Result<Metadata> {
+    let file = File::open(path).map_err(|e| MetadataError::Read(path.to_path_buf(), e))?;
+    let comments = flac_comments(BufReader::new(file)).map_err(|e| match e.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+            MetadataError::Flac(path.to_path_buf())
+        }
+        _ => MetadataError::Read(path.to_path_buf(), e),
+    })?;
+
+    let mut metadata = Metadata::default();
+    for (key, value) in comments {
+        let field = match key.to_uppercase().as_str() {
+            "ARTIST" => "audio.artist",
+            "ALBUMARTIST" => "audio.album_artist",
+            "ALBUM" => "audio.album",
+            "TITLE" => "audio.title",
+            "GENRE" => "audio.genre",
+            "TRACKNUMBER" => "audio.track",
+            "DATE" | "YEAR" => {
+                let year: String = value.chars().take(4).collect();
+                if year.len() == 4 && year.chars().all(|c| c.is_ascii_digit()) {
+                    metadata.insert("audio.year", year);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if metadata.get(field).is_none() {
+            metadata.insert(field, value);
+        }
+    }
+
+    Ok(metadata)
+}
+
+fn read_u32_le(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Reads the Vorbis comments from the metadata blocks of a FLAC stream.
+fn flac_comments(mut reader: impl Read) -> io::Result<Vec<(String, String)>> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    loop {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header)?;
+        let is_last = header[0] & 0x80 != 0;
+        let kind = header[0] & 0x7f;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as u64;
+        let mut block = (&mut reader).take(len);
+
+        if kind == FLAC_VORBIS_COMMENT {
+            let vendor_len = read_u32_le(&mut block)? as u64;
+            io::copy(&mut (&mut block).take(vendor_len), &mut io::sink())?;
+            let count = read_u32_le(&mut block)?;
+            let mut comments = vec![];
+            for _ in 0..count {
+                let len = read_u32_le(&mut block)? as u64;
+                let mut comment = vec![];
+                (&mut block).take(len).read_to_end(&mut comment)?;
+                let comment = String::from_utf8_lossy(&comment);
+                if let Some((key, value)) = comment.split_once('=') {
+                    comments.push((key.to_string(), value.to_string()));
+                }
+            }
+            return Ok(comments);
+        }
+
+        io::copy(&mut block, &mut io::sink())?;
+        if is_last {
+            return Ok(vec![]);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A template like `year={exif.year}` that is rendered into a tag by replacing the names of fields
+/// in braces with their values.
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parses the `template` checking that all referenced fields are known. A literal brace is
+    /// written as `{{` or `}}`.
+    pub fn parse(template: &str) -> Result<Self> {
+        let invalid = |reason: &str| MetadataError::InvalidTemplate(template.into(), reason.into());
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err(invalid("unterminated `{`")),
+                        }
+                    }
+                    let field = field.trim();
+                    if !FIELDS.contains(&field) {
+                        return Err(invalid(&format!("unknown field `{field}`")));
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field.to_string()));
+                }
+                '}' => return Err(invalid("unmatched `}`")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        if !parts.iter().any(|part| matches!(part, Part::Field(_))) {
+            return Err(invalid("template doesn't reference any field"));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Renders the template with values from `metadata`. Returns `None` if any of the referenced
+    /// fields is missing.
+    pub fn render(&self, metadata: &Metadata) -> Option<String> {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Field(field) => rendered.push_str(metadata.get(field)?),
+            }
+        }
+        Some(rendered)
+    }
+}
+
+#[derive(Clone, Debug)]
+/// A regular expression matched against names of files that turns its capture groups into tags.
+/// Named groups like `(?P<client>\w+)` yield key/value tags like `client=acme` while unnamed
+/// groups yield just the captured value.
+pub struct NamePattern {
+    regex: Regex,
+}
+
+impl NamePattern {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let invalid = |reason: String| MetadataError::InvalidNamePattern(pattern.into(), reason);
+        let regex = Regex::new(pattern).map_err(|e| invalid(e.to_string()))?;
+        if regex.captures_len() < 2 {
+            return Err(invalid("pattern doesn't contain any capture group".into()));
+        }
+        Ok(Self { regex })
+    }
+
+    /// Returns the tags extracted from the name of the file at `path` or an empty list if the
+    /// name doesn't match. Groups that didn't participate in the match are skipped.
+    pub fn tags(&self, path: &Path) -> Vec<String> {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy(),
+            None => return vec![],
+        };
+        let captures = match self.regex.captures(&name) {
+            Some(captures) => captures,
+            None => return vec![],
+        };
+        self.regex
+            .capture_names()
+            .enumerate()
+            .skip(1)
+            .filter_map(|(i, group)| {
+                let value = captures.get(i)?.as_str().trim();
+                if value.is_empty() {
+                    return None;
+                }
+                Some(match group {
+                    Some(group) => format!("{group}={value}"),
+                    None => value.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flac_block(kind: u8, is_last: bool, data: &[u8]) -> Vec<u8> {
+        let len = (data.len() as u32).to_be_bytes();
+        let mut block = vec![
+            kind | if is_last { 0x80 } else { 0 },
+            len[1],
+            len[2],
+            len[3],
+        ];
+        block.extend_from_slice(data);
+        block
+    }
+
+    fn vorbis_comments(comments: &[&str]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&6u32.to_le_bytes());
+        data.extend_from_slice(b"vendor");
+        data.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+        for comment in comments {
+            data.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            data.extend_from_slice(comment.as_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn reads_flac_comments() {
+        let tmp_dir = tempdir::TempDir::new("metadata-test").unwrap();
+        let path = tmp_dir.path().join("song.flac");
+        let mut data = b"fLaC".to_vec();
+        data.extend(flac_block(0, false, &[0; 34]));
+        data.extend(flac_block(
+            FLAC_VORBIS_COMMENT,
+            true,
+            &vorbis_comments(&["ARTIST=Boards of Canada", "date=1998-04-20", "GENRE=IDM"]),
+        ));
+        std::fs::write(&path, data).unwrap();
+
+        let metadata = read(&path).unwrap();
+        assert_eq!(metadata.get("audio.artist"), Some("Boards of Canada"));
+        assert_eq!(metadata.get("audio.year"), Some("1998"));
+        assert_eq!(metadata.get("audio.genre"), Some("IDM"));
+        assert_eq!(metadata.get("audio.album"), None);
+    }
+
+    #[test]
+    fn reads_id3_tags() {
+        let tmp_dir = tempdir::TempDir::new("metadata-test").unwrap();
+        let path = tmp_dir.path().join("song.mp3");
+        std::fs::write(&path, b"").unwrap();
+        let mut tag = id3::Tag::new();
+        tag.set_artist("Aphex Twin");
+        tag.set_album("Drukqs");
+        tag.set_date_recorded(id3::Timestamp {
+            year: 2001,
+            month: None,
+            day: None,
+            hour: None,
+            minute: None,
+            second: None,
+        });
+        tag.write_to_path(&path, id3::Version::Id3v24).unwrap();
+
+        let metadata = read(&path).unwrap();
+        assert_eq!(metadata.get("audio.artist"), Some("Aphex Twin"));
+        assert_eq!(metadata.get("audio.album"), Some("Drukqs"));
+        assert_eq!(metadata.get("audio.year"), Some("2001"));
+    }
+
+    /// Builds a little endian TIFF image with ASCII fields in the first IFD.
+    fn tiff(fields: &[(u16, &str)]) -> Vec<u8> {
+        let mut data = b"II\x2a\x00\x08\x00\x00\x00".to_vec();
+        data.extend_from_slice(&(fields.len() as u16).to_le_bytes());
+        let mut offset = 8 + 2 + 12 * fields.len() + 4;
+        let mut values = vec![];
+        for (tag, value) in fields {
+            let value = format!("{value}\0");
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&2u16.to_le_bytes());
+            data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += value.len();
+            values.extend_from_slice(value.as_bytes());
+        }
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend(values);
+        data
+    }
+
+    #[test]
+    fn reads_exif_fields() {
+        let tmp_dir = tempdir::TempDir::new("metadata-test").unwrap();
+        let path = tmp_dir.path().join("photo.tiff");
+        std::fs::write(
+            &path,
+            tiff(&[(0x010f, "FUJIFILM"), (0x0132, "2021:03:14 12:00:00")]),
+        )
+        .unwrap();
+
+        let metadata = read(&path).unwrap();
+        assert_eq!(metadata.get("exif.make"), Some("FUJIFILM"));
+        assert_eq!(metadata.get("exif.year"), Some("2021"));
+        assert_eq!(metadata.get("exif.month"), Some("03"));
+        assert_eq!(metadata.get("exif.date"), Some("2021-03-14"));
+        assert_eq!(metadata.get("exif.model"), None);
+    }
+
+    #[test]
+    fn files_without_metadata_are_empty() {
+        let tmp_dir = tempdir::TempDir::new("metadata-test").unwrap();
+        let image = tmp_dir.path().join("image.png");
+        std::fs::write(&image, b"\x89PNG\r\n\x1a\n\x00\x00\x00\x00").unwrap();
+        let text = tmp_dir.path().join("notes.txt");
+        std::fs::write(&text, b"hello").unwrap();
+
+        assert!(read(&text).unwrap().is_empty());
+        assert!(read(&image).map(|m| m.is_empty()).unwrap_or(true));
+    }
+
+    #[test]
+    fn extracts_tags_from_names() {
+        let pattern = NamePattern::parse(
+            r"^(?P<date>\d{4}-\d{2}-\d{2})_([a-z]+)_(?P<client>[a-z]+)(_draft)?\.",
+        )
+        .unwrap();
+        assert_eq!(
+            pattern.tags(Path::new("/docs/2025-03-14_invoice_acme.pdf")),
+            vec!["date=2025-03-14", "invoice", "client=acme"]
+        );
+        assert_eq!(
+            pattern.tags(Path::new("2025-03-14_offer_acme_draft.pdf")),
+            vec!["date=2025-03-14", "offer", "client=acme", "_draft"]
+        );
+        assert!(pattern.tags(Path::new("/2025-03-14/notes.txt")).is_empty());
+
+        assert!(NamePattern::parse("(unclosed").is_err());
+        assert!(NamePattern::parse("no groups").is_err());
+    }
+
+    #[test]
+    fn parses_and_renders_templates() {
+        let mut metadata = Metadata::default();
+        metadata.insert("exif.year", "2021");
+        metadata.insert("exif.model", " X100V \0");
+
+        let template = Template::parse("year={exif.year}").unwrap();
+        assert_eq!(template.render(&metadata).unwrap(), "year=2021");
+        let template = Template::parse("{{{exif.model}}}/{ exif.year }").unwrap();
+        assert_eq!(template.render(&metadata).unwrap(), "{X100V}/2021");
+        let template = Template::parse("{exif.month}").unwrap();
+        assert!(template.render(&metadata).is_none());
+
+        assert!(Template::parse("year={exif.year").is_err());
+        assert!(Template::parse("year={exif.unknown}").is_err());
+        assert!(Template::parse("year}").is_err());
+        assert!(Template::parse("year").is_err());
+    }
+}
@@ -0,0 +1,411 @@
+//! Detection of the content type of files from their magic bytes, falling back to the extension
+//! for text formats like source code.
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Number of bytes read from the start of a file to detect its type.
+pub const SNIFF_LEN: usize = 8192;
+
+/// Generic MIME type of binary data that wasn't recognized.
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+/// Broad category of a content type.
+pub enum Kind {
+    Image,
+    Audio,
+    Video,
+    Archive,
+    Document,
+    Pdf,
+    Source,
+    Text,
+    Font,
+    Executable,
+    Database,
+    Other,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Image => "image",
+            Kind::Audio => "audio",
+            Kind::Video => "video",
+            Kind::Archive => "archive",
+            Kind::Document => "document",
+            Kind::Pdf => "pdf",
+            Kind::Source => "source",
+            Kind::Text => "text",
+            Kind::Font => "font",
+            Kind::Executable => "executable",
+            Kind::Database => "database",
+            Kind::Other => "other",
+        }
+    }
+
+    /// Returns the name of the tag applied to files of this kind like `type/image`.
+    pub fn tag(&self) -> String {
+        format!("type/{}", self.name())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+/// Detected type of the content of a file.
+pub struct ContentType {
+    pub mime: &'static str,
+    pub kind: Kind,
+}
+
+impl ContentType {
+    const fn new(mime: &'static str, kind: Kind) -> Self {
+        Self { mime, kind }
+    }
+}
+
+/// Magic bytes expected at `offset` from the start of the data.
+struct Signature {
+    offset: usize,
+    magic: &'static [u8],
+    content: ContentType,
+}
+
+const fn sig(offset: usize, magic: &'static [u8], mime: &'static str, kind: Kind) -> Signature {
+    Signature {
+        offset,
+        magic,
+        content: ContentType::new(mime, kind),
+    }
+}
+
+#[rustfmt::skip]
+const SIGNATURES: &[Signature] = &[
+    sig(0, b"\x89PNG\r\n\x1a\n", "image/png", Kind::Image),
+    sig(0, b"\xff\xd8\xff", "image/jpeg", Kind::Image),
+    sig(0, b"GIF87a", "image/gif", Kind::Image),
+    sig(0, b"GIF89a", "image/gif", Kind::Image),
+    sig(0, b"II*\x00", "image/tiff", Kind::Image),
+    sig(0, b"MM\x00*", "image/tiff", Kind::Image),
+    sig(0, b"\x00\x00\x01\x00", "image/vnd.microsoft.icon", Kind::Image),
+    sig(4, b"ftypavif", "image/avif", Kind::Image),
+    sig(4, b"ftypheic", "image/heic", Kind::Image),
+    sig(4, b"ftypheix", "image/heic", Kind::Image),
+    sig(4, b"ftypmif1", "image/heif", Kind::Image),
+    sig(0, b"fLaC", "audio/flac", Kind::Audio),
+    sig(0, b"ID3", "audio/mpeg", Kind::Audio),
+    sig(0, b"\xff\xfb", "audio/mpeg", Kind::Audio),
+    sig(0, b"\xff\xf3", "audio/mpeg", Kind::Audio),
+    sig(0, b"\xff\xf2", "audio/mpeg", Kind::Audio),
+    sig(0, b"OggS", "audio/ogg", Kind::Audio),
+    sig(0, b"MThd", "audio/midi", Kind::Audio),
+    sig(4, b"ftypM4A", "audio/mp4", Kind::Audio),
+    sig(4, b"ftypqt", "video/quicktime", Kind::Video),
+    sig(4, b"ftyp", "video/mp4", Kind::Video),
+    sig(0, b"\x1a\x45\xdf\xa3", "video/x-matroska", Kind::Video),
+    sig(0, b"FLV\x01", "video/x-flv", Kind::Video),
+    sig(0, b"%PDF-", "application/pdf", Kind::Pdf),
+    sig(0, b"{\\rtf", "application/rtf", Kind::Document),
+    sig(0, b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1", "application/x-ole-storage", Kind::Document),
+    sig(0, b"PK\x03\x04", "application/zip", Kind::Archive),
+    sig(0, b"PK\x05\x06", "application/zip", Kind::Archive),
+    sig(0, b"\x1f\x8b", "application/gzip", Kind::Archive),
+    sig(0, b"BZh", "application/x-bzip2", Kind::Archive),
+    sig(0, b"\xfd7zXZ\x00", "application/x-xz", Kind::Archive),
+    sig(0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed", Kind::Archive),
+    sig(0, b"Rar!\x1a\x07", "application/vnd.rar", Kind::Archive),
+    sig(0, b"\x28\xb5\x2f\xfd", "application/zstd", Kind::Archive),
+    sig(257, b"ustar", "application/x-tar", Kind::Archive),
+    sig(0, b"\x7fELF", "application/x-executable", Kind::Executable),
+    sig(0, b"MZ", "application/vnd.microsoft.portable-executable", Kind::Executable),
+    sig(0, b"\xfe\xed\xfa\xce", "application/x-mach-binary", Kind::Executable),
+    sig(0, b"\xfe\xed\xfa\xcf", "application/x-mach-binary", Kind::Executable),
+    sig(0, b"\xce\xfa\xed\xfe", "application/x-mach-binary", Kind::Executable),
+    sig(0, b"\xcf\xfa\xed\xfe", "application/x-mach-binary", Kind::Executable),
+    sig(0, b"\x00asm", "application/wasm", Kind::Executable),
+    sig(0, b"wOFF", "font/woff", Kind::Font),
+    sig(0, b"wOF2", "font/woff2", Kind::Font),
+    sig(0, b"\x00\x01\x00\x00\x00", "font/ttf", Kind::Font),
+    sig(0, b"OTTO", "font/otf", Kind::Font),
+    sig(0, b"SQLite format 3\x00", "application/vnd.sqlite3", Kind::Database),
+];
+
+/// Formats inside of a RIFF container identified by the form type at offset 8.
+#[rustfmt::skip]
+const RIFF_FORMS: &[(&[u8], ContentType)] = &[
+    (b"WEBP", ContentType::new("image/webp", Kind::Image)),
+    (b"WAVE", ContentType::new("audio/wav", Kind::Audio)),
+    (b"AVI ", ContentType::new("video/x-msvideo", Kind::Video)),
+];
+
+/// Formats stored as zip archives that can only be told apart by their extension.
+#[rustfmt::skip]
+const ZIP_EXTENSIONS: &[(&str, ContentType)] = &[
+    ("docx", ContentType::new("application/vnd.openxmlformats-officedocument.wordprocessingml.document", Kind::Document)),
+    ("xlsx", ContentType::new("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", Kind::Document)),
+    ("pptx", ContentType::new("application/vnd.openxmlformats-officedocument.presentationml.presentation", Kind::Document)),
+    ("jar", ContentType::new("application/java-archive", Kind::Archive)),
+    ("apk", ContentType::new("application/vnd.android.package-archive", Kind::Archive)),
+];
+
+/// Legacy office formats stored in an OLE container.
+#[rustfmt::skip]
+const OLE_EXTENSIONS: &[(&str, ContentType)] = &[
+    ("doc", ContentType::new("application/msword", Kind::Document)),
+    ("xls", ContentType::new("application/vnd.ms-excel", Kind::Document)),
+    ("ppt", ContentType::new("application/vnd.ms-powerpoint", Kind::Document)),
+    ("msi", ContentType::new("application/x-msi", Kind::Executable)),
+];
+
+/// Text formats that don't have magic bytes and are recognized by their extension.
+#[rustfmt::skip]
+const TEXT_EXTENSIONS: &[(&str, ContentType)] = &[
+    ("rs", ContentType::new("text/x-rust", Kind::Source)),
+    ("py", ContentType::new("text/x-python", Kind::Source)),
+    ("c", ContentType::new("text/x-c", Kind::Source)),
+    ("h", ContentType::new("text/x-c", Kind::Source)),
+    ("cc", ContentType::new("text/x-c++", Kind::Source)),
+    ("cpp", ContentType::new("text/x-c++", Kind::Source)),
+    ("cxx", ContentType::new("text/x-c++", Kind::Source)),
+    ("hpp", ContentType::new("text/x-c++", Kind::Source)),
+    ("go", ContentType::new("text/x-go", Kind::Source)),
+    ("java", ContentType::new("text/x-java", Kind::Source)),
+    ("kt", ContentType::new("text/x-kotlin", Kind::Source)),
+    ("swift", ContentType::new("text/x-swift", Kind::Source)),
+    ("js", ContentType::new("text/javascript", Kind::Source)),
+    ("mjs", ContentType::new("text/javascript", Kind::Source)),
+    ("ts", ContentType::new("text/x-typescript", Kind::Source)),
+    ("rb", ContentType::new("text/x-ruby", Kind::Source)),
+    ("php", ContentType::new("text/x-php", Kind::Source)),
+    ("pl", ContentType::new("text/x-perl", Kind::Source)),
+    ("hs", ContentType::new("text/x-haskell", Kind::Source)),
+    ("lua", ContentType::new("text/x-lua", Kind::Source)),
+    ("cs", ContentType::new("text/x-csharp", Kind::Source)),
+    ("sh", ContentType::new("application/x-sh", Kind::Source)),
+    ("bash", ContentType::new("application/x-sh", Kind::Source)),
+    ("zsh", ContentType::new("application/x-sh", Kind::Source)),
+    ("sql", ContentType::new("application/sql", Kind::Source)),
+    ("html", ContentType::new("text/html", Kind::Source)),
+    ("htm", ContentType::new("text/html", Kind::Source)),
+    ("css", ContentType::new("text/css", Kind::Source)),
+    ("json", ContentType::new("application/json", Kind::Text)),
+    ("toml", ContentType::new("application/toml", Kind::Text)),
+    ("yaml", ContentType::new("application/yaml", Kind::Text)),
+    ("yml", ContentType::new("application/yaml", Kind::Text)),
+    ("xml", ContentType::new("application/xml", Kind::Text)),
+    ("svg", ContentType::new("image/svg+xml", Kind::Image)),
+    ("md", ContentType::new("text/markdown", Kind::Text)),
+    ("csv", ContentType::new("text/csv", Kind::Text)),
+    ("txt", ContentType::new("text/plain", Kind::Text)),
+];
+
+/// Interpreters recognized in the shebang line of scripts.
+#[rustfmt::skip]
+const INTERPRETERS: &[(&str, ContentType)] = &[
+    ("python", ContentType::new("text/x-python", Kind::Source)),
+    ("node", ContentType::new("text/javascript", Kind::Source)),
+    ("ruby", ContentType::new("text/x-ruby", Kind::Source)),
+    ("perl", ContentType::new("text/x-perl", Kind::Source)),
+    ("sh", ContentType::new("application/x-sh", Kind::Source)),
+];
+
+const PLAIN_TEXT: ContentType = ContentType::new("text/plain", Kind::Text);
+const UNKNOWN: ContentType = ContentType::new(OCTET_STREAM, Kind::Other);
+
+fn lookup(table: &[(&str, ContentType)], key: &str) -> Option<ContentType> {
+    table
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, content)| *content)
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+/// Detects the type of `data` from its magic bytes only. Returns `None` if no known signature
+/// matches.
+pub fn sniff(data: &[u8]) -> Option<ContentType> {
+    if data.len() >= 12 && data.starts_with(b"RIFF") {
+        return RIFF_FORMS
+            .iter()
+            .find(|(form, _)| &data[8..12] == *form)
+            .map(|(_, content)| *content);
+    }
+    if data.len() >= 12 && data.starts_with(b"FORM") && &data[8..12] == b"AIFF" {
+        return Some(ContentType::new("audio/aiff", Kind::Audio));
+    }
+    if data.starts_with(b"\x1a\x45\xdf\xa3") && contains(data, b"webm") {
+        return Some(ContentType::new("video/webm", Kind::Video));
+    }
+    if data.starts_with(b"PK\x03\x04") {
+        if let Some(content) = sniff_zip_mimetype(data) {
+            return Some(content);
+        }
+    }
+
+    SIGNATURES
+        .iter()
+        .find(|sig| {
+            data.len() >= sig.offset + sig.magic.len()
+                && &data[sig.offset..sig.offset + sig.magic.len()] == sig.magic
+        })
+        .map(|sig| sig.content)
+}
+
+fn contains(data: &[u8], needle: &[u8]) -> bool {
+    data.windows(needle.len()).any(|window| window == needle)
+}
+
+/// OpenDocument files and EPUBs store their MIME type uncompressed as the first entry named
+/// `mimetype` of the archive.
+fn sniff_zip_mimetype(data: &[u8]) -> Option<ContentType> {
+    const NAME: &[u8] = b"mimetype";
+    if data.len() < 38 || &data[30..38] != NAME {
+        return None;
+    }
+    let mime = &data[38..];
+    if mime.starts_with(b"application/epub+zip") {
+        Some(ContentType::new("application/epub+zip", Kind::Document))
+    } else if mime.starts_with(b"application/vnd.oasis.opendocument.text") {
+        Some(ContentType::new(
+            "application/vnd.oasis.opendocument.text",
+            Kind::Document,
+        ))
+    } else if mime.starts_with(b"application/vnd.oasis.opendocument.spreadsheet") {
+        Some(ContentType::new(
+            "application/vnd.oasis.opendocument.spreadsheet",
+            Kind::Document,
+        ))
+    } else if mime.starts_with(b"application/vnd.oasis.opendocument.presentation") {
+        Some(ContentType::new(
+            "application/vnd.oasis.opendocument.presentation",
+            Kind::Document,
+        ))
+    } else {
+        None
+    }
+}
+
+/// Checks whether `data` looks like text. The data may end in the middle of a character.
+fn is_text(data: &[u8]) -> bool {
+    if data.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(data) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+fn sniff_shebang(data: &[u8]) -> Option<ContentType> {
+    let line = data.strip_prefix(b"#!")?.split(|b| *b == b'\n').next()?;
+    let line = String::from_utf8_lossy(line);
+    let mut words = line.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-'))?;
+    }
+    INTERPRETERS
+        .iter()
+        .find(|(name, _)| {
+            interpreter.starts_with(name) || (*name == "sh" && interpreter.ends_with("sh"))
+        })
+        .map(|(_, content)| *content)
+}
+
+/// Detects the type of the `data` read from the start of a file at `path`. The path is only used
+/// to tell apart formats that share magic bytes and to recognize text formats.
+pub fn detect_data(path: &Path, data: &[u8]) -> ContentType {
+    let ext = extension(path);
+    match sniff(data) {
+        Some(content) if content.mime == "application/zip" => ext
+            .and_then(|ext| lookup(ZIP_EXTENSIONS, &ext))
+            .unwrap_or(content),
+        Some(content) if content.mime == "application/x-ole-storage" => ext
+            .and_then(|ext| lookup(OLE_EXTENSIONS, &ext))
+            .unwrap_or(content),
+        Some(content) => content,
+        None if is_text(data) => sniff_shebang(data)
+            .or_else(|| ext.and_then(|ext| lookup(TEXT_EXTENSIONS, &ext)))
+            .unwrap_or(PLAIN_TEXT),
+        None => UNKNOWN,
+    }
+}
+
+/// Detects the type of the file at `path` by reading its first [SNIFF_LEN](SNIFF_LEN) bytes.
+pub fn detect<P: AsRef<Path>>(path: P) -> io::Result<ContentType> {
+    let path = path.as_ref();
+    let mut data = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut data)?;
+    Ok(detect_data(path, &data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_magic_bytes() {
+        let mime = |data: &[u8]| sniff(data).map(|content| content.mime);
+        assert_eq!(mime(b"\x89PNG\r\n\x1a\n\x00\x00"), Some("image/png"));
+        assert_eq!(mime(b"RIFF\x00\x00\x00\x00WEBPVP8 "), Some("image/webp"));
+        assert_eq!(mime(b"RIFF\x00\x00\x00\x00WAVEfmt "), Some("audio/wav"));
+        assert_eq!(mime(b"ID3\x03\x00"), Some("audio/mpeg"));
+        assert_eq!(mime(b"\x00\x00\x00\x18ftypmp42"), Some("video/mp4"));
+        assert_eq!(mime(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(mime(b"\x7fELF\x02\x01"), Some("application/x-executable"));
+        assert_eq!(mime(b"\x1f\x8b\x08"), Some("application/gzip"));
+
+        let mut tar = vec![0; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(mime(&tar), Some("application/x-tar"));
+
+        let mut epub = b"PK\x03\x04".to_vec();
+        epub.resize(30, 0);
+        epub.extend_from_slice(b"mimetypeapplication/epub+zip");
+        assert_eq!(mime(&epub), Some("application/epub+zip"));
+
+        assert_eq!(mime(b"fn main() {}"), None);
+    }
+
+    #[test]
+    fn detects_formats_by_extension_and_content() {
+        let detect = |path: &str, data: &[u8]| detect_data(Path::new(path), data);
+
+        assert_eq!(detect("lib.rs", b"fn main() {}").kind, Kind::Source);
+        assert_eq!(detect("lib.rs", b"fn main() {}").mime, "text/x-rust");
+        assert_eq!(detect("notes", b"hello").mime, "text/plain");
+        assert_eq!(
+            detect("run", b"#!/usr/bin/env python3\n").mime,
+            "text/x-python"
+        );
+        assert_eq!(detect("run", b"#!/bin/bash\necho").mime, "application/x-sh");
+        assert_eq!(detect("report.docx", b"PK\x03\x04").kind, Kind::Document);
+        assert_eq!(detect("files.zip", b"PK\x03\x04").kind, Kind::Archive);
+        assert_eq!(detect("fake.png", b"not really").kind, Kind::Text);
+        assert_eq!(detect("blob", b"\x00\x01\x02\x03").mime, OCTET_STREAM);
+        // multibyte character cut at the end of the sniffed data
+        assert_eq!(
+            detect("utf8.txt", "zażółć".as_bytes()[..4].as_ref()).kind,
+            Kind::Text
+        );
+    }
+
+    #[test]
+    fn detects_files() {
+        let tmp_dir = tempdir::TempDir::new("mime-test").unwrap();
+        let path = tmp_dir.path().join("image");
+        std::fs::write(&path, b"GIF89a\x01\x00\x01\x00").unwrap();
+
+        let content = detect(&path).unwrap();
+        assert_eq!(content.mime, "image/gif");
+        assert_eq!(content.kind.tag(), "type/image");
+        assert!(detect(tmp_dir.path().join("missing")).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+//! Queries of saved searches written with the same arguments as the `search` subcommand like
+//! `invoice --any --tagged-since 30d --type f`.
+use crate::filter::{FileFilter, FilterError};
+use crate::time::parse_time;
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum QueryError {
+    #[error("unterminated quote")]
+    UnterminatedQuote,
+    #[error("unknown option `{0}`")]
+    UnknownOption(String),
+    #[error("option `{0}` requires a value")]
+    MissingValue(String),
+    #[error("option `{0}` doesn't take a value")]
+    UnexpectedValue(String),
+    #[error("invalid value of `{0}` - {1}")]
+    InvalidValue(String, String),
+    #[error("`--under` must be an absolute path, got `{0}`")]
+    RelativePath(PathBuf),
+    #[error("query has no tags to search for")]
+    NoTags,
+    #[error(transparent)]
+    Filter(#[from] FilterError),
+}
+
+type Result<T> = std::result::Result<T, QueryError>;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// A parsed query. Relative times like `7d` are resolved when the query is parsed so a saved
+/// query should be parsed every time it is run.
+pub struct Query {
+    /// Names of tags or other saved searches to search for.
+    pub tags: Vec<String>,
+    pub any: bool,
+    pub inherit: bool,
+    pub tagged_since: Option<DateTime<Utc>>,
+    pub tagged_before: Option<DateTime<Utc>>,
+    pub filter: FileFilter,
+}
+
+impl Query {
+    /// Parses a query. Values can be quoted with `'` or `"` and given either as the next argument
+    /// or after `=` like `--size=+10M`.
+    pub fn parse(query: &str) -> Result<Self> {
+        let mut parsed = Query::default();
+        let mut args = split(query)?.into_iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') || arg == "-" {
+                parsed.tags.push(arg);
+                continue;
+            }
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let name = match name.as_str() {
+                "-a" => "--any",
+                "-i" => "--inherit",
+                name => name,
+            }
+            .to_string();
+
+            if name == "--any" || name == "--inherit" {
+                if inline.is_some() {
+                    return Err(QueryError::UnexpectedValue(name));
+                }
+                if name == "--any" {
+                    parsed.any = true;
+                } else {
+                    parsed.inherit = true;
+                }
+                continue;
+            }
+
+            let value = match inline.or_else(|| args.next()) {
+                Some(value) => value,
+                None if is_option(&name) => return Err(QueryError::MissingValue(name)),
+                None => return Err(QueryError::UnknownOption(name)),
+            };
+            let time = |value: &str| {
+                parse_time(value).map_err(|e| QueryError::InvalidValue(name.clone(), e.to_string()))
+            };
+            let filter = &mut parsed.filter;
+            match name.as_str() {
+                "--tagged-since" => parsed.tagged_since = Some(time(&value)?),
+                "--tagged-before" => parsed.tagged_before = Some(time(&value)?),
+                "--under" => {
+                    let under = PathBuf::from(value);
+                    if !under.is_absolute() {
+                        return Err(QueryError::RelativePath(under));
+                    }
+                    filter.under = Some(under);
+                }
+                "--type" => filter.file_type = Some(value.parse()?),
+                "--size" => filter.size.push(value.parse()?),
+                "--modified" => filter.modified.push(value.parse()?),
+                "--path-regex" => {
+                    filter.path_regex = Some(value);
+                    filter.matcher()?;
+                }
+                _ => return Err(QueryError::UnknownOption(name)),
+            }
+        }
+
+        if parsed.tags.is_empty() {
+            return Err(QueryError::NoTags);
+        }
+        Ok(parsed)
+    }
+}
+
+fn is_option(name: &str) -> bool {
+    matches!(
+        name,
+        "--tagged-since"
+            | "--tagged-before"
+            | "--under"
+            | "--type"
+            | "--size"
+            | "--modified"
+            | "--path-regex"
+    )
+}
+
+/// Splits the query into arguments on whitespace outside of quotes. Outside of single quotes a
+/// backslash escapes the next character.
+fn split(query: &str) -> Result<Vec<String>> {
+    let mut args = vec![];
+    let mut current: Option<String> = None;
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(QueryError::UnterminatedQuote),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => arg.push(c),
+                            None => return Err(QueryError::UnterminatedQuote),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(QueryError::UnterminatedQuote),
+                    }
+                }
+            }
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    current.get_or_insert_with(String::new).push(c);
+                }
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::{FileType, ModifiedFilter};
+
+    #[test]
+    fn splits_arguments() {
+        assert_eq!(
+            split(r#"invoice  'two words' "a \"b\"" c\ d e''"#).unwrap(),
+            vec!["invoice", "two words", r#"a "b""#, "c d", "e"]
+        );
+        assert_eq!(split("'' x").unwrap(), vec!["", "x"]);
+        assert!(split("'open").is_err());
+        assert!(split("\"open").is_err());
+    }
+
+    #[test]
+    fn parses_queries() {
+        let query = Query::parse(
+            "invoice paid -a --tagged-since 30d --type=f --size +10k --size -1M \
+             --modified -7d --under '/home/user/my docs' --path-regex '\\.pdf$'",
+        )
+        .unwrap();
+        assert_eq!(query.tags, vec!["invoice", "paid"]);
+        assert!(query.any);
+        assert!(!query.inherit);
+        assert!(query.tagged_since.is_some());
+        assert_eq!(query.tagged_before, None);
+        assert_eq!(query.filter.file_type, Some(FileType::File));
+        assert_eq!(query.filter.size.len(), 2);
+        assert!(matches!(
+            query.filter.modified[..],
+            [ModifiedFilter::Since(_)]
+        ));
+        assert_eq!(query.filter.under, Some("/home/user/my docs".into()));
+        assert_eq!(query.filter.path_regex.as_deref(), Some("\\.pdf$"));
+
+        assert!(matches!(Query::parse("-i"), Err(QueryError::NoTags)));
+        assert!(matches!(
+            Query::parse("x --sort size"),
+            Err(QueryError::UnknownOption(_))
+        ));
+        assert!(matches!(
+            Query::parse("x --size"),
+            Err(QueryError::MissingValue(_))
+        ));
+        assert!(matches!(
+            Query::parse("x --any=true"),
+            Err(QueryError::UnexpectedValue(_))
+        ));
+        assert!(matches!(
+            Query::parse("x --under docs"),
+            Err(QueryError::RelativePath(_))
+        ));
+        assert!(matches!(
+            Query::parse("x --tagged-since yesterday"),
+            Err(QueryError::InvalidValue(_, _))
+        ));
+        assert!(Query::parse("x --path-regex (").is_err());
+        assert!(Query::parse("x --type q").is_err());
+    }
+}
@@ -1,13 +1,16 @@
 #![allow(dead_code)]
 
+use crate::query::{Query, QueryError};
 use crate::tag::Tag;
 
+use chrono::{DateTime, Utc};
 use colored::Color;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::io;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -20,6 +23,20 @@ pub enum RegistryError {
     SaveRegistry(io::Error),
     #[error("Failed to serialize registry - {0}")]
     SerializeRegistry(serde_cbor::Error),
+    #[error("tag `{0}` can't imply itself")]
+    SelfImplication(String),
+    #[error("`{0}` implying `{1}` would create a cycle because `{1}` already implies `{0}`")]
+    ImplicationCycle(String, String),
+    #[error("tag `{0}` can't be an alias of itself")]
+    SelfAlias(String),
+    #[error("`{0}` is used in implication rules, remove the rules before making it an alias")]
+    AliasInImplication(String),
+    #[error("`{0}` is already a tag or an alias")]
+    SavedSearchIsTag(String),
+    #[error("invalid query of saved search `{0}` - {1}")]
+    InvalidQuery(String, QueryError),
+    #[error("saved search `{0}` would refer to itself through `{1}`")]
+    SavedSearchCycle(String, String),
 }
 
 type Result<T> = std::result::Result<T, RegistryError>;
@@ -27,12 +44,26 @@ type Result<T> = std::result::Result<T, RegistryError>;
 #[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
 pub struct EntryData {
     path: PathBuf,
+    #[serde(default)]
+    /// When the entry was first added to the registry. Not known for entries added by older
+    /// versions.
+    added: Option<DateTime<Utc>>,
+    #[serde(default)]
+    /// When each tag of the entry was applied keyed by the name of the tag.
+    tagged: BTreeMap<String, DateTime<Utc>>,
+    #[serde(default)]
+    /// When tags of the entry that were applied with an expiry expire keyed by the name of the
+    /// tag.
+    expires: BTreeMap<String, DateTime<Utc>>,
 }
 
 impl EntryData {
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
         Self {
             path: path.as_ref().to_path_buf(),
+            added: Some(Utc::now()),
+            tagged: BTreeMap::new(),
+            expires: BTreeMap::new(),
         }
     }
 
@@ -40,6 +71,51 @@ impl EntryData {
         &self.path
     }
 
+    /// Returns the time at which this entry was first added to the registry if known.
+    pub fn added(&self) -> Option<&DateTime<Utc>> {
+        self.added.as_ref()
+    }
+
+    /// Returns the time at which the tag with `tag_name` was applied to this entry if known.
+    pub fn tagged_at(&self, tag_name: &str) -> Option<&DateTime<Utc>> {
+        self.tagged.get(tag_name)
+    }
+
+    /// Lists the names of tags of this entry with the times at which they were applied.
+    pub fn tagged(&self) -> &BTreeMap<String, DateTime<Utc>> {
+        &self.tagged
+    }
+
+    /// Returns the time at which the tag with `tag_name` expires if it was applied with an expiry.
+    pub fn expires_at(&self, tag_name: &str) -> Option<&DateTime<Utc>> {
+        self.expires.get(tag_name)
+    }
+
+    /// Lists the names of expiring tags of this entry with the times at which they expire.
+    pub fn expires(&self) -> &BTreeMap<String, DateTime<Utc>> {
+        &self.expires
+    }
+
+    /// Checks whether the tag with `tag_name` was applied to this entry at or after `since` and
+    /// before `before`. Returns `false` if the time is not known and any bound is set.
+    pub fn tagged_between(
+        &self,
+        tag_name: &str,
+        since: Option<&DateTime<Utc>>,
+        before: Option<&DateTime<Utc>>,
+    ) -> bool {
+        if since.is_none() && before.is_none() {
+            return true;
+        }
+        match self.tagged_at(tag_name) {
+            Some(time) => {
+                since.map(|since| time >= since).unwrap_or(true)
+                    && before.map(|before| time < before).unwrap_or(true)
+            }
+            None => false,
+        }
+    }
+
     pub fn into_path_buf(self) -> PathBuf {
         self.path
     }
@@ -47,11 +123,204 @@ impl EntryData {
 
 pub type EntryId = usize;
 
+/// Ids of entries by their paths, see [entry_index](TagRegistry::entry_index).
+pub type EntryIndex = HashMap<PathBuf, EntryId>;
+
+/// Normalizes the `path` without accessing the filesystem by removing `.` components and trailing
+/// slashes and resolving `..` components against preceding components.
+pub fn normalize_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.as_ref().components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Returns the path under which the entry at `path` is stored in the registry. Relative paths are
+/// resolved against the current working directory and all symbolic links are resolved, tags set
+/// through a symbolic link are stored in the attributes of its target so the target is the entry.
+/// If the path doesn't exist only its parent is resolved and if that doesn't exist either the
+/// path is only normalized with [normalize_path](normalize_path).
+pub fn canonical_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    fs::canonicalize(&path)
+        .or_else(|e| match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => fs::canonicalize(parent).map(|parent| parent.join(name)),
+            _ => Err(e),
+        })
+        .unwrap_or_else(|_| normalize_path(&path))
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+/// Describes whether a tag is added to or removed from an entry.
+pub enum ChangeKind {
+    Add,
+    Remove,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+/// A single modification of the tags of an entry.
+pub struct TagChange {
+    pub path: PathBuf,
+    pub tag: Tag,
+    pub kind: ChangeKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// When the added tag expires. For removed tags this is the expiry the tag had so that the
+    /// removal can be reverted.
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl TagChange {
+    pub fn add<P: AsRef<Path>>(path: P, tag: Tag) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            tag,
+            kind: ChangeKind::Add,
+            expires: None,
+        }
+    }
+
+    pub fn remove<P: AsRef<Path>>(path: P, tag: Tag) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            tag,
+            kind: ChangeKind::Remove,
+            expires: None,
+        }
+    }
+
+    pub fn with_expiry(mut self, expires: Option<DateTime<Utc>>) -> Self {
+        self.expires = expires;
+        self
+    }
+
+    /// Returns a change that reverts this change.
+    pub fn inverse(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            tag: self.tag.clone(),
+            kind: match self.kind {
+                ChangeKind::Add => ChangeKind::Remove,
+                ChangeKind::Remove => ChangeKind::Add,
+            },
+            expires: self.expires,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+/// A tag that applies to a path either directly or through one of its parent directories.
+pub struct EffectiveTag {
+    pub tag: Tag,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// The directory from which the tag is inherited or `None` if the path itself is tagged.
+    pub inherited_from: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+/// An entry found by a search together with the searched tags that it inherited from parent
+/// directories.
+pub struct SearchMatch {
+    pub entry: EntryData,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    /// Directories from which the tags were inherited keyed by the name of the tag.
+    pub inherited: BTreeMap<String, PathBuf>,
+}
+
+impl From<EntryData> for SearchMatch {
+    fn from(entry: EntryData) -> Self {
+        Self {
+            entry,
+            inherited: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+/// A tag proposed for an entry. Each partial score is between 0 and 1.
+pub struct Suggestion {
+    pub tag: Tag,
+    /// Weighted sum of the partial scores, the higher the better.
+    pub score: f64,
+    /// Average share of entries with the tags of the entry that also have the suggested tag.
+    pub co_occurrence: f64,
+    /// Share of other entries in the same directory that have the suggested tag.
+    pub siblings: f64,
+    /// Share of other entries with the same extension that have the suggested tag.
+    pub extension: f64,
+}
+
+/// Weights of the partial scores of a [Suggestion](Suggestion) in the order co-occurrence,
+/// siblings, extension.
+const SUGGESTION_WEIGHTS: (f64, f64, f64) = (0.5, 0.3, 0.2);
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+/// Details of a tag together with statistics of its usage.
+pub struct TagInfo {
+    pub tag: Tag,
+    /// Aliases resolving to the tag.
+    pub aliases: Vec<String>,
+    /// Names of tags implied by the tag directly or through other tags.
+    pub implies: Vec<String>,
+    /// Number of entries tagged with the tag.
+    pub usage: usize,
+    /// Names of tags applied to the same entries together with the number of such entries, the
+    /// most frequent first.
+    pub co_occurring: Vec<(String, usize)>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+/// An entry of an exported registry with names of its tags.
+pub struct ExportedEntry {
+    #[serde(flatten)]
+    pub entry: EntryData,
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// Portable form of the tag database used to export and import it.
+pub struct RegistryExport {
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub entries: Vec<ExportedEntry>,
+    #[serde(default)]
+    pub implications: BTreeMap<String, BTreeSet<String>>,
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    #[serde(default)]
+    pub saved_searches: BTreeMap<String, String>,
+}
+
 #[derive(Default, Deserialize, Serialize)]
 pub struct TagRegistry {
     tags: HashMap<Tag, BTreeSet<EntryId>>,
+    #[serde(default)]
+    /// Names of tags implied by each tag keyed by the name of the implying tag.
+    implications: BTreeMap<String, BTreeSet<String>>,
+    #[serde(default)]
+    /// Names of canonical tags keyed by their aliases.
+    aliases: BTreeMap<String, String>,
+    #[serde(default)]
+    /// Queries of saved searches keyed by their names.
+    saved_searches: BTreeMap<String, String>,
     entries: HashMap<EntryId, EntryData>,
     path: PathBuf,
+    #[serde(skip)]
+    /// Number of times the registry was saved since it was loaded.
+    revision: AtomicU64,
 }
 
 impl TagRegistry {
@@ -68,13 +337,75 @@ impl TagRegistry {
         let path = path.as_ref();
         let data = fs::read(path).map_err(RegistryError::LoadRegistry)?;
 
-        serde_cbor::from_slice(&data).map_err(RegistryError::DeserializeRegistry)
+        let mut registry: Self =
+            serde_cbor::from_slice(&data).map_err(RegistryError::DeserializeRegistry)?;
+        registry.canonicalize_entries();
+        Ok(registry)
+    }
+
+    /// Replaces paths of entries with their canonical form, see [canonical_path](canonical_path).
+    /// Entries stored by older versions under different forms of the same path are merged into
+    /// the oldest one. Returns the number of entries that were changed or merged.
+    pub fn canonicalize_entries(&mut self) -> usize {
+        let mut ids: Vec<_> = self.entries.keys().copied().collect();
+        ids.sort_unstable();
+        let mut by_path = HashMap::new();
+        let mut changed = 0;
+        for id in ids {
+            let path = canonical_path(&self.entries[&id].path);
+            if let Some(&kept) = by_path.get(&path) {
+                self.merge_entry(id, kept);
+                changed += 1;
+                continue;
+            }
+            let entry = self.entries.get_mut(&id).expect("entry");
+            // paths compare equal regardless of `.` components so the raw form is compared
+            if entry.path.as_os_str() != path.as_os_str() {
+                entry.path = path.clone();
+                changed += 1;
+            }
+            by_path.insert(path, id);
+        }
+        changed
+    }
+
+    /// Moves tags of the entry with id `from` to the entry with id `into` and removes it. Earliest
+    /// times at which the entries were added and tagged are kept.
+    fn merge_entry(&mut self, from: EntryId, into: EntryId) {
+        let removed = match self.entries.remove(&from) {
+            Some(removed) => removed,
+            None => return,
+        };
+        for entries in self.tags.values_mut() {
+            if entries.remove(&from) {
+                entries.insert(into);
+            }
+        }
+        let entry = self.entries.get_mut(&into).expect("entry");
+        entry.added = match (entry.added, removed.added) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        for (tag, tagged) in removed.tagged {
+            let time = entry.tagged.entry(tag).or_insert(tagged);
+            *time = (*time).min(tagged);
+        }
+        for (tag, expires) in removed.expires {
+            entry.expires.entry(tag).or_insert(expires);
+        }
     }
 
     /// Saves the registry serialized to the path from which it was loaded.
     pub fn save(&self) -> Result<()> {
         let serialized = serde_cbor::to_vec(&self).map_err(RegistryError::SerializeRegistry)?;
-        fs::write(&self.path, &serialized).map_err(RegistryError::SaveRegistry)
+        fs::write(&self.path, &serialized).map_err(RegistryError::SaveRegistry)?;
+        self.revision.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns a number that changes every time the registry is saved.
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::Relaxed)
     }
 
     /// Clears this tag registry by removing all entries and tags.
@@ -86,6 +417,7 @@ impl TagRegistry {
     /// Removes the tag from this registry returing a set of entries that were
     /// completely removed.
     pub fn clear_tag(&mut self, tag: &Tag) -> Option<Vec<EntryData>> {
+        let tag = &self.resolve_tag(tag);
         let removed = self.tags.remove(tag);
         let mut final_removed = None;
         if let Some(removed) = removed {
@@ -93,6 +425,10 @@ impl TagRegistry {
                 removed
                     .into_iter()
                     .filter_map(|entry| {
+                        if let Some(data) = self.entries.get_mut(&entry) {
+                            data.tagged.remove(tag.name());
+                            data.expires.remove(tag.name());
+                        }
                         if self.list_entry_tags(entry).is_none() {
                             self.remove_entry(entry)
                         } else {
@@ -105,16 +441,20 @@ impl TagRegistry {
         final_removed
     }
 
-    /// Updates the entry or adds it if it is not present.
-    pub fn add_or_update_entry(&mut self, entry: EntryData) -> (EntryId, bool) {
-        let pos = self
-            .list_entries_and_ids()
-            .find(|(_, e)| **e == entry)
-            .map(|(idx, _)| *idx);
+    /// Updates the entry or adds it if it is not present. Times at which an existing entry was
+    /// added and tagged are preserved.
+    pub fn add_or_update_entry(&mut self, mut entry: EntryData) -> (EntryId, bool) {
+        entry.path = normalize_path(&entry.path);
+        let pos = self.find_entry(&entry.path);
 
         let res = if let Some(pos) = pos {
             let e = self.entries.get_mut(&pos).expect("entry");
-            *e = entry;
+            *e = EntryData {
+                added: e.added.or(entry.added),
+                tagged: std::mem::take(&mut e.tagged),
+                expires: std::mem::take(&mut e.expires),
+                ..entry
+            };
             (pos, false)
         } else {
             let timestamp = chrono::Utc::now().timestamp_nanos();
@@ -134,7 +474,11 @@ impl TagRegistry {
         let exists = self.tags.iter().any(|(t, _)| t == tag);
 
         if !exists {
-            self.tags.insert(tag.clone(), BTreeSet::new());
+            let mut tag = tag.clone();
+            if tag.created().is_none() {
+                tag.set_created(Utc::now());
+            }
+            self.tags.insert(tag, BTreeSet::new());
         }
 
         self.tags.get_mut(tag).unwrap()
@@ -143,14 +487,48 @@ impl TagRegistry {
     /// Adds the `tag` to an entry with `entry` id. Returns the id if the entry was already tagged
     /// or `None` if the tag was added.
     pub fn tag_entry(&mut self, tag: &Tag, entry: EntryId) -> Option<EntryId> {
+        let tag = &self.resolve_tag(tag);
         let entries = self.mut_tag_entries(tag);
         if !entries.insert(entry) {
             Some(entry)
         } else {
+            if let Some(data) = self.entries.get_mut(&entry) {
+                data.tagged.insert(tag.name().to_string(), Utc::now());
+            }
             None
         }
     }
 
+    /// Sets or clears the time at which the tag with `tag_name` of the `entry` expires.
+    pub fn set_tag_expiry(
+        &mut self,
+        tag_name: &str,
+        entry: EntryId,
+        expires: Option<DateTime<Utc>>,
+    ) {
+        if let Some(data) = self.entries.get_mut(&entry) {
+            match expires {
+                Some(expires) => data.expires.insert(tag_name.to_string(), expires),
+                None => data.expires.remove(tag_name),
+            };
+        }
+    }
+
+    /// Returns changes that remove all tags that expired at or before `now`.
+    pub fn list_expired(&self, now: &DateTime<Utc>) -> Vec<TagChange> {
+        let mut changes = vec![];
+        for entry in self.entries.values() {
+            for (name, expires) in entry.expires.iter().filter(|(_, e)| *e <= now) {
+                if let Some(tag) = self.get_tag(name) {
+                    changes.push(
+                        TagChange::remove(&entry.path, tag.clone()).with_expiry(Some(*expires)),
+                    );
+                }
+            }
+        }
+        changes
+    }
+
     fn clean_tag_if_no_entries(&mut self, tag: &Tag) {
         let remove = if let Some(entries) = self.tags.get(tag) {
             entries.is_empty()
@@ -166,10 +544,15 @@ impl TagRegistry {
     /// Removes the `tag` from an entry with `entry` id. Returns the entry data if it has no tags
     /// left or `None` otherwise.
     pub fn untag_entry(&mut self, tag: &Tag, entry: EntryId) -> Option<EntryData> {
+        let tag = &self.resolve_tag(tag);
         let entries = self.mut_tag_entries(tag);
 
         let _ = entries.remove(&entry);
         self.clean_tag_if_no_entries(tag);
+        if let Some(data) = self.entries.get_mut(&entry) {
+            data.tagged.remove(tag.name());
+            data.expires.remove(tag.name());
+        }
         if self.list_entry_tags(entry).is_none() {
             return self.entries.remove(&entry);
         }
@@ -205,11 +588,13 @@ impl TagRegistry {
         self.remove_entry(entry);
     }
 
-    /// Finds the entry by a `path`. Returns the id of the entry if found.
+    /// Finds the entry by a `path`. The path is normalized with [normalize_path](normalize_path)
+    /// so `.`, `..` and trailing slashes don't matter. Returns the id of the entry if found.
     pub fn find_entry<P: AsRef<Path>>(&self, path: P) -> Option<EntryId> {
+        let path = normalize_path(path);
         self.entries
             .iter()
-            .find(|(_, entry)| entry.path == path.as_ref())
+            .find(|(_, entry)| entry.path == path)
             .map(|(idx, _)| *idx)
     }
 
@@ -261,7 +646,7 @@ impl TagRegistry {
             if let Some(entries) = self
                 .tags
                 .iter()
-                .find(|(t, _)| t.name() == tag.as_ref())
+                .find(|(t, _)| t.name() == self.resolve_alias(tag.as_ref()))
                 .map(|(_, e)| e)
             {
                 acc.extend(entries);
@@ -282,7 +667,7 @@ impl TagRegistry {
             if let Some(entries) = self
                 .tags
                 .iter()
-                .find(|(t, _)| t.name() == tag.as_ref())
+                .find(|(t, _)| t.name() == self.resolve_alias(tag.as_ref()))
                 .map(|(_, e)| e.iter().collect::<BTreeSet<_>>())
             {
                 if acc.is_empty() {
@@ -297,6 +682,181 @@ impl TagRegistry {
         entries.into_iter().copied().collect()
     }
 
+    /// Indexes the ids of all entries by their paths so that the entries of many paths can be
+    /// found without scanning all entries for each of them.
+    pub fn entry_index(&self) -> EntryIndex {
+        self.entries
+            .iter()
+            .map(|(id, entry)| (entry.path.clone(), *id))
+            .collect()
+    }
+
+    /// Lists tags that apply to the `path` including tags inherited from entries of its parent
+    /// directories. Tags of the path itself come first followed by tags of the closest parents.
+    /// Entries are looked up in the `index` built by [entry_index](TagRegistry::entry_index).
+    pub fn effective_tags<P: AsRef<Path>>(&self, path: P, index: &EntryIndex) -> Vec<EffectiveTag> {
+        let path = normalize_path(path);
+        let path = path.as_path();
+        let mut effective: Vec<EffectiveTag> = vec![];
+        for ancestor in path.ancestors() {
+            let tags = index
+                .get(ancestor)
+                .and_then(|id| self.list_entry_tags(*id))
+                .unwrap_or_default();
+            for tag in tags {
+                if !effective.iter().any(|t| &t.tag == tag) {
+                    effective.push(EffectiveTag {
+                        tag: tag.clone(),
+                        inherited_from: (ancestor != path).then(|| ancestor.to_path_buf()),
+                    });
+                }
+            }
+        }
+        effective
+    }
+
+    /// Checks whether the `path` has any or all of the `tags` including tags inherited from its
+    /// parent directories and tags implying the searched tags. Every matching tag has to be
+    /// applied to the entry it comes from between `since` and `before`. Entries are looked up in
+    /// the `index` built by [entry_index](TagRegistry::entry_index).
+    pub fn match_effective<P: AsRef<Path>>(
+        &self,
+        path: P,
+        index: &EntryIndex,
+        tags: &[String],
+        any: bool,
+        since: Option<&DateTime<Utc>>,
+        before: Option<&DateTime<Utc>>,
+    ) -> Option<SearchMatch> {
+        let path = normalize_path(path);
+        let path = path.as_path();
+        let effective = self.effective_tags(path, index);
+        let mut inherited = BTreeMap::new();
+        let mut has_tag = |name: &String| {
+            let names = self.implying_tags(name);
+            let tag = effective.iter().find(|t| {
+                names.contains(t.tag.name()) && {
+                    let origin = t.inherited_from.as_deref().unwrap_or(path);
+                    index
+                        .get(origin)
+                        .and_then(|id| self.get_entry(*id))
+                        .map(|entry| entry.tagged_between(t.tag.name(), since, before))
+                        .unwrap_or(false)
+                }
+            });
+            match tag {
+                Some(EffectiveTag {
+                    inherited_from: Some(from),
+                    ..
+                }) => {
+                    inherited.insert(name.clone(), from.clone());
+                    true
+                }
+                Some(_) => true,
+                None => false,
+            }
+        };
+        let matches = if any {
+            // every tag is checked so that all inherited tags are recorded
+            tags.iter().filter(|tag| has_tag(tag)).count() > 0
+        } else {
+            tags.iter().all(has_tag)
+        };
+        if !matches {
+            return None;
+        }
+
+        let entry = index
+            .get(path)
+            .and_then(|id| self.get_entry(*id))
+            .cloned()
+            .unwrap_or_else(|| EntryData {
+                path: path.to_path_buf(),
+                ..Default::default()
+            });
+        Some(SearchMatch { entry, inherited })
+    }
+
+    /// Adds a rule that anything tagged with `tag` is also tagged with `implied`. Returns `false`
+    /// if the rule already exists. Fails if the rule would create a cycle.
+    pub fn add_implication<S: Into<String>>(&mut self, tag: S, implied: S) -> Result<bool> {
+        let (tag, implied) = (tag.into(), implied.into());
+        let tag = self.resolve_alias(&tag).to_string();
+        let implied = self.resolve_alias(&implied).to_string();
+        self.check_implication(&tag, &implied)?;
+        Ok(self.implications.entry(tag).or_default().insert(implied))
+    }
+
+    /// Checks that adding a rule that `tag` implies `implied` wouldn't create a cycle.
+    pub fn check_implication(&self, tag: &str, implied: &str) -> Result<()> {
+        let (tag, implied) = (self.resolve_alias(tag), self.resolve_alias(implied));
+        if tag == implied {
+            return Err(RegistryError::SelfImplication(tag.into()));
+        }
+        if self.implied_tags(implied).contains(tag) {
+            return Err(RegistryError::ImplicationCycle(tag.into(), implied.into()));
+        }
+        Ok(())
+    }
+
+    /// Removes the rule that `tag` implies `implied`. Returns `false` if there was no such rule.
+    pub fn remove_implication(&mut self, tag: &str, implied: &str) -> bool {
+        let tag = self.resolve_alias(tag).to_string();
+        let implied = self.resolve_alias(implied).to_string();
+        let (tag, implied) = (tag.as_str(), implied.as_str());
+        let removed = match self.implications.get_mut(tag) {
+            Some(implications) => implications.remove(implied),
+            None => return false,
+        };
+        if self.implications.get(tag).map(BTreeSet::is_empty) == Some(true) {
+            self.implications.remove(tag);
+        }
+        removed
+    }
+
+    /// Lists all implication rules as pairs of the implying and implied tag names.
+    pub fn list_implications(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.implications.iter().flat_map(|(tag, implied)| {
+            implied
+                .iter()
+                .map(move |implied| (tag.as_str(), implied.as_str()))
+        })
+    }
+
+    /// Returns names of all tags implied by `tag` directly or through other implied tags.
+    pub fn implied_tags(&self, tag: &str) -> BTreeSet<String> {
+        let mut implied = BTreeSet::new();
+        let mut queue = vec![self.resolve_alias(tag)];
+        while let Some(tag) = queue.pop() {
+            for next in self.implications.get(tag).into_iter().flatten() {
+                if implied.insert(next.clone()) {
+                    queue.push(next);
+                }
+            }
+        }
+        implied
+    }
+
+    /// Returns the name of `tag` together with names of all tags that imply it directly or
+    /// through other tags. An entry with any of these tags is considered tagged with `tag`.
+    pub fn implying_tags(&self, tag: &str) -> BTreeSet<String> {
+        let mut implying = BTreeSet::from([self.resolve_alias(tag).to_string()]);
+        loop {
+            let found: Vec<_> = self
+                .implications
+                .iter()
+                .filter(|(name, implied)| {
+                    !implying.contains(*name) && implied.iter().any(|i| implying.contains(i))
+                })
+                .map(|(name, _)| name.clone())
+                .collect();
+            if found.is_empty() {
+                return implying;
+            }
+            implying.extend(found);
+        }
+    }
+
     /// Lists ids of all entries present in the registry.
     pub fn list_entries_ids(&self) -> impl Iterator<Item = &EntryId> {
         self.entries.keys()
@@ -352,23 +912,287 @@ impl TagRegistry {
         self.entries.get(&id)
     }
 
-    /// Returns the tag with the name `tag` if it exists.
+    /// Returns the tag with the name `tag` if it exists. Aliases resolve to their canonical tag.
     pub fn get_tag<T: AsRef<str>>(&self, tag: T) -> Option<&Tag> {
-        self.tags.keys().find(|t| t.name() == tag.as_ref())
+        let name = self.resolve_alias(tag.as_ref());
+        self.tags.keys().find(|t| t.name() == name)
+    }
+
+    /// Returns the name of the canonical tag if `name` is an alias or `name` itself otherwise.
+    pub fn resolve_alias<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map(String::as_str).unwrap_or(name)
+    }
+
+    /// Returns the tag as it should be saved: the registered canonical tag if `tag` is an alias or
+    /// is already registered, a tag with the canonical name and color of `tag` if the canonical
+    /// tag isn't registered yet, or `tag` itself otherwise.
+    pub fn resolve_tag(&self, tag: &Tag) -> Tag {
+        match self.get_tag(tag.name()) {
+            Some(registered) => registered.clone(),
+            None => Tag::new(self.resolve_alias(tag.name()), *tag.color()),
+        }
+    }
+
+    /// Makes `alias` an alias of the `tag`. Aliases of `alias` are moved to the canonical tag so
+    /// that aliases never point to other aliases. Returns `false` if the alias already exists.
+    ///
+    /// Entries tagged with `alias` should be retagged with the canonical tag before adding the
+    /// alias, afterwards they could no longer be found by it.
+    pub fn add_alias<S: Into<String>>(&mut self, alias: S, tag: S) -> Result<bool> {
+        let alias = alias.into();
+        let tag = self.resolve_alias(&tag.into()).to_string();
+        self.check_alias(&alias, &tag)?;
+        if self.aliases.get(&alias) == Some(&tag) {
+            return Ok(false);
+        }
+        for canonical in self.aliases.values_mut() {
+            if *canonical == alias {
+                *canonical = tag.clone();
+            }
+        }
+        self.aliases.insert(alias, tag);
+        Ok(true)
+    }
+
+    /// Checks that `alias` can become an alias of the `tag`.
+    pub fn check_alias(&self, alias: &str, tag: &str) -> Result<()> {
+        if alias == self.resolve_alias(tag) {
+            return Err(RegistryError::SelfAlias(alias.into()));
+        }
+        if self
+            .list_implications()
+            .any(|(implying, implied)| implying == alias || implied == alias)
+        {
+            return Err(RegistryError::AliasInImplication(alias.into()));
+        }
+        Ok(())
+    }
+
+    /// Removes the `alias`. Returns `false` if there was no such alias.
+    pub fn remove_alias(&mut self, alias: &str) -> bool {
+        self.aliases.remove(alias).is_some()
+    }
+
+    /// Lists all aliases as pairs of the alias and the name of the canonical tag.
+    pub fn list_aliases(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.aliases
+            .iter()
+            .map(|(alias, tag)| (alias.as_str(), tag.as_str()))
     }
 
     /// Updates the color of the `tag`. Returns `true` if the tag was found and updated and `false`
     /// otherwise.
     pub fn update_tag_color<T: AsRef<str>>(&mut self, tag: T, color: Color) -> bool {
-        if let Some(mut t) = self.tags.keys().find(|t| t.name() == tag.as_ref()).cloned() {
+        self.update_tag(tag, |t| t.set_color(&color))
+    }
+
+    /// Modifies the `tag` with `update`. Returns `true` if the tag was found and updated and
+    /// `false` otherwise.
+    pub fn update_tag<T, F>(&mut self, tag: T, update: F) -> bool
+    where
+        T: AsRef<str>,
+        F: FnOnce(&mut Tag),
+    {
+        if let Some(mut t) = self.get_tag(tag).cloned() {
             let data = self.tags.remove(&t).expect("removed tag");
-            t.set_color(&color);
+            update(&mut t);
             self.tags.insert(t, data);
             true
         } else {
             false
         }
     }
+
+    /// Proposes at most `limit` tags for the entry at `path` that it doesn't have yet, the best
+    /// first. Tags are scored by how often they are applied together with the tags of the entry,
+    /// how many entries in the same directory have them and how many entries with the same
+    /// extension have them.
+    pub fn suggest_tags<P: AsRef<Path>>(&self, path: P, limit: usize) -> Vec<Suggestion> {
+        let path = path.as_ref();
+        let id = self.find_entry(path);
+        let own: BTreeSet<&Tag> = id
+            .and_then(|id| self.list_entry_tags_btree(id))
+            .unwrap_or_default();
+        let others: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(other, _)| Some(**other) != id)
+            .collect();
+        let siblings: BTreeSet<EntryId> = others
+            .iter()
+            .filter(|(_, entry)| path.parent().is_some() && entry.path.parent() == path.parent())
+            .map(|(id, _)| **id)
+            .collect();
+        let same_extension: BTreeSet<EntryId> = match path.extension() {
+            Some(extension) => others
+                .iter()
+                .filter(|(_, entry)| entry.path.extension() == Some(extension))
+                .map(|(id, _)| **id)
+                .collect(),
+            None => BTreeSet::new(),
+        };
+        let share = |entries: &BTreeSet<EntryId>, of: &BTreeSet<EntryId>| {
+            if of.is_empty() {
+                0.
+            } else {
+                entries.intersection(of).count() as f64 / of.len() as f64
+            }
+        };
+
+        let mut suggestions: Vec<_> = self
+            .tags
+            .iter()
+            .filter(|(tag, _)| !own.contains(tag))
+            .map(|(tag, entries)| {
+                let co_occurrence = if own.is_empty() {
+                    0.
+                } else {
+                    own.iter()
+                        .filter_map(|t| self.tags.get(*t))
+                        .map(|with| share(entries, with))
+                        .sum::<f64>()
+                        / own.len() as f64
+                };
+                let siblings = share(entries, &siblings);
+                let extension = share(entries, &same_extension);
+                let (w_co, w_siblings, w_extension) = SUGGESTION_WEIGHTS;
+                Suggestion {
+                    tag: tag.clone(),
+                    score: w_co * co_occurrence + w_siblings * siblings + w_extension * extension,
+                    co_occurrence,
+                    siblings,
+                    extension,
+                }
+            })
+            .filter(|suggestion| suggestion.score > 0.)
+            .collect();
+        suggestions.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.tag.name().cmp(b.tag.name()))
+        });
+        suggestions.truncate(limit);
+        suggestions
+    }
+
+    /// Returns details of the `tag` with at most `top` tags most often applied to the same
+    /// entries. Returns `None` if there is no such tag.
+    pub fn tag_info<T: AsRef<str>>(&self, tag: T, top: usize) -> Option<TagInfo> {
+        let tag = self.get_tag(tag)?.clone();
+        let entries = self.tags.get(&tag)?;
+        let mut co_occurring = BTreeMap::new();
+        for (other, other_entries) in self.tags.iter().filter(|(t, _)| **t != tag) {
+            let count = other_entries.intersection(entries).count();
+            if count > 0 {
+                co_occurring.insert(other.name().to_string(), count);
+            }
+        }
+        let mut co_occurring: Vec<_> = co_occurring.into_iter().collect();
+        // the most frequent first, ties sorted by name
+        co_occurring.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        co_occurring.truncate(top);
+
+        Some(TagInfo {
+            aliases: self
+                .list_aliases()
+                .filter(|(_, canonical)| *canonical == tag.name())
+                .map(|(alias, _)| alias.to_string())
+                .collect(),
+            implies: self.implied_tags(tag.name()).into_iter().collect(),
+            usage: entries.len(),
+            co_occurring,
+            tag,
+        })
+    }
+
+    /// Saves the `query` under the `name`, replacing the query previously saved under it. Returns
+    /// the replaced query. Fails if the query is invalid or the name is used by a tag or an alias.
+    pub fn add_saved_search<S: Into<String>>(
+        &mut self,
+        name: S,
+        query: S,
+    ) -> Result<Option<String>> {
+        let (name, query) = (name.into(), query.into());
+        self.check_saved_search(&name, &query)?;
+        Ok(self.saved_searches.insert(name, query))
+    }
+
+    /// Checks that the `query` can be saved under the `name`.
+    pub fn check_saved_search(&self, name: &str, query: &str) -> Result<()> {
+        if self.get_tag(name).is_some() || self.aliases.contains_key(name) {
+            return Err(RegistryError::SavedSearchIsTag(name.into()));
+        }
+        let query = Query::parse(query).map_err(|e| RegistryError::InvalidQuery(name.into(), e))?;
+        let mut visited = HashSet::new();
+        let mut pending: Vec<_> = query
+            .tags
+            .iter()
+            .map(|tag| (tag.clone(), tag.clone()))
+            .collect();
+        // every saved search reachable from the query is visited with the name it was reached by
+        while let Some((via, tag)) = pending.pop() {
+            if tag == name {
+                return Err(RegistryError::SavedSearchCycle(name.into(), via));
+            }
+            if !visited.insert(tag.clone()) {
+                continue;
+            }
+            if let Some(Ok(query)) = self.saved_search(&tag).map(Query::parse) {
+                pending.extend(query.tags.into_iter().map(|tag| (via.clone(), tag)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the saved search with the `name` returning its query.
+    pub fn remove_saved_search(&mut self, name: &str) -> Option<String> {
+        self.saved_searches.remove(name)
+    }
+
+    /// Returns the query of the saved search with the `name`.
+    pub fn saved_search(&self, name: &str) -> Option<&str> {
+        self.saved_searches.get(name).map(String::as_str)
+    }
+
+    /// Lists all saved searches as pairs of their name and query.
+    pub fn list_saved_searches(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.saved_searches
+            .iter()
+            .map(|(name, query)| (name.as_str(), query.as_str()))
+    }
+
+    /// Exports tags, entries, implication rules, aliases and saved searches. Tags are ordered by
+    /// name and entries by path so that exports of the same registry are identical.
+    pub fn export(&self) -> RegistryExport {
+        let mut tags: Vec<_> = self.tags.keys().cloned().collect();
+        tags.sort_by(|a, b| a.name().cmp(b.name()));
+        let mut entries: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(id, entry)| {
+                let mut tags: Vec<_> = self
+                    .list_entry_tags(*id)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|tag| tag.name().to_string())
+                    .collect();
+                tags.sort_unstable();
+                ExportedEntry {
+                    entry: entry.clone(),
+                    tags,
+                }
+            })
+            .collect();
+        entries.sort_by(|a, b| a.entry.path().cmp(b.entry.path()));
+
+        RegistryExport {
+            tags,
+            entries,
+            implications: self.implications.clone(),
+            aliases: self.aliases.clone(),
+            saved_searches: self.saved_searches.clone(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -403,6 +1227,163 @@ mod tests {
         assert_eq!(registry.list_entry_tags(id), None);
     }
 
+    #[test]
+    fn inherits_tags_of_directories() {
+        let mut registry = TagRegistry::default();
+        let project = Tag::new("project", Red);
+        let draft = Tag::new("draft", Blue);
+        let (dir, _) = registry.add_or_update_entry(EntryData::new("/work/project"));
+        registry.tag_entry(&project, dir);
+        registry.tag_entry(&draft, dir);
+        let (file, _) = registry.add_or_update_entry(EntryData::new("/work/project/src/main.rs"));
+        registry.tag_entry(&draft, file);
+
+        let index = registry.entry_index();
+        let effective = registry.effective_tags("/work/project/src/main.rs", &index);
+        assert_eq!(effective.len(), 2);
+        assert_eq!(effective[0].tag, draft);
+        assert_eq!(effective[0].inherited_from, None);
+        assert_eq!(effective[1].tag, project);
+        assert_eq!(
+            effective[1].inherited_from.as_deref(),
+            Some(Path::new("/work/project"))
+        );
+        assert!(registry.effective_tags("/work/other.rs", &index).is_empty());
+
+        let tags = vec!["project".to_string(), "draft".to_string()];
+        let found = registry
+            .match_effective("/work/project/README.md", &index, &tags, false, None, None)
+            .unwrap();
+        assert_eq!(found.entry.path(), Path::new("/work/project/README.md"));
+        assert_eq!(found.inherited.len(), 2);
+        let found = registry
+            .match_effective(
+                "/work/project/src/main.rs",
+                &index,
+                &tags,
+                false,
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(found.inherited.keys().collect::<Vec<_>>(), vec!["project"]);
+        assert!(registry
+            .match_effective("/work/other.rs", &index, &tags, true, None, None)
+            .is_none());
+        let future = Utc::now() + chrono::Duration::hours(1);
+        assert!(registry
+            .match_effective("/work/project/a", &index, &tags, true, Some(&future), None)
+            .is_none());
+    }
+
+    #[test]
+    fn resolves_implications_and_detects_cycles() {
+        let mut registry = TagRegistry::default();
+        assert!(registry.add_implication("invoice", "finance").unwrap());
+        assert!(registry.add_implication("finance", "paperwork").unwrap());
+        assert!(registry.add_implication("raw", "photo").unwrap());
+        assert!(!registry.add_implication("invoice", "finance").unwrap());
+
+        assert!(matches!(
+            registry.add_implication("paperwork", "invoice"),
+            Err(RegistryError::ImplicationCycle(_, _))
+        ));
+        assert!(matches!(
+            registry.add_implication("raw", "raw"),
+            Err(RegistryError::SelfImplication(_))
+        ));
+
+        assert_eq!(
+            registry.implied_tags("invoice"),
+            BTreeSet::from(["finance".to_string(), "paperwork".to_string()])
+        );
+        assert_eq!(
+            registry.implying_tags("paperwork"),
+            BTreeSet::from([
+                "finance".to_string(),
+                "invoice".to_string(),
+                "paperwork".to_string()
+            ])
+        );
+        assert_eq!(registry.list_implications().count(), 3);
+
+        let (id, _) = registry.add_or_update_entry(EntryData::new("/docs/acme.pdf"));
+        registry.tag_entry(&Tag::new("invoice", Red), id);
+        let index = registry.entry_index();
+        let tags = vec!["paperwork".to_string()];
+        assert!(registry
+            .match_effective("/docs/acme.pdf", &index, &tags, false, None, None)
+            .is_some());
+
+        assert!(registry.remove_implication("finance", "paperwork"));
+        assert!(!registry.remove_implication("finance", "paperwork"));
+        assert!(registry.implied_tags("invoice").len() == 1);
+        assert!(registry
+            .match_effective("/docs/acme.pdf", &index, &tags, false, None, None)
+            .is_none());
+    }
+
+    #[test]
+    fn records_tagging_times() {
+        let mut registry = TagRegistry::default();
+        let before = Utc::now();
+        let (id, _) = registry.add_or_update_entry(EntryData::new("/tmp"));
+        let added = *registry.get_entry(id).unwrap().added().unwrap();
+        assert!(added >= before);
+
+        let tag = Tag::new("todo", Red);
+        registry.tag_entry(&tag, id);
+        registry.tag_entry(&Tag::new("other", Red), id);
+        let tagged = *registry.get_entry(id).unwrap().tagged_at("todo").unwrap();
+        assert!(tagged >= added);
+
+        let (same_id, added_now) = registry.add_or_update_entry(EntryData::new("/tmp"));
+        assert_eq!(same_id, id);
+        assert!(!added_now);
+        let entry = registry.get_entry(id).unwrap();
+        assert_eq!(entry.added(), Some(&added));
+        assert_eq!(entry.tagged_at("todo"), Some(&tagged));
+
+        let hour = chrono::Duration::hours(1);
+        assert!(entry.tagged_between("todo", None, None));
+        assert!(entry.tagged_between("todo", Some(&(tagged - hour)), None));
+        assert!(!entry.tagged_between("todo", None, Some(&tagged)));
+        assert!(!entry.tagged_between("todo", Some(&(tagged + hour)), None));
+        assert!(!entry.tagged_between("missing", Some(&(tagged - hour)), None));
+
+        registry.untag_entry(&tag, id);
+        assert!(registry.get_entry(id).unwrap().tagged_at("todo").is_none());
+    }
+
+    #[test]
+    fn lists_expired_tags() {
+        let mut registry = TagRegistry::default();
+        let (id, _) = registry.add_or_update_entry(EntryData::new("/tmp"));
+        let tmp = Tag::new("tmp", Red);
+        let keep = Tag::new("keep", Red);
+        registry.tag_entry(&tmp, id);
+        registry.tag_entry(&keep, id);
+
+        let now = Utc::now();
+        let expires = now + chrono::Duration::days(1);
+        registry.set_tag_expiry(tmp.name(), id, Some(expires));
+        assert_eq!(
+            registry.get_entry(id).unwrap().expires_at("tmp"),
+            Some(&expires)
+        );
+        assert!(registry.list_expired(&now).is_empty());
+
+        let expired = registry.list_expired(&(expires + chrono::Duration::seconds(1)));
+        assert_eq!(
+            expired,
+            vec![TagChange::remove("/tmp", tmp.clone()).with_expiry(Some(expires))]
+        );
+        assert_eq!(expired[0].inverse().expires, Some(expires));
+
+        registry.untag_entry(&tmp, id);
+        assert!(registry.get_entry(id).unwrap().expires_at("tmp").is_none());
+    }
+
     #[test]
     fn adds_multiple_entries() {
         let mut registry = TagRegistry::default();
@@ -554,6 +1535,78 @@ mod tests {
         assert!(entries.contains(&id4));
     }
 
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(normalize_path("/a/./b/../c/"), PathBuf::from("/a/c"));
+        assert_eq!(normalize_path("/../a//b/."), PathBuf::from("/a/b"));
+        assert_eq!(normalize_path("../a/../../b"), PathBuf::from("../../b"));
+        assert_eq!(normalize_path("./a"), PathBuf::from("a"));
+        assert_eq!(normalize_path("/a/./b").as_os_str(), "/a/b");
+
+        let mut registry = TagRegistry::default();
+        let (id, _) = registry.add_or_update_entry(EntryData::new("/tmp/a/../b/"));
+        assert_eq!(registry.get_entry(id).unwrap().path(), Path::new("/tmp/b"));
+        assert_eq!(registry.find_entry("/tmp/./b/"), Some(id));
+        assert_eq!(registry.find_entry("/tmp/c/../b"), Some(id));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn canonicalizes_entries() {
+        let tmp_dir = tempdir::TempDir::new("registry-test").unwrap();
+        let dir = fs::canonicalize(tmp_dir.path()).unwrap();
+        fs::create_dir(dir.join("files")).unwrap();
+        fs::write(dir.join("files/a.txt"), b"").unwrap();
+        std::os::unix::fs::symlink(dir.join("files"), dir.join("link")).unwrap();
+        std::os::unix::fs::symlink(dir.join("files/a.txt"), dir.join("files/b.txt")).unwrap();
+
+        assert_eq!(
+            canonical_path(dir.join("link/./a.txt")),
+            dir.join("files/a.txt")
+        );
+        assert_eq!(
+            canonical_path(dir.join("link/b.txt")),
+            dir.join("files/a.txt")
+        );
+        assert_eq!(
+            canonical_path(dir.join("link/missing.txt")),
+            dir.join("files/missing.txt")
+        );
+        assert_eq!(canonical_path(dir.join("missing/../x/")), dir.join("x"));
+
+        let mut registry = TagRegistry::default();
+        let first = Tag::new("first", Black);
+        let second = Tag::new("second", Black);
+        registry
+            .entries
+            .insert(1, EntryData::new(dir.join("files/a.txt")));
+        registry
+            .entries
+            .insert(2, EntryData::new(dir.join("link/a.txt")));
+        registry.tag_entry(&first, 1);
+        registry.tag_entry(&second, 2);
+        registry
+            .entries
+            .insert(3, EntryData::new(dir.join("./files/")));
+
+        assert_eq!(registry.canonicalize_entries(), 2);
+        assert_eq!(
+            registry.get_entry(3).unwrap().path().as_os_str(),
+            dir.join("files").as_os_str()
+        );
+        registry.remove_entry(3);
+        let entries: Vec<_> = registry.list_entries_and_ids().collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(*entries[0].0, 1);
+        assert!(entries[0].1.tagged_at("first").is_some());
+        assert!(entries[0].1.tagged_at("second").is_some());
+        assert_eq!(
+            registry.list_entries_with_all_tags(vec!["first", "second"]),
+            vec![1]
+        );
+        assert_eq!(registry.canonicalize_entries(), 0);
+    }
+
     #[test]
     fn saves_and_loads() {
         let tmp_dir = tempdir::TempDir::new("registry-test").unwrap();
@@ -574,10 +1627,214 @@ mod tests {
         let (got_id, got_entry) = entries.next().unwrap();
         assert!(entries.next().is_none());
         assert_eq!(got_id, &id);
-        assert_eq!(got_entry, &entry);
+        assert_eq!(got_entry.path(), entry.path());
+        assert_eq!(got_entry.added(), entry.added());
+        assert!(got_entry.tagged_at(tag.name()).is_some());
         assert_eq!(
             registry.list_entries_with_any_tags(vec![tag.name()]),
             vec![id]
         );
     }
+
+    #[test]
+    fn resolves_aliases() {
+        let mut registry = TagRegistry::default();
+        let docs = Tag::new("docs", Red);
+        let (id, _) = registry.add_or_update_entry(EntryData::new("/work/README.md"));
+        registry.tag_entry(&docs, id);
+
+        assert!(registry.add_alias("doc", "docs").unwrap());
+        assert!(!registry.add_alias("doc", "docs").unwrap());
+        assert!(registry.add_alias("documentation", "doc").unwrap());
+        assert!(matches!(
+            registry.add_alias("docs", "documentation"),
+            Err(RegistryError::SelfAlias(_))
+        ));
+        assert_eq!(registry.resolve_alias("documentation"), "docs");
+        assert_eq!(registry.get_tag("doc"), Some(&docs));
+        assert_eq!(registry.list_entries_with_any_tags(["doc"]), vec![id]);
+        assert_eq!(
+            registry.list_entries_with_all_tags(["documentation", "docs"]),
+            vec![id]
+        );
+
+        let (other, _) = registry.add_or_update_entry(EntryData::new("/work/guide.md"));
+        registry.tag_entry(&Tag::new("documentation", Blue), other);
+        assert_eq!(registry.list_entry_tags(other), Some(vec![&docs]));
+        assert!(registry.untag_entry(&Tag::new("doc", Blue), id).is_some());
+        assert_eq!(registry.list_entries_with_any_tags(["docs"]), vec![other]);
+
+        registry.add_alias("notes", "todo").unwrap();
+        registry.add_alias("howto", "notes").unwrap();
+        assert_eq!(registry.resolve_alias("howto"), "todo");
+        registry.add_alias("todo", "docs").unwrap();
+        assert_eq!(registry.resolve_alias("notes"), "docs");
+        assert_eq!(registry.resolve_alias("howto"), "docs");
+
+        registry.add_implication("docs", "paperwork").unwrap();
+        assert!(matches!(
+            registry.add_alias("paperwork", "docs"),
+            Err(RegistryError::AliasInImplication(_))
+        ));
+        assert!(registry.implying_tags("paperwork").contains("docs"));
+        assert!(registry.implied_tags("doc").contains("paperwork"));
+
+        assert_eq!(registry.list_aliases().count(), 5);
+        assert!(registry.remove_alias("doc"));
+        assert!(!registry.remove_alias("doc"));
+        assert_eq!(registry.get_tag("doc"), None);
+    }
+
+    #[test]
+    fn describes_tags() {
+        let mut registry = TagRegistry::default();
+        let (a, _) = registry.add_or_update_entry(EntryData::new("/a"));
+        let (b, _) = registry.add_or_update_entry(EntryData::new("/b"));
+        let (c, _) = registry.add_or_update_entry(EntryData::new("/c"));
+        for (tag, entries) in [
+            ("work", vec![a, b, c]),
+            ("urgent", vec![a, b]),
+            ("draft", vec![c]),
+            ("home", vec![]),
+        ] {
+            for entry in entries {
+                registry.tag_entry(&Tag::new(tag, Red), entry);
+            }
+        }
+        registry.add_alias("job", "work").unwrap();
+        registry.add_implication("urgent", "work").unwrap();
+
+        assert!(registry.get_tag("work").unwrap().created().is_some());
+        assert!(registry.update_tag("job", |tag| {
+            tag.set_description("paid work");
+            tag.set_icon("💼");
+        }));
+        assert!(!registry.update_tag("home", |tag| tag.set_description("")));
+
+        let info = registry.tag_info("job", 1).unwrap();
+        assert_eq!(info.tag.description(), Some("paid work"));
+        assert_eq!(info.tag.icon(), Some("💼"));
+        assert_eq!(info.usage, 3);
+        assert_eq!(info.aliases, vec!["job"]);
+        assert!(info.implies.is_empty());
+        assert_eq!(info.co_occurring, vec![("urgent".to_string(), 2)]);
+
+        let info = registry.tag_info("urgent", 5).unwrap();
+        assert_eq!(info.implies, vec!["work"]);
+        assert_eq!(info.co_occurring, vec![("work".to_string(), 2)]);
+        assert!(registry.tag_info("home", 5).is_none());
+
+        registry.update_tag("work", |tag| tag.set_description(""));
+        assert_eq!(registry.get_tag("work").unwrap().description(), None);
+    }
+
+    #[test]
+    fn suggests_tags() {
+        let mut registry = TagRegistry::default();
+        for (path, tags) in [
+            ("/photos/a.jpg", vec!["photo", "holiday"]),
+            ("/photos/b.jpg", vec!["photo", "holiday", "beach"]),
+            ("/photos/c.png", vec!["photo"]),
+            ("/docs/d.jpg", vec!["scan"]),
+            ("/docs/e.txt", vec!["holiday"]),
+        ] {
+            let (id, _) = registry.add_or_update_entry(EntryData::new(path));
+            for tag in tags {
+                registry.tag_entry(&Tag::new(tag, Red), id);
+            }
+        }
+
+        let suggestions = registry.suggest_tags("/photos/new.jpg", 10);
+        let names: Vec<_> = suggestions.iter().map(|s| s.tag.name()).collect();
+        assert_eq!(names, vec!["photo", "holiday", "beach", "scan"]);
+        assert_eq!(suggestions[0].siblings, 1.);
+        assert_eq!(suggestions[0].extension, 2. / 3.);
+        assert_eq!(suggestions[0].co_occurrence, 0.);
+        assert_eq!(registry.suggest_tags("/photos/new.jpg", 2).len(), 2);
+
+        let suggestions = registry.suggest_tags("/photos/c.png", 10);
+        assert_eq!(suggestions[0].tag.name(), "holiday");
+        assert_eq!(suggestions[0].co_occurrence, 2. / 3.);
+        assert!(suggestions.iter().all(|s| s.tag.name() != "photo"));
+        assert!(registry.suggest_tags("/other/x.bin", 10).is_empty());
+    }
+
+    #[test]
+    fn saves_searches_and_exports() {
+        let mut registry = TagRegistry::default();
+        let (id, _) = registry.add_or_update_entry(EntryData::new("/docs/b.pdf"));
+        registry.tag_entry(&Tag::new("invoice", Red), id);
+        registry.tag_entry(&Tag::new("acme", Blue), id);
+        let (id, _) = registry.add_or_update_entry(EntryData::new("/docs/a.pdf"));
+        registry.tag_entry(&Tag::new("invoice", Red), id);
+        registry.add_alias("bill", "invoice").unwrap();
+
+        assert_eq!(
+            registry
+                .add_saved_search("recent", "invoice --tagged-since 30d")
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            registry
+                .add_saved_search("recent", "invoice --tagged-since 7d")
+                .unwrap()
+                .as_deref(),
+            Some("invoice --tagged-since 30d")
+        );
+        assert!(matches!(
+            registry.add_saved_search("invoice", "acme"),
+            Err(RegistryError::SavedSearchIsTag(_))
+        ));
+        assert!(matches!(
+            registry.add_saved_search("bill", "acme"),
+            Err(RegistryError::SavedSearchIsTag(_))
+        ));
+        assert!(matches!(
+            registry.add_saved_search("broken", "acme --size"),
+            Err(RegistryError::InvalidQuery(_, _))
+        ));
+        assert_eq!(
+            registry.saved_search("recent"),
+            Some("invoice --tagged-since 7d")
+        );
+        registry
+            .add_saved_search("acme-recent", "recent acme")
+            .unwrap();
+        assert!(matches!(
+            registry.add_saved_search("recent", "acme-recent"),
+            Err(RegistryError::SavedSearchCycle(_, via)) if via == "acme-recent"
+        ));
+        assert!(matches!(
+            registry.add_saved_search("loop", "loop"),
+            Err(RegistryError::SavedSearchCycle(_, _))
+        ));
+        registry.remove_saved_search("acme-recent");
+
+        let export = registry.export();
+        let names: Vec<_> = export.tags.iter().map(Tag::name).collect();
+        assert_eq!(names, vec!["acme", "invoice"]);
+        let paths: Vec<_> = export.entries.iter().map(|e| e.entry.path()).collect();
+        assert_eq!(
+            paths,
+            vec![Path::new("/docs/a.pdf"), Path::new("/docs/b.pdf")]
+        );
+        assert_eq!(export.entries[1].tags, vec!["acme", "invoice"]);
+        assert_eq!(
+            export.aliases.get("bill").map(String::as_str),
+            Some("invoice")
+        );
+        assert_eq!(export.saved_searches.len(), 1);
+
+        let yaml = serde_yaml::to_string(&export).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<RegistryExport>(&yaml).unwrap(),
+            export
+        );
+        assert_eq!(
+            registry.remove_saved_search("recent").as_deref(),
+            Some("invoice --tagged-since 7d")
+        );
+        assert_eq!(registry.list_saved_searches().count(), 0);
+    }
 }
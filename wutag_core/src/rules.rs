@@ -0,0 +1,336 @@
+//! Rules that decide which tags are automatically applied to files in watched folders.
+use crate::mime;
+use globset::GlobBuilder;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RuleError {
+    #[error("invalid glob pattern `{0}` - {1}")]
+    InvalidGlob(String, globset::Error),
+    #[error("invalid regex `{0}` - {1}")]
+    InvalidRegex(String, regex::Error),
+    #[error("failed to read metadata of `{0}` - {1}")]
+    ReadMetadata(PathBuf, io::Error),
+    #[error("failed to detect content type of `{0}` - {1}")]
+    DetectType(PathBuf, io::Error),
+}
+
+type Result<T> = std::result::Result<T, RuleError>;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// A rule that applies `tags` to a file when all of its conditions match. Conditions that are not
+/// set always match.
+pub struct Rule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Glob pattern matched against the path of the file relative to the watched folder.
+    pub glob: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    /// Extensions of the file without the leading dot, compared case insensitively.
+    pub extensions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Minimum size of the file in bytes.
+    pub min_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Maximum size of the file in bytes.
+    pub max_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Regular expression matched against the file name.
+    pub name_regex: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// MIME type of the file like `application/pdf`. The subtype can be a wildcard like `image/*`.
+    /// The type is detected from the content of the file.
+    pub mime: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    /// Whether to also apply a tag describing the detected content type like `type/image`.
+    pub type_tags: bool,
+}
+
+impl Rule {
+    /// Checks whether the rule matches the file at `path` located in the `folder`.
+    pub fn matches(&self, folder: &Path, path: &Path) -> Result<bool> {
+        if let Some(glob) = &self.glob {
+            let matcher = GlobBuilder::new(glob)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| RuleError::InvalidGlob(glob.clone(), e))?
+                .compile_matcher();
+            if !matcher.is_match(path.strip_prefix(folder).unwrap_or(path)) {
+                return Ok(false);
+            }
+        }
+
+        if !self.extensions.is_empty() {
+            let extension = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            if !self
+                .extensions
+                .iter()
+                .any(|ext| ext.trim_start_matches('.').to_lowercase() == extension)
+            {
+                return Ok(false);
+            }
+        }
+
+        if let Some(name_regex) = &self.name_regex {
+            let regex = Regex::new(name_regex)
+                .map_err(|e| RuleError::InvalidRegex(name_regex.clone(), e))?;
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default();
+            if !regex.is_match(&name) {
+                return Ok(false);
+            }
+        }
+
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let size = fs::metadata(path)
+                .map_err(|e| RuleError::ReadMetadata(path.to_path_buf(), e))?
+                .len();
+            if self.min_size.map(|min| size < min).unwrap_or(false)
+                || self.max_size.map(|max| size > max).unwrap_or(false)
+            {
+                return Ok(false);
+            }
+        }
+
+        if let Some(mime) = &self.mime {
+            let detected =
+                mime::detect(path).map_err(|e| RuleError::DetectType(path.to_path_buf(), e))?;
+            if !mime_matches(mime, detected.mime) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Returns the tags applied by this rule to the file at `path` that it matched.
+    pub fn tags_for(&self, path: &Path) -> Result<Vec<String>> {
+        let mut tags = self.tags.clone();
+        if self.type_tags {
+            let detected =
+                mime::detect(path).map_err(|e| RuleError::DetectType(path.to_path_buf(), e))?;
+            let tag = detected.kind.tag();
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        Ok(tags)
+    }
+
+    /// Checks that the patterns of this rule are valid.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(glob) = &self.glob {
+            GlobBuilder::new(glob)
+                .build()
+                .map_err(|e| RuleError::InvalidGlob(glob.clone(), e))?;
+        }
+        if let Some(name_regex) = &self.name_regex {
+            Regex::new(name_regex).map_err(|e| RuleError::InvalidRegex(name_regex.clone(), e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks whether the MIME type `mime` matches the `pattern` like `image/png` or `image/*`.
+pub fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(top_level) => mime
+            .split('/')
+            .next()
+            .map(|t| t.eq_ignore_ascii_case(top_level))
+            .unwrap_or(false),
+        None => pattern.eq_ignore_ascii_case(mime),
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// A folder in which newly created or moved files are tagged according to the `rules`.
+pub struct WatchFolder {
+    pub path: PathBuf,
+    #[serde(default)]
+    /// Whether files in subdirectories of the folder are tagged as well.
+    pub recursive: bool,
+    pub rules: Vec<Rule>,
+}
+
+impl WatchFolder {
+    /// Checks whether the file at `path` belongs to this folder.
+    pub fn contains(&self, path: &Path) -> bool {
+        if self.recursive {
+            path.starts_with(&self.path) && path != self.path
+        } else {
+            path.parent() == Some(self.path.as_path())
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+/// A rule that matched a file.
+pub struct RuleMatch {
+    /// The watched folder that contains the rule.
+    pub folder: PathBuf,
+    /// Index of the rule in the list of rules of the folder.
+    pub rule: usize,
+    pub tags: Vec<String>,
+}
+
+/// Finds all rules of the `folders` that match the file at `path`.
+pub fn match_rules(folders: &[WatchFolder], path: &Path) -> Result<Vec<RuleMatch>> {
+    let mut matches = vec![];
+    for folder in folders.iter().filter(|folder| folder.contains(path)) {
+        for (i, rule) in folder.rules.iter().enumerate() {
+            if rule.matches(&folder.path, path)? {
+                matches.push(RuleMatch {
+                    folder: folder.path.clone(),
+                    rule: i,
+                    tags: rule.tags_for(path)?,
+                });
+            }
+        }
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn downloads() -> WatchFolder {
+        WatchFolder {
+            path: "/home/user/Downloads".into(),
+            recursive: false,
+            rules: vec![
+                Rule {
+                    glob: Some("*.pdf".into()),
+                    tags: vec!["inbox".into(), "pdf".into()],
+                    ..Default::default()
+                },
+                Rule {
+                    extensions: vec!["JPG".into(), "png".into()],
+                    name_regex: Some("^IMG_".into()),
+                    tags: vec!["photos".into()],
+                    ..Default::default()
+                },
+                Rule {
+                    extensions: vec!["jpg".into(), "png".into()],
+                    tags: vec!["image".into()],
+                    ..Default::default()
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn matches_rules_of_folder() {
+        let folders = [downloads()];
+
+        let matches = match_rules(&folders, Path::new("/home/user/Downloads/doc.pdf")).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].rule, 0);
+        assert_eq!(matches[0].tags, vec!["inbox", "pdf"]);
+
+        let matches = match_rules(&folders, Path::new("/home/user/Downloads/IMG_1.jpg")).unwrap();
+        let rules: Vec<_> = matches.iter().map(|m| m.rule).collect();
+        assert_eq!(rules, vec![1, 2]);
+
+        let matches = match_rules(&folders, Path::new("/home/user/Downloads/cat.png")).unwrap();
+        let rules: Vec<_> = matches.iter().map(|m| m.rule).collect();
+        assert_eq!(rules, vec![2]);
+
+        assert!(
+            match_rules(&folders, Path::new("/home/user/Downloads/sub/doc.pdf"))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(match_rules(&folders, Path::new("/home/user/doc.pdf"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn matches_size_and_recursive_folders() {
+        let tmp_dir = tempdir::TempDir::new("rules-test").unwrap();
+        let nested = tmp_dir.path().join("nested");
+        fs::create_dir(&nested).unwrap();
+        let file = nested.join("big.bin");
+        fs::write(&file, [0; 100]).unwrap();
+
+        let folder = WatchFolder {
+            path: tmp_dir.path().to_path_buf(),
+            recursive: true,
+            rules: vec![
+                Rule {
+                    glob: Some("nested/*".into()),
+                    min_size: Some(50),
+                    tags: vec!["big".into()],
+                    ..Default::default()
+                },
+                Rule {
+                    max_size: Some(50),
+                    tags: vec!["small".into()],
+                    ..Default::default()
+                },
+            ],
+        };
+        let matches = match_rules(&[folder], &file).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].tags, vec!["big"]);
+    }
+
+    #[test]
+    fn matches_detected_content_types() {
+        let tmp_dir = tempdir::TempDir::new("rules-test").unwrap();
+        let image = tmp_dir.path().join("image.bin");
+        fs::write(&image, b"\x89PNG\r\n\x1a\n\x00\x00").unwrap();
+        let text = tmp_dir.path().join("fake.png");
+        fs::write(&text, b"not an image").unwrap();
+
+        let folder = WatchFolder {
+            path: tmp_dir.path().to_path_buf(),
+            recursive: false,
+            rules: vec![
+                Rule {
+                    mime: Some("image/*".into()),
+                    tags: vec!["image".into()],
+                    ..Default::default()
+                },
+                Rule {
+                    type_tags: true,
+                    ..Default::default()
+                },
+            ],
+        };
+        let folders = [folder];
+        let matches = match_rules(&folders, &image).unwrap();
+        let tags: Vec<_> = matches.iter().flat_map(|m| m.tags.clone()).collect();
+        assert_eq!(tags, vec!["image", "type/image"]);
+
+        let matches = match_rules(&folders, &text).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].tags, vec!["type/text"]);
+    }
+
+    #[test]
+    fn validates_and_matches_mime_patterns() {
+        assert!(mime_matches("image/*", "image/png"));
+        assert!(mime_matches("application/pdf", "application/pdf"));
+        assert!(!mime_matches("image/*", "text/plain"));
+
+        let invalid = Rule {
+            name_regex: Some("(".into()),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//! Ordering and paging of listed and searched entries so that results are deterministic.
+use crate::registry::EntryData;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error(
+    "`{0}` is not a valid sort key, expected one of `path`, `tags`, `tagged`, `size` or `modified`"
+)]
+pub struct InvalidSortKey(String);
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Path,
+    /// Number of tags of the entry.
+    Tags,
+    /// Time at which the entry was last tagged.
+    Tagged,
+    /// Size of the file.
+    Size,
+    /// Modification time of the file.
+    Modified,
+}
+
+impl FromStr for SortKey {
+    type Err = InvalidSortKey;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(SortKey::Path),
+            "tags" => Ok(SortKey::Tags),
+            "tagged" => Ok(SortKey::Tagged),
+            "size" => Ok(SortKey::Size),
+            "modified" | "mtime" => Ok(SortKey::Modified),
+            _ => Err(InvalidSortKey(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// Order of entries. Entries with equal keys are ordered by path so the order is always the same.
+pub struct Sort {
+    pub key: SortKey,
+    pub reverse: bool,
+}
+
+/// Value of a sort key of a single entry. Files whose size or modification time can't be read
+/// have no value and are ordered after all other files, or before them in reverse order.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum KeyValue {
+    None,
+    Count(usize),
+    Time(Option<DateTime<Utc>>),
+    Size(Option<u64>),
+}
+
+impl KeyValue {
+    fn cmp_missing_last(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (KeyValue::Time(None), KeyValue::Time(Some(_)))
+            | (KeyValue::Size(None), KeyValue::Size(Some(_))) => Ordering::Greater,
+            (KeyValue::Time(Some(_)), KeyValue::Time(None))
+            | (KeyValue::Size(Some(_)), KeyValue::Size(None)) => Ordering::Less,
+            (a, b) => a.cmp(b),
+        }
+    }
+}
+
+impl Sort {
+    /// Sorts the `items`. `entry` returns the entry of an item and `tag_count` the number of its
+    /// tags.
+    pub fn sort<T>(
+        &self,
+        items: Vec<T>,
+        entry: impl Fn(&T) -> &EntryData,
+        tag_count: impl Fn(&T) -> usize,
+    ) -> Vec<T> {
+        // keys are computed once per item as they may require reading metadata of the file
+        let key = |item: &T| {
+            let entry = entry(item);
+            match self.key {
+                SortKey::Path => KeyValue::None,
+                SortKey::Tags => KeyValue::Count(tag_count(item)),
+                SortKey::Tagged => KeyValue::Time(entry.tagged().values().max().copied()),
+                SortKey::Size => {
+                    KeyValue::Size(fs::symlink_metadata(entry.path()).ok().map(|m| m.len()))
+                }
+                SortKey::Modified => KeyValue::Time(
+                    fs::symlink_metadata(entry.path())
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .map(DateTime::from),
+                ),
+            }
+        };
+        let mut keyed: Vec<_> = items.into_iter().map(|item| (key(&item), item)).collect();
+        keyed.sort_by(|(a_key, a), (b_key, b)| {
+            let ordering = a_key
+                .cmp_missing_last(b_key)
+                .then_with(|| entry(a).path().cmp(entry(b).path()));
+            if self.reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        keyed.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// A slice of sorted results.
+pub struct Page {
+    /// Number of results to skip.
+    pub offset: usize,
+    /// Maximum number of returned results, all remaining results when not set.
+    pub limit: Option<usize>,
+}
+
+impl Page {
+    pub fn apply<T>(&self, items: Vec<T>) -> Vec<T> {
+        items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::TagRegistry;
+    use crate::tag::Tag;
+    use colored::Color::{Blue, Red};
+
+    #[test]
+    fn sorts_entries() {
+        let tmp_dir = tempdir::TempDir::new("sort-test").unwrap();
+        let dir = tmp_dir.path();
+        let small = dir.join("small");
+        let big = dir.join("big");
+        fs::write(&small, b"1").unwrap();
+        fs::write(&big, b"1234").unwrap();
+
+        let mut registry = TagRegistry::default();
+        let red = Tag::new("red", Red);
+        let blue = Tag::new("blue", Blue);
+        let missing = dir.join("missing");
+        for (path, tags) in [
+            (&small, vec![&red, &blue]),
+            (&big, vec![&red]),
+            (&missing, vec![&red, &blue]),
+        ] {
+            let (id, _) = registry.add_or_update_entry(EntryData::new(path));
+            for tag in tags {
+                registry.tag_entry(tag, id);
+            }
+        }
+        let entries: Vec<_> = registry.list_entries().cloned().collect();
+        let sorted = |key, reverse| {
+            Sort { key, reverse }
+                .sort(entries.clone(), |e| e, |e| e.tagged().len())
+                .into_iter()
+                .map(|e| e.path().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            sorted(SortKey::Path, false),
+            vec![big.clone(), missing.clone(), small.clone()]
+        );
+        assert_eq!(
+            sorted(SortKey::Path, true),
+            vec![small.clone(), missing.clone(), big.clone()]
+        );
+        // equal numbers of tags are ordered by path
+        assert_eq!(
+            sorted(SortKey::Tags, false),
+            vec![big.clone(), missing.clone(), small.clone()]
+        );
+        // files without metadata come last
+        assert_eq!(
+            sorted(SortKey::Size, false),
+            vec![small.clone(), big.clone(), missing.clone()]
+        );
+        assert_eq!(
+            sorted(SortKey::Size, true),
+            vec![missing.clone(), big.clone(), small.clone()]
+        );
+    }
+
+    #[test]
+    fn pages_results() {
+        let items: Vec<_> = (0..10).collect();
+        assert_eq!(Page::default().apply(items.clone()), items);
+        let page = Page {
+            offset: 3,
+            limit: Some(4),
+        };
+        assert_eq!(page.apply(items.clone()), vec![3, 4, 5, 6]);
+        let page = Page {
+            offset: 8,
+            limit: Some(4),
+        };
+        assert_eq!(page.apply(items.clone()), vec![8, 9]);
+        let page = Page {
+            offset: 20,
+            limit: None,
+        };
+        assert!(page.apply(items).is_empty());
+        assert!("mtime".parse::<SortKey>().is_ok());
+        assert!("name".parse::<SortKey>().is_err());
+    }
+}
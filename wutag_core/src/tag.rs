@@ -1,23 +1,38 @@
 //! Functions for manipulating tags on files.
+use chrono::{DateTime, Utc};
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryFrom;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::path::Path;
+use unicode_normalization::UnicodeNormalization;
 
 use crate::color::Color;
-use crate::xattr::{list_xattrs, remove_xattr, set_xattr, Xattr};
+use crate::xattr::{get_xattr, list_xattrs, remove_xattr, set_xattr, Xattr};
 use crate::{Error, Result, WUTAG_NAMESPACE};
 
 pub const DEFAULT_COLOR: Color = Color::BrightWhite;
+/// Longest name in bytes whose attribute key still fits into the 255 byte limit of extended
+/// attribute names.
+pub const MAX_NAME_BYTES: usize = 183;
 
 #[derive(Clone, Debug, Deserialize, Eq, Serialize)]
 pub struct Tag {
     name: String,
     color: Color,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// What the tag means, set with `wutag edit --description`.
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// An icon or emoji displayed next to the tag.
+    icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// When the tag was first added to the registry. Tags registered before this was recorded
+    /// don't have it.
+    created: Option<DateTime<Utc>>,
 }
 
 impl Hash for Tag {
@@ -34,6 +49,9 @@ impl Tag {
         Tag {
             name: name.into(),
             color,
+            description: None,
+            icon: None,
+            created: None,
         }
     }
 
@@ -60,12 +78,49 @@ impl Tag {
         self.color = *color;
     }
 
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Sets the description of this tag, an empty description removes it.
+    pub fn set_description<S: Into<String>>(&mut self, description: S) {
+        let description = description.into();
+        self.description = (!description.is_empty()).then_some(description);
+    }
+
+    pub fn icon(&self) -> Option<&str> {
+        self.icon.as_deref()
+    }
+
+    /// Sets the icon of this tag, an empty icon removes it.
+    pub fn set_icon<S: Into<String>>(&mut self, icon: S) {
+        let icon = icon.into();
+        self.icon = (!icon.is_empty()).then_some(icon);
+    }
+
+    pub fn created(&self) -> Option<&DateTime<Utc>> {
+        self.created.as_ref()
+    }
+
+    pub fn set_created(&mut self, created: DateTime<Utc>) {
+        self.created = Some(created);
+    }
+
     fn hash(&self) -> String {
         format!("{}.{}", WUTAG_NAMESPACE, base64::encode(&self.name))
     }
 
     /// Tags the file at the given `path` with this tag. If the tag exists returns an error.
     pub fn save_to<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        self.save_to_with_expiry(path, None)
+    }
+
+    /// Tags the file at the given `path` with this tag that expires at `expires`. The expiry is
+    /// stored as the value of the attribute. If the tag exists returns an error.
+    pub fn save_to_with_expiry<P>(&self, path: P, expires: Option<&DateTime<Utc>>) -> Result<()>
     where
         P: AsRef<Path>,
     {
@@ -74,7 +129,23 @@ impl Tag {
                 return Err(Error::TagExists);
             }
         }
-        set_xattr(path, self.hash().as_str(), "")
+        let value = expires.map(DateTime::to_rfc3339).unwrap_or_default();
+        set_xattr(path, self.hash(), value)
+    }
+
+    /// Reads the expiry of this tag stored on the file at the given `path`. Returns `None` if
+    /// the tag doesn't expire.
+    pub fn expiry_in<P>(&self, path: P) -> Result<Option<DateTime<Utc>>>
+    where
+        P: AsRef<Path>,
+    {
+        let value = String::from_utf8(get_xattr(path, self.hash())?)?;
+        if value.is_empty() {
+            return Ok(None);
+        }
+        DateTime::parse_from_rfc3339(&value)
+            .map(|expires| Some(expires.with_timezone(&Utc)))
+            .map_err(|_| Error::InvalidTime(value))
     }
 
     /// Removes this tag from the file at the given `path`. If the tag doesn't exists returns
@@ -88,7 +159,7 @@ impl Tag {
         for xattr in list_xattrs(path.as_ref())? {
             let key = xattr.key();
             // make sure to only remove attributes corresponding to this namespace
-            if key == hash {
+            if key == hash.as_str() {
                 return remove_xattr(path, key);
             }
         }
@@ -122,7 +193,7 @@ impl PartialEq for Tag {
 
 impl PartialOrd for Tag {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.name.partial_cmp(&other.name)
+        Some(self.cmp(other))
     }
 }
 
@@ -136,7 +207,12 @@ macro_rules! next_or_else {
 impl TryFrom<Xattr> for Tag {
     type Error = Error;
     fn try_from(xattr: Xattr) -> Result<Self> {
-        let key = xattr.key();
+        let key = xattr.key().to_str().ok_or_else(|| {
+            Error::InvalidTagKey(format!(
+                "`{}` is not valid UTF-8",
+                xattr.key().to_string_lossy()
+            ))
+        })?;
 
         let mut elems = key.split("wutag.");
 
@@ -155,6 +231,97 @@ impl TryFrom<Xattr> for Tag {
     }
 }
 
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(default)]
+/// Rules applied to names of new tags. Names are always normalized to Unicode NFC with
+/// surrounding whitespace trimmed, must not be empty, must not contain control characters and
+/// must fit into [MAX_NAME_BYTES](MAX_NAME_BYTES).
+pub struct NameRules {
+    /// Convert names to lowercase so that `Foo` and `foo` are the same tag.
+    pub case_fold: bool,
+    /// Characters that names must not contain.
+    pub forbidden: Vec<char>,
+    /// Maximum number of characters of a name, only [MAX_NAME_BYTES](MAX_NAME_BYTES) applies if
+    /// not set.
+    pub max_length: Option<usize>,
+}
+
+impl NameRules {
+    /// Returns the normalized `name` or an error if it breaks any of the rules.
+    pub fn normalize(&self, name: &str) -> Result<String> {
+        let invalid = |reason: String| Error::InvalidTagName(name.to_string(), reason);
+        let mut normalized: String = name.trim().nfc().collect();
+        if self.case_fold {
+            normalized = normalized.to_lowercase();
+        }
+
+        if normalized.is_empty() {
+            return Err(invalid("name is empty".into()));
+        }
+        if let Some(c) = normalized
+            .chars()
+            .find(|c| c.is_control() || self.forbidden.contains(c))
+        {
+            return Err(invalid(format!("character {c:?} is not allowed")));
+        }
+        let length = normalized.chars().count();
+        if let Some(max_length) = self.max_length.filter(|max| length > *max) {
+            return Err(invalid(format!(
+                "name has {length} characters, at most {max_length} are allowed"
+            )));
+        }
+        if normalized.len() > MAX_NAME_BYTES {
+            return Err(invalid(format!(
+                "name has {} bytes, at most {MAX_NAME_BYTES} are allowed",
+                normalized.len()
+            )));
+        }
+        Ok(normalized)
+    }
+
+    /// Returns the `tag` with a normalized name or an error if the name breaks any of the rules.
+    pub fn normalize_tag(&self, mut tag: Tag) -> Result<Tag> {
+        tag.name = self.normalize(&tag.name)?;
+        Ok(tag)
+    }
+}
+
+/// Returns the form of `name` shared by names that are easy to confuse with each other. It ignores
+/// case, Unicode normalization, surrounding whitespace, separators like `-`, `_`, `.` or spaces and
+/// characters that look alike such as Latin `a` and Cyrillic `а`.
+pub fn confusable_key(name: &str) -> String {
+    let folded: String = name.trim().nfkc().collect::<String>().to_lowercase();
+    let stripped: String = folded
+        .chars()
+        .filter(|c| !(c.is_whitespace() || matches!(c, '-' | '_' | '.')))
+        .collect();
+    unicode_security::skeleton(&stripped)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Groups the `names` that are easy to confuse with each other, see
+/// [confusable_key](confusable_key). Only groups with at least two names are returned.
+pub fn near_duplicates<I, S>(names: I) -> Vec<Vec<String>>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let mut groups: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for name in names {
+        let name = name.into();
+        groups
+            .entry(confusable_key(&name))
+            .or_default()
+            .insert(name);
+    }
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|group| group.into_iter().collect())
+        .collect()
+}
+
 pub fn get_tag<P, T>(path: P, tag: T) -> Result<Tag>
 where
     P: AsRef<Path>,
@@ -180,7 +347,7 @@ where
         let mut tags = Vec::new();
         let it = attrs
             .into_iter()
-            .filter(|xattr| xattr.key().starts_with(WUTAG_NAMESPACE))
+            .filter(|xattr| xattr.is_wutag())
             .map(Tag::try_from);
 
         for tag in it.flatten() {
@@ -199,7 +366,7 @@ where
         let mut tags = BTreeSet::new();
         let it = attrs
             .into_iter()
-            .filter(|xattr| xattr.key().starts_with(WUTAG_NAMESPACE))
+            .filter(|xattr| xattr.is_wutag())
             .map(Tag::try_from);
 
         for tag in it.flatten() {
@@ -216,7 +383,7 @@ where
 {
     for xattr in list_xattrs(path.as_ref())?
         .iter()
-        .filter(|xattr| xattr.key().starts_with(WUTAG_NAMESPACE))
+        .filter(|xattr| xattr.is_wutag())
     {
         remove_xattr(path.as_ref(), xattr.key())?;
     }
@@ -233,3 +400,63 @@ where
 {
     list_tags(path).map(|tags| !tags.is_empty())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_names() {
+        let rules = NameRules::default();
+        assert_eq!(rules.normalize("  foo ").unwrap(), "foo");
+        // `e` followed by a combining acute accent becomes a single `é`
+        assert_eq!(rules.normalize("cafe\u{301}").unwrap(), "caf\u{e9}");
+        assert_eq!(rules.normalize("Foo").unwrap(), "Foo");
+        assert!(rules.normalize("   ").is_err());
+        assert!(rules.normalize("a\tb").is_err());
+        assert!(rules.normalize(&"x".repeat(MAX_NAME_BYTES)).is_ok());
+        assert!(rules.normalize(&"x".repeat(MAX_NAME_BYTES + 1)).is_err());
+        assert!(NameRules {
+            max_length: Some(64),
+            ..Default::default()
+        }
+        .normalize(&"x".repeat(65))
+        .is_err());
+        assert!(NameRules {
+            max_length: Some(100),
+            ..Default::default()
+        }
+        .normalize(&"ż".repeat(100))
+        .is_err());
+
+        let rules = NameRules {
+            case_fold: true,
+            forbidden: vec!['/', ','],
+            max_length: Some(8),
+        };
+        assert_eq!(rules.normalize("Foo").unwrap(), "foo");
+        assert!(matches!(
+            rules.normalize("a/b"),
+            Err(Error::InvalidTagName(name, _)) if name == "a/b"
+        ));
+        let tag = rules.normalize_tag(Tag::new(" ToDo", Color::Red)).unwrap();
+        assert_eq!(tag.name(), "todo");
+        assert_eq!(tag.color(), &Color::Red);
+    }
+
+    #[test]
+    fn finds_near_duplicates() {
+        let names = [
+            "docs", "Docs", "docs ", "dосs", // Cyrillic `о` and `с`
+            "to-do", "to_do", "todo", "photos", "music",
+        ];
+        assert_eq!(
+            near_duplicates(names),
+            vec![
+                vec!["Docs", "docs", "docs ", "dосs"],
+                vec!["to-do", "to_do", "todo"]
+            ]
+        );
+        assert!(near_duplicates(["a", "b"]).is_empty());
+    }
+}
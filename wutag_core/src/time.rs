@@ -0,0 +1,108 @@
+//! Functions for parsing points in time and durations passed by the user.
+use crate::{Error, Result};
+
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+
+/// Parses a duration like `30s`, `15m`, `12h`, `7d` or `2w`.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let invalid = || Error::InvalidTime(s.to_string());
+    let unit_start = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (value, unit) = s.split_at(unit_start);
+    let value: i64 = value.parse().map_err(|_| invalid())?;
+
+    let unit_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    // constructors like `Duration::days` panic on values out of range
+    value
+        .checked_mul(unit_seconds * 1000)
+        .map(Duration::milliseconds)
+        .ok_or_else(invalid)
+}
+
+/// Parses a point in time given either as a RFC 3339 timestamp like `2021-03-14T12:00:00Z`, a date
+/// like `2021-03-14` or a duration like `7d` meaning the point in time that long ago.
+pub fn parse_time(s: &str) -> Result<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Some(midnight) = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+    {
+        return Ok(Utc.from_utc_datetime(&midnight));
+    }
+    parse_duration(s).and_then(|duration| {
+        Utc::now()
+            .checked_sub_signed(duration)
+            .ok_or_else(|| Error::InvalidTime(s.to_string()))
+    })
+}
+
+/// Parses an expiry given either as a duration like `7d` meaning that long from now or as a point
+/// in time accepted by [parse_time](parse_time).
+pub fn parse_expiry(s: &str) -> Result<DateTime<Utc>> {
+    match parse_duration(s) {
+        Ok(duration) => Utc::now()
+            .checked_add_signed(duration)
+            .ok_or_else(|| Error::InvalidTime(s.to_string())),
+        Err(_) => parse_time(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::seconds(30));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::minutes(15));
+        assert_eq!(parse_duration("12h").unwrap(), Duration::hours(12));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::days(7));
+        assert_eq!(parse_duration("2w").unwrap(), Duration::weeks(2));
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("7y").is_err());
+    }
+
+    #[test]
+    fn parses_times() {
+        let noon = parse_time("2021-03-14T12:00:00Z").unwrap();
+        assert_eq!(noon.to_rfc3339(), "2021-03-14T12:00:00+00:00");
+        assert_eq!(
+            parse_time("2021-03-14").unwrap(),
+            noon - Duration::hours(12)
+        );
+        let week_ago = parse_time("1w").unwrap();
+        assert!(week_ago < Utc::now() - Duration::days(6));
+        assert!(parse_time("yesterday").is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_durations() {
+        assert!(parse_duration("100000000000000000w").is_err());
+        assert!(parse_duration("99999999999999999999s").is_err());
+        assert!(parse_duration("100000000d").is_ok());
+        assert!(parse_time("100000000d").is_err());
+    }
+
+    #[test]
+    fn parses_expiries() {
+        let in_week = parse_expiry("7d").unwrap();
+        assert!(in_week > Utc::now() + Duration::days(6));
+        assert_eq!(
+            parse_expiry("2021-03-14").unwrap(),
+            parse_time("2021-03-14").unwrap()
+        );
+        assert!(parse_expiry("soon").is_err());
+        assert!(parse_expiry("100000000d").is_err());
+    }
+}
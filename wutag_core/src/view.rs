@@ -0,0 +1,457 @@
+//! Views of the registry materialized on disk as directories of symlinks so that tagged files can
+//! be browsed with any file manager.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+/// Name of the file in the root of a view listing the symlinks and directories created by wutag.
+pub const VIEW_MANIFEST: &str = ".wutag-view";
+
+#[derive(Debug, Error)]
+pub enum ViewError {
+    #[error("path of a view must be absolute, got `{0}`")]
+    RelativePath(PathBuf),
+    #[error("`{0}` is not a directory")]
+    NotADirectory(PathBuf),
+    #[error("failed to read manifest of view `{0}` - {1}")]
+    ReadManifest(PathBuf, io::Error),
+    #[error("failed to parse manifest of view `{0}` - {1}")]
+    ParseManifest(PathBuf, serde_json::Error),
+    #[error("failed to save manifest of view `{0}` - {1}")]
+    SaveManifest(PathBuf, io::Error),
+    #[error("failed to create directory `{0}` - {1}")]
+    CreateDir(PathBuf, io::Error),
+}
+
+type Result<T> = std::result::Result<T, ViewError>;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// Paths relative to the root of a view of everything wutag created in it. Only these paths are
+/// ever removed from a view.
+struct Manifest {
+    links: BTreeSet<PathBuf>,
+    dirs: BTreeSet<PathBuf>,
+}
+
+impl Manifest {
+    fn load(root: &Path) -> Result<Self> {
+        let path = root.join(VIEW_MANIFEST);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(ViewError::ReadManifest(root.into(), e)),
+        };
+        let mut manifest: Self =
+            serde_json::from_slice(&data).map_err(|e| ViewError::ParseManifest(root.into(), e))?;
+        // never follow an edited manifest outside of the view
+        manifest.links.retain(|link| is_contained(link));
+        manifest.dirs.retain(|dir| is_contained(dir));
+        Ok(manifest)
+    }
+
+    fn save(&self, root: &Path) -> Result<()> {
+        let path = root.join(VIEW_MANIFEST);
+        if self.links.is_empty() && self.dirs.is_empty() {
+            return match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    Err(ViewError::SaveManifest(root.into(), e))
+                }
+                _ => Ok(()),
+            };
+        }
+        let data = serde_json::to_vec_pretty(self).expect("serializable manifest");
+        fs::write(&path, data).map_err(|e| ViewError::SaveManifest(root.into(), e))
+    }
+}
+
+/// Checks whether the relative `path` stays inside of the directory it is relative to.
+fn is_contained(path: &Path) -> bool {
+    path.components().next().is_some()
+        && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Returns a name of a directory for the tag. Tags like `type/image` become nested directories and
+/// components that would escape the view or replace its manifest are replaced.
+fn tag_dir(tag: &str) -> PathBuf {
+    tag.split('/')
+        .enumerate()
+        .map(|(i, component)| match component {
+            "" | "." | ".." => "_".to_string(),
+            VIEW_MANIFEST if i == 0 => format!("_{VIEW_MANIFEST}"),
+            component => component.to_string(),
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// Changes made to a view on disk.
+pub struct ViewReport {
+    /// Created symlinks and their targets.
+    pub created: Vec<(PathBuf, PathBuf)>,
+    /// Removed symlinks.
+    pub removed: Vec<PathBuf>,
+    /// Symlinks that weren't created because something not created by wutag is in their place.
+    pub skipped: Vec<PathBuf>,
+    /// Errors of symlinks that failed to be created or removed.
+    pub errors: Vec<String>,
+}
+
+impl ViewReport {
+    /// Checks whether the view was left unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty()
+            && self.removed.is_empty()
+            && self.skipped.is_empty()
+            && self.errors.is_empty()
+    }
+}
+
+#[derive(Clone, Debug)]
+/// The symlinks a view should contain.
+pub struct View {
+    root: PathBuf,
+    /// Targets of the symlinks keyed by their paths relative to the root.
+    links: BTreeMap<PathBuf, PathBuf>,
+}
+
+impl View {
+    /// Creates an empty view in the directory at `root`. Materializing it removes everything
+    /// created in the directory by previous views.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Result<Self> {
+        let root = root.into();
+        if !root.is_absolute() {
+            return Err(ViewError::RelativePath(root));
+        }
+        Ok(Self {
+            root,
+            links: BTreeMap::new(),
+        })
+    }
+
+    /// Creates a view with a directory for each tag containing symlinks to the tagged paths.
+    pub fn by_tags<P, T, I>(root: P, tags: T) -> Result<Self>
+    where
+        P: Into<PathBuf>,
+        T: IntoIterator<Item = (String, I)>,
+        I: IntoIterator<Item = PathBuf>,
+    {
+        let mut view = Self::new(root)?;
+        for (tag, targets) in tags {
+            view.add_all(&tag_dir(&tag), targets);
+        }
+        Ok(view)
+    }
+
+    /// Creates a view with symlinks to the `targets` directly in the root.
+    pub fn flat<P: Into<PathBuf>>(
+        root: P,
+        targets: impl IntoIterator<Item = PathBuf>,
+    ) -> Result<Self> {
+        let mut view = Self::new(root)?;
+        view.add_all(Path::new(""), targets);
+        Ok(view)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Adds symlinks to the `targets` in the directory `dir` relative to the root.
+    fn add_all(&mut self, dir: &Path, targets: impl IntoIterator<Item = PathBuf>) {
+        let reserved = BTreeSet::from([OsString::from(VIEW_MANIFEST)]);
+        for (name, target) in link_names(targets, &reserved) {
+            self.links.insert(dir.join(name), target);
+        }
+    }
+
+    /// Updates the directory of the view on disk to contain exactly the symlinks of this view.
+    /// Only symlinks and directories created by previous updates are removed, anything else in
+    /// the way of a symlink is left alone and reported as skipped. With `dry_run` only reports
+    /// what would change.
+    pub fn materialize(&self, dry_run: bool) -> Result<ViewReport> {
+        if self.root.exists() && !self.root.is_dir() {
+            return Err(ViewError::NotADirectory(self.root.clone()));
+        }
+        let mut manifest = Manifest::load(&self.root)?;
+        let mut report = ViewReport::default();
+
+        for link in manifest.links.clone() {
+            let path = self.root.join(&link);
+            let is_symlink = fs::symlink_metadata(&path)
+                .map(|meta| meta.file_type().is_symlink())
+                .unwrap_or(false);
+            let current = fs::read_link(&path).ok();
+            if is_symlink && current.as_ref() == self.links.get(&link) {
+                continue;
+            }
+            manifest.links.remove(&link);
+            if !is_symlink {
+                // already removed or replaced by something that isn't ours
+                continue;
+            }
+            if !dry_run {
+                if let Err(e) = fs::remove_file(&path) {
+                    report.errors.push(link_error(&path, e));
+                    manifest.links.insert(link);
+                    continue;
+                }
+            }
+            report.removed.push(path);
+        }
+
+        for (link, target) in &self.links {
+            if manifest.links.contains(link) {
+                continue;
+            }
+            let path = self.root.join(link);
+            if fs::symlink_metadata(&path).is_ok() {
+                report.skipped.push(path);
+                continue;
+            }
+            if !dry_run {
+                if let Err(e) = self.create_dirs(&mut manifest, link) {
+                    report.errors.push(e.to_string());
+                    continue;
+                }
+                if let Err(e) = symlink(target, &path) {
+                    report.errors.push(link_error(&path, e));
+                    continue;
+                }
+            }
+            manifest.links.insert(link.clone());
+            report.created.push((path, target.clone()));
+        }
+
+        if !dry_run {
+            self.remove_empty_dirs(&mut manifest);
+            if self.root.exists() {
+                manifest.save(&self.root)?;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Creates missing parent directories of the `link` recording them in the `manifest`.
+    fn create_dirs(&self, manifest: &mut Manifest, link: &Path) -> Result<()> {
+        let mut missing = vec![];
+        for dir in link.ancestors().skip(1) {
+            if self.root.join(dir).exists() {
+                break;
+            }
+            missing.push(dir);
+        }
+        for dir in missing.into_iter().rev() {
+            let path = self.root.join(dir);
+            fs::create_dir_all(&path).map_err(|e| ViewError::CreateDir(path, e))?;
+            if dir != Path::new("") {
+                manifest.dirs.insert(dir.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes directories created by wutag that are left empty, deepest first.
+    fn remove_empty_dirs(&self, manifest: &mut Manifest) {
+        let mut dirs: Vec<_> = manifest.dirs.iter().cloned().collect();
+        dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+        for dir in dirs {
+            let path = self.root.join(&dir);
+            let empty = fs::read_dir(&path)
+                .map(|mut entries| entries.next().is_none())
+                .unwrap_or(false);
+            if !path.exists() || (empty && fs::remove_dir(&path).is_ok()) {
+                manifest.dirs.remove(&dir);
+            }
+        }
+    }
+
+    /// Removes everything created in the view at `root` by previous updates leaving anything else
+    /// in place. The root itself is removed if it is left empty.
+    pub fn clean<P: Into<PathBuf>>(root: P, dry_run: bool) -> Result<ViewReport> {
+        let view = Self::new(root)?;
+        let report = view.materialize(dry_run)?;
+        if !dry_run {
+            // directories that still contain files of the user are no longer tracked
+            match fs::remove_file(view.root.join(VIEW_MANIFEST)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(ViewError::SaveManifest(view.root, e))
+                }
+                _ => {}
+            }
+            let empty = fs::read_dir(&view.root)
+                .map(|mut entries| entries.next().is_none())
+                .unwrap_or(false);
+            if empty {
+                let _ = fs::remove_dir(&view.root);
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Returns names of links to the `targets` in a single directory. Links are named after the
+/// targets, targets with the same name or a name in `reserved` get a ` (2)`, ` (3)`... suffix in
+/// the order of their paths so that the names don't change when other targets are added.
+pub fn link_names(
+    targets: impl IntoIterator<Item = PathBuf>,
+    reserved: &BTreeSet<OsString>,
+) -> Vec<(OsString, PathBuf)> {
+    let targets: BTreeSet<_> = targets.into_iter().collect();
+    let mut used = BTreeSet::new();
+    let mut links = vec![];
+    for target in targets {
+        let name = match target.file_name() {
+            Some(name) => name.to_os_string(),
+            None => continue,
+        };
+        let mut link = name.clone();
+        let mut n = 1;
+        while reserved.contains(&link) || !used.insert(link.clone()) {
+            n += 1;
+            link = numbered(&name, n);
+        }
+        links.push((link, target));
+    }
+    links
+}
+
+fn numbered(name: &std::ffi::OsStr, n: usize) -> OsString {
+    let path = Path::new(name);
+    let mut numbered = path.file_stem().unwrap_or(name).to_os_string();
+    numbered.push(format!(" ({n})"));
+    if let Some(extension) = path.extension() {
+        numbered.push(".");
+        numbered.push(extension);
+    }
+    numbered
+}
+
+fn link_error(path: &Path, e: io::Error) -> String {
+    format!("failed to update symlink `{}` - {e}", path.display())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn symlink(_: &Path, _: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "symlinks are only supported on unix",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_links() {
+        assert_eq!(tag_dir("type/image"), Path::new("type/image"));
+        assert_eq!(tag_dir("../x"), Path::new("_/x"));
+        assert_eq!(tag_dir(VIEW_MANIFEST), Path::new("_.wutag-view"));
+        assert_eq!(tag_dir("a/.wutag-view"), Path::new("a/.wutag-view"));
+        assert_eq!(numbered("a.tar.gz".as_ref(), 2), "a.tar (2).gz");
+        assert_eq!(numbered("README".as_ref(), 3), "README (3)");
+        assert!(!is_contained(Path::new("../etc")));
+        assert!(!is_contained(Path::new("/etc")));
+        assert!(is_contained(Path::new("a/b")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn materializes_and_cleans_views() {
+        let dir = tempdir::TempDir::new("wutag-view").unwrap();
+        let files = dir.path().join("files");
+        let root = dir.path().join("view");
+        fs::create_dir_all(files.join("other")).unwrap();
+        for file in ["a.pdf", "b.txt", "other/a.pdf"] {
+            fs::write(files.join(file), "").unwrap();
+        }
+
+        let view = View::by_tags(
+            &root,
+            vec![
+                (
+                    "invoice".to_string(),
+                    vec![files.join("other/a.pdf"), files.join("a.pdf")],
+                ),
+                ("type/text".to_string(), vec![files.join("b.txt")]),
+            ],
+        )
+        .unwrap();
+        assert_eq!(view.materialize(true).unwrap().created.len(), 3);
+        assert!(!root.exists());
+
+        let report = view.materialize(false).unwrap();
+        assert_eq!(report.created.len(), 3);
+        assert_eq!(
+            fs::read_link(root.join("invoice/a.pdf")).unwrap(),
+            files.join("a.pdf")
+        );
+        assert_eq!(
+            fs::read_link(root.join("invoice/a (2).pdf")).unwrap(),
+            files.join("other/a.pdf")
+        );
+        assert!(root.join("type/text/b.txt").exists());
+        assert!(view.materialize(false).unwrap().is_empty());
+
+        // files of the user in the view are never touched
+        fs::write(root.join("invoice/notes.txt"), "mine").unwrap();
+        fs::write(root.join("type/text/mine"), "").unwrap();
+        std::os::unix::fs::symlink(files.join("b.txt"), root.join("invoice/b.txt")).unwrap();
+        let view = View::by_tags(
+            &root,
+            vec![(
+                "invoice".to_string(),
+                vec![files.join("a.pdf"), files.join("b.txt")],
+            )],
+        )
+        .unwrap();
+        let report = view.materialize(false).unwrap();
+        assert_eq!(report.removed.len(), 2);
+        assert_eq!(report.skipped, vec![root.join("invoice/b.txt")]);
+        assert!(root.join("type/text/mine").exists());
+        assert!(!root.join("invoice/a (2).pdf").exists());
+
+        let report = View::clean(&root, false).unwrap();
+        assert_eq!(report.removed, vec![root.join("invoice/a.pdf")]);
+        assert!(root.join("invoice/notes.txt").exists());
+        assert!(root.join("invoice/b.txt").exists());
+        assert!(root.join("type/text/mine").exists());
+        assert!(!root.join(VIEW_MANIFEST).exists());
+
+        // a file in place of a tag directory fails only the links inside of it
+        fs::remove_dir_all(&root).unwrap();
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("invoice"), "mine").unwrap();
+        let view = View::by_tags(
+            &root,
+            vec![
+                ("invoice".to_string(), vec![files.join("a.pdf")]),
+                ("text".to_string(), vec![files.join("b.txt")]),
+            ],
+        )
+        .unwrap();
+        let report = view.materialize(false).unwrap();
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.created.len(), 1);
+        assert!(fs::read_to_string(root.join(VIEW_MANIFEST))
+            .unwrap()
+            .contains("b.txt"));
+        View::clean(&root, false).unwrap();
+
+        fs::remove_dir_all(&root).unwrap();
+        let view = View::flat(&root, vec![files.join("a.pdf")]).unwrap();
+        view.materialize(false).unwrap();
+        assert!(root.join("a.pdf").exists());
+        View::clean(&root, false).unwrap();
+        assert!(!root.exists());
+    }
+}
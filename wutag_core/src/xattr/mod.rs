@@ -15,19 +15,22 @@ pub use windows::{
     set_xattr as _set_xattr,
 };
 
-use crate::Result;
+use crate::{Result, WUTAG_NAMESPACE};
+use std::ffi::{OsStr, OsString};
 use std::path::Path;
 
+/// An extended attribute of a file. Names and values are kept as returned by the system so
+/// attributes that are not valid UTF-8 are preserved.
 pub struct Xattr {
-    key: String,
-    val: String,
+    key: OsString,
+    val: Vec<u8>,
 }
 
 impl Xattr {
     pub fn new<K, V>(key: K, val: V) -> Self
     where
-        K: Into<String>,
-        V: Into<String>,
+        K: Into<OsString>,
+        V: Into<Vec<u8>>,
     {
         Self {
             key: key.into(),
@@ -35,33 +38,41 @@ impl Xattr {
         }
     }
 
-    pub fn key(&self) -> &str {
+    pub fn key(&self) -> &OsStr {
         &self.key
     }
 
-    pub fn val(&self) -> &str {
+    pub fn val(&self) -> &[u8] {
         &self.val
     }
+
+    /// Checks whether this attribute is in the namespace of attributes added by wutag.
+    pub fn is_wutag(&self) -> bool {
+        self.key
+            .to_str()
+            .is_some_and(|key| key.starts_with(WUTAG_NAMESPACE))
+    }
 }
 
-impl From<(String, String)> for Xattr {
-    fn from(xattr: (String, String)) -> Self {
+impl From<(OsString, Vec<u8>)> for Xattr {
+    fn from(xattr: (OsString, Vec<u8>)) -> Self {
         Self::new(xattr.0, xattr.1)
     }
 }
 
-pub fn set_xattr<P, S>(path: P, name: S, value: S) -> Result<()>
+pub fn set_xattr<P, N, V>(path: P, name: N, value: V) -> Result<()>
 where
     P: AsRef<Path>,
-    S: AsRef<str>,
+    N: AsRef<OsStr>,
+    V: AsRef<[u8]>,
 {
     _set_xattr(path, name, value)
 }
 
-pub fn get_xattr<P, S>(path: P, name: S) -> Result<String>
+pub fn get_xattr<P, N>(path: P, name: N) -> Result<Vec<u8>>
 where
     P: AsRef<Path>,
-    S: AsRef<str>,
+    N: AsRef<OsStr>,
 {
     _get_xattr(path, name)
 }
@@ -73,10 +84,10 @@ where
     _list_xattrs(path).map(|attrs| attrs.into_iter().map(From::from).collect())
 }
 
-pub fn remove_xattr<P, S>(path: P, name: S) -> Result<()>
+pub fn remove_xattr<P, N>(path: P, name: N) -> Result<()>
 where
     P: AsRef<Path>,
-    S: AsRef<str>,
+    N: AsRef<OsStr>,
 {
     _remove_xattr(path, name)
 }
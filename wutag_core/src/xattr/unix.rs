@@ -4,10 +4,9 @@ use libc::XATTR_NOFOLLOW;
 use libc::{getxattr, listxattr, removexattr, setxattr, XATTR_CREATE};
 #[cfg(target_os = "linux")]
 use libc::{lgetxattr, llistxattr, lremovexattr, lsetxattr};
-use std::ffi::{CStr, CString, OsStr};
+use std::ffi::{CStr, CString, OsStr, OsString};
 use std::fs;
 use std::io;
-use std::mem;
 use std::os::raw::{c_char, c_void};
 use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
@@ -25,23 +24,22 @@ fn is_symlink(path: &Path) -> bool {
 
 /// Sets the value of the extended attribute identified by `name` and associated with the given `path` in the
 /// filesystem.
-pub fn set_xattr<P, S>(path: P, name: S, value: S) -> Result<()>
+pub fn set_xattr<P, N, V>(path: P, name: N, value: V) -> Result<()>
 where
     P: AsRef<Path>,
-    S: AsRef<str>,
+    N: AsRef<OsStr>,
+    V: AsRef<[u8]>,
 {
-    let size = value.as_ref().as_bytes().len();
     let path = path.as_ref();
-
-    _set_xattr(path, name.as_ref(), value.as_ref(), size, is_symlink(path))
+    _set_xattr(path, name.as_ref(), value.as_ref(), is_symlink(path))
 }
 
 /// Retrieves the value of the extended attribute identified by `name` and associated with the given
 /// `path` in the filesystem.
-pub fn get_xattr<P, S>(path: P, name: S) -> Result<String>
+pub fn get_xattr<P, N>(path: P, name: N) -> Result<Vec<u8>>
 where
     P: AsRef<Path>,
-    S: AsRef<str>,
+    N: AsRef<OsStr>,
 {
     let path = path.as_ref();
     _get_xattr(path, name.as_ref(), is_symlink(path))
@@ -49,7 +47,7 @@ where
 
 /// Retrieves a list of all extended attributes with their values associated with the given `path`
 /// in the filesystem.
-pub fn list_xattrs<P>(path: P) -> Result<Vec<(String, String)>>
+pub fn list_xattrs<P>(path: P) -> Result<Vec<(OsString, Vec<u8>)>>
 where
     P: AsRef<Path>,
 {
@@ -59,10 +57,10 @@ where
 
 /// Removes the extended attribute identified by `name` and associated with the given `path` in the
 /// filesystem.
-pub fn remove_xattr<P, S>(path: P, name: S) -> Result<()>
+pub fn remove_xattr<P, N>(path: P, name: N) -> Result<()>
 where
     P: AsRef<Path>,
-    S: AsRef<str>,
+    N: AsRef<OsStr>,
 {
     let path = path.as_ref();
     _remove_xattr(path, name.as_ref(), is_symlink(path))
@@ -156,9 +154,9 @@ unsafe fn __listxattr(path: *const i8, list: *mut i8, size: usize, symlink: bool
 // Impl
 //################################################################################
 
-fn _remove_xattr(path: &Path, name: &str, symlink: bool) -> Result<()> {
-    let path = CString::new(path.to_string_lossy().as_bytes())?;
-    let name = CString::new(name.as_bytes())?;
+fn _remove_xattr(path: &Path, name: &OsStr, symlink: bool) -> Result<()> {
+    let path = c_string(path.as_os_str())?;
+    let name = c_string(name)?;
 
     unsafe {
         let ret = __removexattr(path.as_ptr(), name.as_ptr(), symlink);
@@ -172,21 +170,19 @@ fn _remove_xattr(path: &Path, name: &str, symlink: bool) -> Result<()> {
 
 fn _set_xattr(
     path: &Path,
-    name: &str,
-    value: &str,
-    size: usize,
+    name: &OsStr,
+    value: &[u8],
     symlink: bool, // if provided path is a symlink set the attribute on the symlink not the file/directory it points to
 ) -> Result<()> {
-    let path = CString::new(path.to_string_lossy().as_bytes())?;
-    let name = CString::new(name.as_bytes())?;
-    let value = CString::new(value.as_bytes())?;
+    let path = c_string(path.as_os_str())?;
+    let name = c_string(name)?;
 
     unsafe {
         let ret = __setxattr(
             path.as_ptr(),
             name.as_ptr(),
             value.as_ptr() as *const c_void,
-            size,
+            value.len(),
             symlink,
         );
 
@@ -198,20 +194,17 @@ fn _set_xattr(
     Ok(())
 }
 
-fn _get_xattr(path: &Path, name: &str, symlink: bool) -> Result<String> {
-    let path = CString::new(path.to_string_lossy().as_bytes())?;
-    let name = CString::new(name.as_bytes())?;
+fn _get_xattr(path: &Path, name: &OsStr, symlink: bool) -> Result<Vec<u8>> {
+    let path = c_string(path.as_os_str())?;
+    let name = c_string(name)?;
     let size = get_xattr_size(path.as_c_str(), name.as_c_str(), symlink)?;
-    let mut buf = Vec::<u8>::with_capacity(size);
-    let buf_ptr = buf.as_mut_ptr();
-
-    mem::forget(buf);
+    let mut buf = vec![0u8; size];
 
     let ret = unsafe {
         __getxattr(
             path.as_ptr(),
             name.as_ptr(),
-            buf_ptr as *mut c_void,
+            buf.as_mut_ptr() as *mut c_void,
             size,
             symlink,
         )
@@ -221,28 +214,23 @@ fn _get_xattr(path: &Path, name: &str, symlink: bool) -> Result<String> {
         return Err(Error::from(io::Error::last_os_error()));
     }
 
-    let ret = ret as usize;
-
-    if ret != size {
+    if ret as usize != size {
         return Err(Error::AttrsChanged);
     }
 
-    let buf = unsafe { Vec::from_raw_parts(buf_ptr, ret, size) };
-
-    Ok(unsafe { CString::from_vec_unchecked(buf) }
-        .to_string_lossy()
-        .to_string())
+    Ok(buf)
 }
 
-fn _list_xattrs(path: &Path, symlink: bool) -> Result<Vec<(String, String)>> {
-    let cpath = CString::new(path.to_string_lossy().as_bytes())?;
+fn _list_xattrs(path: &Path, symlink: bool) -> Result<Vec<(OsString, Vec<u8>)>> {
+    let cpath = c_string(path.as_os_str())?;
     let raw = list_xattrs_raw(cpath.as_c_str(), symlink)?;
     let keys = parse_xattrs(&raw);
 
     let mut attrs = Vec::new();
 
     for key in keys {
-        attrs.push((key.clone(), _get_xattr(path, key.as_str(), symlink)?));
+        let value = _get_xattr(path, &key, symlink)?;
+        attrs.push((key, value));
     }
 
     Ok(attrs)
@@ -274,44 +262,41 @@ fn get_xattrs_list_size(path: &CStr, symlink: bool) -> Result<usize> {
 
 fn list_xattrs_raw(path: &CStr, symlink: bool) -> Result<Vec<u8>> {
     let size = get_xattrs_list_size(path, symlink)?;
-    let mut buf = Vec::<u8>::with_capacity(size);
-    let buf_ptr = buf.as_mut_ptr();
+    let mut buf = vec![0u8; size];
 
-    mem::forget(buf);
-
-    let ret = unsafe { __listxattr(path.as_ptr(), buf_ptr as *mut c_char, size, symlink) };
+    let ret = unsafe {
+        __listxattr(
+            path.as_ptr(),
+            buf.as_mut_ptr() as *mut c_char,
+            size,
+            symlink,
+        )
+    };
 
     if ret == -1 {
         return Err(Error::from(io::Error::last_os_error()));
     }
 
-    let ret = ret as usize;
-
-    if ret != size {
+    if ret as usize != size {
         return Err(Error::AttrsChanged);
     }
 
-    // its safe to construct a Vec here because original pointer to buf is forgotten
-    // and the size of return buffer is verified against original size
-    unsafe { Ok(Vec::from_raw_parts(buf_ptr, ret, size)) }
+    Ok(buf)
 }
 
-fn parse_xattrs(input: &[u8]) -> Vec<String> {
-    let mut keys = Vec::new();
-    let mut start = 0;
-
-    for (i, ch) in input.iter().enumerate() {
-        if *ch == b'\0' {
-            keys.push(
-                OsStr::from_bytes(&input[start..i])
-                    .to_string_lossy()
-                    .to_string(),
-            );
-            start += i - start + 1;
-        }
-    }
+/// Splits the NUL separated list of attribute names returned by `listxattr`.
+fn parse_xattrs(input: &[u8]) -> Vec<OsString> {
+    input
+        .split(|ch| *ch == b'\0')
+        .filter(|key| !key.is_empty())
+        .map(|key| OsStr::from_bytes(key).to_os_string())
+        .collect()
+}
 
-    keys
+/// Converts a path or a name to a C string without any lossy conversion so that names that are
+/// not valid UTF-8 are passed to the system unchanged.
+fn c_string(s: &OsStr) -> Result<CString> {
+    CString::new(s.as_bytes()).map_err(Error::from)
 }
 
 #[test]
@@ -325,8 +310,35 @@ fn parses_xattrs_from_raw() {
     let attrs = parse_xattrs(raw);
     let mut it = attrs.iter();
 
-    assert_eq!(it.next(), Some(&"user.key1".to_string()));
-    assert_eq!(it.next(), Some(&"user.key2".to_string()));
-    assert_eq!(it.next(), Some(&"user.key3".to_string()));
-    assert_eq!(it.next(), Some(&"security.testing".to_string()));
+    assert_eq!(it.next().unwrap(), "user.key1");
+    assert_eq!(it.next().unwrap(), "user.key2");
+    assert_eq!(it.next().unwrap(), "user.key3");
+    assert_eq!(it.next().unwrap(), "security.testing");
+    assert!(it.next().is_none());
+}
+
+// APFS on macOS rejects names that aren't valid UTF-8
+#[cfg(target_os = "linux")]
+#[test]
+fn handles_non_utf8_names_and_binary_values() {
+    let dir = tempdir::TempDir::new("wutag_xattr").unwrap();
+    let path = dir.path().join(OsStr::from_bytes(b"caf\xe9.txt"));
+    // the name `caf\xe9.txt` would become after a lossy conversion
+    let lossy = dir.path().join("caf\u{fffd}.txt");
+    fs::write(&path, b"").unwrap();
+    fs::write(&lossy, b"").unwrap();
+
+    let name = OsStr::from_bytes(b"user.wutag.\xff");
+    let value = b"\x00\xffbinary\x00value";
+    set_xattr(&path, name, value).unwrap();
+
+    assert_eq!(get_xattr(&path, name).unwrap(), value);
+    assert_eq!(
+        list_xattrs(&path).unwrap(),
+        vec![(name.to_os_string(), value.to_vec())]
+    );
+    assert!(list_xattrs(&lossy).unwrap().is_empty());
+
+    remove_xattr(&path, name).unwrap();
+    assert!(list_xattrs(&path).unwrap().is_empty());
 }
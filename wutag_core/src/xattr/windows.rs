@@ -1,36 +1,38 @@
 #![cfg(windows)]
 #![allow(unused_variables)]
+use std::ffi::{OsStr, OsString};
 use std::path::Path;
 
 use crate::Result;
 
-pub fn set_xattr<P, S>(path: P, name: S, value: S) -> Result<()>
+pub fn set_xattr<P, N, V>(path: P, name: N, value: V) -> Result<()>
 where
     P: AsRef<Path>,
-    S: AsRef<str>,
+    N: AsRef<OsStr>,
+    V: AsRef<[u8]>,
 {
     Ok(())
 }
 
-pub fn get_xattr<P, S>(path: P, name: S) -> Result<String>
+pub fn get_xattr<P, N>(path: P, name: N) -> Result<Vec<u8>>
 where
     P: AsRef<Path>,
-    S: AsRef<str>,
+    N: AsRef<OsStr>,
 {
-    Ok(String::new())
+    Ok(Vec::new())
 }
 
-pub fn list_xattrs<P>(path: P) -> Result<Vec<(String, String)>>
+pub fn list_xattrs<P>(path: P) -> Result<Vec<(OsString, Vec<u8>)>>
 where
     P: AsRef<Path>,
 {
     Ok(Vec::new())
 }
 
-pub fn remove_xattr<P, S>(path: P, name: S) -> Result<()>
+pub fn remove_xattr<P, N>(path: P, name: N) -> Result<()>
 where
     P: AsRef<Path>,
-    S: AsRef<str>,
+    N: AsRef<OsStr>,
 {
     Ok(())
 }
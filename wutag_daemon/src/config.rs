@@ -0,0 +1,95 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use thiserror::Error as ThisError;
+use wutag_core::rules::{RuleError, WatchFolder};
+use wutag_core::tag::NameRules;
+
+#[derive(Debug, ThisError)]
+pub enum ConfigError {
+    #[error("failed to load configuration - {0}")]
+    Load(io::Error),
+    #[error("failed to deserialize configuration - {0}")]
+    Deserialize(serde_yaml::Error),
+    #[error("invalid rule for folder `{0}` - {1}")]
+    InvalidRule(PathBuf, RuleError),
+}
+
+const CONFIG_FILE: &str = "wutagd.yml";
+
+/// Configuration of the daemon loaded once at startup.
+pub static CONFIG: Lazy<DaemonConfig> = Lazy::new(|| match DaemonConfig::load_default_location() {
+    Ok(config) => config,
+    Err(ConfigError::Load(e)) if e.kind() == io::ErrorKind::NotFound => DaemonConfig::default(),
+    Err(e) => {
+        log::error!("{e}");
+        DaemonConfig::default()
+    }
+});
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct DaemonConfig {
+    #[serde(default)]
+    /// Folders in which new files are automatically tagged.
+    pub watch: Vec<WatchFolder>,
+    #[serde(default)]
+    /// Rules normalizing and validating names of new tags.
+    pub tag_names: NameRules,
+    #[serde(default)]
+    /// Views of tags materialized on disk that are kept up to date with the registry.
+    pub views: Vec<ViewConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+/// A directory of symlinks to tagged files updated whenever tags change.
+pub struct ViewConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    /// Query written like the arguments of `search`. Without a query the view contains a
+    /// directory for each tag.
+    pub query: Option<String>,
+}
+
+/// Expands a `path` starting with `~` to the home directory of the user.
+fn expand_home(path: &mut PathBuf) {
+    if let Ok(rest) = path.strip_prefix("~") {
+        if let Some(home) = dirs::home_dir() {
+            *path = home.join(rest);
+        }
+    }
+}
+
+impl DaemonConfig {
+    /// Loads the configuration from the file at `path`. Paths of watched folders and views starting
+    /// with `~` are expanded to the home directory of the user.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let mut config: Self = serde_yaml::from_slice(&fs::read(path).map_err(ConfigError::Load)?)
+            .map_err(ConfigError::Deserialize)?;
+
+        for folder in &mut config.watch {
+            expand_home(&mut folder.path);
+            for rule in &folder.rules {
+                rule.validate()
+                    .map_err(|e| ConfigError::InvalidRule(folder.path.clone(), e))?;
+            }
+        }
+
+        for view in &mut config.views {
+            expand_home(&mut view.path);
+        }
+
+        Ok(config)
+    }
+
+    /// Loads [CONFIG_FILE](CONFIG_FILE) from the config directory of the user running the daemon.
+    pub fn load_default_location() -> Result<Self, ConfigError> {
+        let dir = dirs::config_dir().ok_or_else(|| {
+            ConfigError::Load(io::Error::new(
+                io::ErrorKind::NotFound,
+                "failed to determine user config directory",
+            ))
+        })?;
+        Self::load(dir.join(CONFIG_FILE))
+    }
+}
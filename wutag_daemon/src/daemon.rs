@@ -1,11 +1,27 @@
+use crate::config::CONFIG;
+use crate::plan;
 use crate::registry::{get_registry_read, get_registry_write};
-use crate::{EntryEvent, Result, ENTRIES_EVENTS};
+use crate::views::build_view;
+use crate::{audit_log, data_path, EntryEvent, Result, ENTRIES_EVENTS};
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::PathBuf;
 use thiserror::Error as ThisError;
-use wutag_core::color::{Color, DEFAULT_COLORS};
-use wutag_core::registry::EntryData;
-use wutag_core::tag::{clear_tags, list_tags, Tag};
-use wutag_ipc::{IpcError, IpcServer, PayloadResult, Request, Response};
+use wutag_core::audit::{AuditAction, AuditEntry, AuditFilter, AuditLog};
+use wutag_core::journal::{Journal, JournalEntry};
+use wutag_core::query::Query;
+use wutag_core::registry::{
+    canonical_path, ChangeKind, EntryData, RegistryExport, SearchMatch, TagChange, TagRegistry,
+};
+use wutag_core::rules::match_rules;
+use wutag_core::sort::{Page, Sort};
+use wutag_core::tag::{clear_tags, near_duplicates, Tag};
+use wutag_core::view::View;
+use wutag_ipc::{
+    AutoTagOptions, AutoTagReport, DoctorReport, ImportReport, IpcError, IpcServer, PayloadResult,
+    Request, Response, SearchOptions, TagEdit,
+};
 
 #[derive(Debug, ThisError)]
 pub enum DaemonError {
@@ -15,16 +31,398 @@ pub enum DaemonError {
     SendResponse(IpcError),
 }
 
+/// Applies the `changes` to the files and then to the registry. The registry is only locked to
+/// look up the entries and to record the changes, not while attributes of the files are written.
+/// Returns the changes that were applied successfully, errors of those that failed and events
+/// describing entries that were added or removed.
+pub fn apply_changes(changes: Vec<TagChange>) -> (Vec<TagChange>, Vec<String>, Vec<EntryEvent>) {
+    let mut known: BTreeSet<PathBuf> = {
+        let registry = get_registry_read();
+        changes
+            .iter()
+            .filter(|change| registry.find_entry(&change.path).is_some())
+            .map(|change| change.path.clone())
+            .collect()
+    };
+    let mut applied = vec![];
+    let mut errors = vec![];
+
+    for change in changes {
+        let path = change.path.as_path();
+        match change.kind {
+            ChangeKind::Add => {
+                log::trace!("tagging file {}, tag {}", path.display(), change.tag);
+                if !known.contains(path) {
+                    if let Err(e) = clear_tags(path) {
+                        log::error!(
+                            "failed to clear tags of file `{}`, reason: {e}",
+                            path.display()
+                        );
+                    }
+                }
+                if let Err(e) = change
+                    .tag
+                    .save_to_with_expiry(path, change.expires.as_ref())
+                {
+                    errors.push(format!(
+                        "Error for `{}` tag: `{}`, reason: {e}",
+                        path.display(),
+                        change.tag
+                    ));
+                    continue;
+                }
+                known.insert(path.to_path_buf());
+            }
+            ChangeKind::Remove => {
+                if !known.contains(path) {
+                    continue;
+                }
+                log::trace!("untagging file {}, tag {}", path.display(), change.tag);
+                match change.tag.remove_from(path) {
+                    // the registry is out of sync with the file so just drop the tag
+                    Err(wutag_core::Error::TagNotFound(_)) | Ok(_) => {}
+                    Err(_) if !path.exists() => {}
+                    Err(e) => {
+                        errors.push(format!(
+                            "{} tag: {}, error: {e}",
+                            path.display(),
+                            change.tag
+                        ));
+                        continue;
+                    }
+                }
+            }
+        }
+        applied.push(change);
+    }
+
+    let events = record_changes(&mut get_registry_write(), &applied);
+    (applied, errors, events)
+}
+
+/// Records the `changes` already applied to the files in the `registry` and saves it. Returns
+/// events describing entries that were added or removed.
+pub fn record_changes(registry: &mut TagRegistry, changes: &[TagChange]) -> Vec<EntryEvent> {
+    let mut new_entries = vec![];
+    let mut removed = vec![];
+
+    for change in changes {
+        let path = change.path.as_path();
+        match change.kind {
+            ChangeKind::Add => {
+                let (id, added) = registry.add_or_update_entry(EntryData::new(path));
+                if added {
+                    new_entries.push(path.to_path_buf());
+                }
+                registry.tag_entry(&change.tag, id);
+                registry.set_tag_expiry(change.tag.name(), id, change.expires);
+            }
+            ChangeKind::Remove => {
+                let id = match registry.find_entry(path) {
+                    Some(id) => id,
+                    None => continue,
+                };
+                if let Some(entry) = registry.untag_entry(&change.tag, id) {
+                    removed.push(entry.into_path_buf());
+                }
+            }
+        }
+    }
+
+    if let Err(e) = registry.save() {
+        log::error!("{e}")
+    }
+
+    let mut events = vec![];
+    if !new_entries.is_empty() {
+        events.push(EntryEvent::Add(new_entries));
+    }
+    if !removed.is_empty() {
+        events.push(EntryEvent::Remove(removed));
+    }
+    events
+}
+
+/// Paths found below tagged directories by the directory. These paths inherit the tags of the
+/// directory when searching with inheritance.
+pub type Descendants = BTreeMap<PathBuf, Vec<PathBuf>>;
+
+/// How deep below a tagged directory paths are looked for when searching with inheritance.
+const INHERIT_MAX_DEPTH: usize = 8;
+
+/// Collects paths of the entries whose tags may be inherited by entries found by a search for the
+/// `tags` with the `options`, including the searches of the saved searches among the `tags`.
+/// Invalid saved searches are skipped as the search itself reports them.
+fn inheriting_entries(
+    registry: &TagRegistry,
+    tags: &[String],
+    options: &SearchOptions,
+    visiting: &mut Vec<String>,
+    entries: &mut BTreeSet<PathBuf>,
+) {
+    for tag in tags {
+        let name = plan::normalize_name(&CONFIG.tag_names, tag);
+        match registry.saved_search(&name).map(Query::parse) {
+            Some(Ok(query)) if !visiting.contains(&name) => {
+                visiting.push(name);
+                let options = query_options(&query);
+                inheriting_entries(registry, &query.tags, &options, visiting, entries);
+                visiting.pop();
+            }
+            Some(_) => {}
+            None if options.inherit => {
+                let implying = registry.implying_tags(&name);
+                entries.extend(
+                    registry
+                        .list_entries_with_any_tags(implying)
+                        .into_iter()
+                        .filter_map(|id| registry.get_entry(id))
+                        .map(|entry| entry.path().to_path_buf()),
+                );
+            }
+            None => {}
+        }
+    }
+}
+
+/// Finds the paths below the tagged directories that may inherit tags in a search for the `tags`
+/// with the `options`. The registry is only locked to find the directories and not while they are
+/// walked, which can take long.
+pub fn search_descendants(tags: &[String], options: &SearchOptions) -> Descendants {
+    let mut entries = BTreeSet::new();
+    inheriting_entries(
+        &get_registry_read(),
+        tags,
+        options,
+        &mut vec![],
+        &mut entries,
+    );
+    entries
+        .into_iter()
+        .filter(|path| path.is_dir())
+        .map(|dir| {
+            let below = walkdir::WalkDir::new(&dir)
+                .min_depth(1)
+                .max_depth(INHERIT_MAX_DEPTH)
+                .into_iter()
+                .flatten()
+                .map(|entry| entry.into_path())
+                .collect();
+            (dir, below)
+        })
+        .collect()
+}
+
+/// Finds the paths below the tagged directories that may inherit tags in a search for the `query`
+/// written like the arguments of `search`, see [search_descendants](search_descendants).
+pub fn query_descendants(query: &str) -> Descendants {
+    match Query::parse(query) {
+        Ok(query) => search_descendants(&query.tags, &query_options(&query)),
+        Err(_) => Descendants::new(),
+    }
+}
+
+/// Returns paths of entries tagged with any of the `tags` or tags implying them together with the
+/// paths found below the tagged directories. These are the only paths that can have the tags when
+/// directory tags are inherited.
+fn inherited_candidates(
+    registry: &TagRegistry,
+    tags: &[String],
+    descendants: &Descendants,
+) -> BTreeSet<PathBuf> {
+    let mut candidates = BTreeSet::new();
+    let implying = tags.iter().flat_map(|tag| registry.implying_tags(tag));
+    for id in registry.list_entries_with_any_tags(implying) {
+        let path = match registry.get_entry(id) {
+            Some(entry) => entry.path(),
+            None => continue,
+        };
+        if let Some(below) = descendants.get(path) {
+            candidates.extend(below.iter().cloned());
+        }
+        candidates.insert(path.to_path_buf());
+    }
+    candidates
+}
+
+/// Returns options of a search for the tags of the `query`.
+fn query_options(query: &Query) -> SearchOptions {
+    SearchOptions {
+        any: query.any,
+        tagged_since: query.tagged_since,
+        tagged_before: query.tagged_before,
+        inherit: query.inherit,
+        filter: query.filter.clone(),
+        ..Default::default()
+    }
+}
+
+/// Converts paths received from clients to the form under which entries are stored in the
+/// registry, see [canonical_path](wutag_core::registry::canonical_path).
+fn canonical_paths(files: Vec<PathBuf>) -> Vec<PathBuf> {
+    files.into_iter().map(canonical_path).collect()
+}
+
+/// Finds entries matching a query written like the arguments of `search`.
+/// Paths below tagged directories are taken from the `descendants` found by
+/// [query_descendants](query_descendants).
+pub fn find_query_matches(
+    registry: &TagRegistry,
+    query: &str,
+    descendants: &Descendants,
+) -> std::result::Result<Vec<SearchMatch>, String> {
+    let query = Query::parse(query).map_err(|e| format!("invalid query - {e}"))?;
+    find_matches(
+        registry,
+        &query.tags,
+        &query_options(&query),
+        descendants,
+        &mut vec![],
+    )
+}
+
+/// Finds entries matching the `tags` and `options`. Names of saved searches among the `tags`
+/// match the entries found by their queries. `visiting` holds names of the saved searches that are
+/// being evaluated to detect saved searches referring to themselves.
+fn find_matches(
+    registry: &TagRegistry,
+    tags: &[String],
+    options: &SearchOptions,
+    descendants: &Descendants,
+    visiting: &mut Vec<String>,
+) -> std::result::Result<Vec<SearchMatch>, String> {
+    let matcher = options.filter.matcher().map_err(|e| e.to_string())?;
+    let mut names = vec![];
+    let mut saved = vec![];
+    for tag in tags {
+        let name = plan::normalize_name(&CONFIG.tag_names, tag);
+        let query = match registry.saved_search(&name) {
+            Some(query) => query,
+            None => {
+                names.push(name);
+                continue;
+            }
+        };
+        if visiting.contains(&name) {
+            return Err(format!("saved search `{name}` refers to itself"));
+        }
+        let query = Query::parse(query)
+            .map_err(|e| format!("invalid query of saved search `{name}` - {e}"))?;
+        visiting.push(name);
+        let options = query_options(&query);
+        let found = find_matches(registry, &query.tags, &options, descendants, visiting)?;
+        visiting.pop();
+        saved.push(
+            found
+                .into_iter()
+                .map(|found| (found.entry.path().to_path_buf(), found))
+                .collect::<BTreeMap<_, _>>(),
+        );
+    }
+
+    let mut groups = saved.into_iter();
+    let tagged = if names.is_empty() {
+        groups.next().unwrap_or_default()
+    } else {
+        find_tagged(registry, &names, options, descendants)
+            .into_iter()
+            .map(|found| (found.entry.path().to_path_buf(), found))
+            .collect()
+    };
+    let found = groups.fold(tagged, |mut found, group| {
+        if options.any {
+            for (path, m) in group {
+                found.entry(path).or_insert(m);
+            }
+        } else {
+            found.retain(|path, _| group.contains_key(path));
+        }
+        found
+    });
+    // tags are checked first as the filter may need to read metadata of the file
+    Ok(found
+        .into_values()
+        .filter(|found| matcher.matches(found.entry.path()))
+        .collect())
+}
+
+/// Finds entries tagged with the tags with `names` or tags implying them.
+fn find_tagged(
+    registry: &TagRegistry,
+    names: &[String],
+    options: &SearchOptions,
+    descendants: &Descendants,
+) -> Vec<SearchMatch> {
+    let (any, since, before) = (
+        options.any,
+        options.tagged_since.as_ref(),
+        options.tagged_before.as_ref(),
+    );
+    if options.inherit {
+        let index = registry.entry_index();
+        return inherited_candidates(registry, names, descendants)
+            .into_iter()
+            .filter_map(|path| registry.match_effective(path, &index, names, any, since, before))
+            .collect();
+    }
+
+    // an entry has a searched tag if it has the tag itself or any tag implying it
+    let expanded: Vec<_> = names
+        .iter()
+        .map(|tag| registry.implying_tags(tag))
+        .collect();
+    let mut found = vec![];
+    for id in registry.list_entries_with_any_tags(expanded.iter().flatten()) {
+        let entry = match registry.get_entry(id) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let entry_tags = registry.list_entry_tags(id).unwrap_or_default();
+        let has_tag = |names: &BTreeSet<String>| {
+            entry_tags.iter().any(|tag| {
+                names.contains(tag.name()) && entry.tagged_between(tag.name(), since, before)
+            })
+        };
+        let matches = if any {
+            expanded.iter().any(has_tag)
+        } else {
+            expanded.iter().all(has_tag)
+        };
+        if matches {
+            found.push(SearchMatch::from(entry.clone()));
+        }
+    }
+    found
+}
+
+/// Sorts the search results and returns the requested page of them. Inherited tags count as tags
+/// of the entry.
+fn sort_matches(found: Vec<SearchMatch>, sort: Sort, page: Page) -> Vec<SearchMatch> {
+    page.apply(sort.sort(
+        found,
+        |found| &found.entry,
+        |found| found.entry.tagged().len() + found.inherited.len(),
+    ))
+}
+
 pub struct WutagDaemon {
     listener: IpcServer,
     unprocessed_events: Vec<EntryEvent>,
+    journal: Journal,
+    audit: AuditLog,
+    /// Id of the operation the request being processed is a part of.
+    operation: Option<u64>,
 }
 
 impl WutagDaemon {
     pub fn new(listener: IpcServer) -> Result<Self> {
+        let journal_file = data_path("wutag.history");
         Ok(Self {
             listener,
             unprocessed_events: vec![],
+            journal: Journal::load(&journal_file).unwrap_or_else(|_| Journal::new(journal_file)),
+            audit: audit_log(),
+            operation: None,
         })
     }
 
@@ -77,301 +475,706 @@ impl WutagDaemon {
 
     fn process_request(&mut self, request: Request) -> Response {
         match request {
-            Request::TagFiles { files, tags } => self.tag_files(files, tags),
-            Request::TagFilesPattern { glob, tags } => match glob.glob_paths() {
-                Ok(files) => self.tag_files(files, tags),
+            Request::TagFiles {
+                files,
+                tags,
+                expires,
+                implied,
+                dry_run,
+            } => self.tag_files(files, tags, expires, implied, dry_run),
+            Request::TagFilesPattern {
+                glob,
+                tags,
+                expires,
+                implied,
+                dry_run,
+            } => match glob.glob_paths() {
+                Ok(files) => self.tag_files(files, tags, expires, implied, dry_run),
                 Err(e) => Response::TagFiles(PayloadResult::Error(vec![e.to_string()])),
             },
-            Request::UntagFiles { files, tags } => self.untag_files(files, tags),
-            Request::UntagFilesPattern { glob, tags } => match glob.glob_paths() {
-                Ok(files) => self.untag_files(files, tags),
+            Request::UntagFiles {
+                files,
+                tags,
+                dry_run,
+            } => self.untag_files(files, tags, dry_run),
+            Request::UntagFilesPattern {
+                glob,
+                tags,
+                dry_run,
+            } => match glob.glob_paths() {
+                Ok(files) => self.untag_files(files, tags, dry_run),
                 Err(e) => Response::UntagFiles(PayloadResult::Error(vec![e.to_string()])),
             },
-            Request::ListTags { with_files } => self.list_tags(with_files),
-            Request::ListFiles { with_tags } => self.list_files(with_tags),
+            Request::ListTags { with_files, page } => self.list_tags(with_files, page),
+            Request::ListFiles {
+                with_tags,
+                sort,
+                page,
+            } => self.list_files(with_tags, sort, page),
             Request::InspectFiles { files } => self.inspect_files(files),
             Request::InspectFilesPattern { glob } => match glob.glob_paths() {
                 Ok(files) => self.inspect_files(files),
                 Err(e) => Response::InspectFiles(PayloadResult::Error(e.to_string())),
             },
-            Request::ClearFiles { files } => self.clear_files(files),
-            Request::ClearFilesPattern { glob } => match glob.glob_paths() {
-                Ok(files) => self.clear_files(files),
+            Request::InspectEffective { files } => self.inspect_effective(files),
+            Request::InspectEffectivePattern { glob } => match glob.glob_paths() {
+                Ok(files) => self.inspect_effective(files),
+                Err(e) => Response::InspectEffective(PayloadResult::Error(e.to_string())),
+            },
+            Request::ClearFiles { files, dry_run } => self.clear_files(files, dry_run),
+            Request::ClearFilesPattern { glob, dry_run } => match glob.glob_paths() {
+                Ok(files) => self.clear_files(files, dry_run),
                 Err(e) => Response::ClearFiles(PayloadResult::Error(vec![e.to_string()])),
             },
-            Request::ClearTags { tags } => self.clear_tags(tags),
-            Request::Search { tags, any } => self.search(tags, any),
-            Request::CopyTags { source, target } => self.copy_tags(source, target),
-            Request::CopyTagsPattern { source, glob } => match glob.glob_paths() {
-                Ok(target) => self.copy_tags(source, target),
+            Request::ClearTags { tags, dry_run } => self.clear_tags(tags, dry_run),
+            Request::Search { tags, options } => self.search(tags, options),
+            Request::CopyTags {
+                source,
+                target,
+                dry_run,
+            } => self.copy_tags(source, target, dry_run),
+            Request::CopyTagsPattern {
+                source,
+                glob,
+                dry_run,
+            } => match glob.glob_paths() {
+                Ok(target) => self.copy_tags(source, target, dry_run),
                 Err(e) => Response::CopyTags(PayloadResult::Error(vec![e.to_string()])),
             },
             Request::Ping => self.ping(),
-            Request::EditTag { tag, color } => self.edit_tag(tag, color),
-            Request::ClearCache => self.clean_cache(),
+            Request::EditTag { tag, edit, dry_run } => self.edit_tag(tag, edit, dry_run),
+            Request::ClearCache { dry_run } => self.clean_cache(dry_run),
+            Request::Undo { count, dry_run } => self.undo(count, dry_run),
+            Request::Redo { count, dry_run } => self.redo(count, dry_run),
+            Request::History => self.history(),
+            Request::AuditLog { filter } => self.audit_log(filter),
+            Request::TestRules { path } => self.test_rules(path),
+            Request::AddImplication {
+                tag,
+                implies,
+                dry_run,
+            } => self.add_implication(tag, implies, dry_run),
+            Request::RemoveImplication {
+                tag,
+                implies,
+                dry_run,
+            } => self.remove_implication(tag, implies, dry_run),
+            Request::ListImplications => self.list_implications(),
+            Request::AddAlias {
+                alias,
+                tag,
+                dry_run,
+            } => self.add_alias(alias, tag, dry_run),
+            Request::RemoveAlias { alias, dry_run } => self.remove_alias(alias, dry_run),
+            Request::ListAliases => self.list_aliases(),
+            Request::AddSavedSearch {
+                name,
+                query,
+                dry_run,
+            } => self.add_saved_search(name, query, dry_run),
+            Request::RemoveSavedSearch { name, dry_run } => self.remove_saved_search(name, dry_run),
+            Request::ListSavedSearches => self.list_saved_searches(),
+            Request::Export => {
+                Response::Export(PayloadResult::Ok(Box::new(get_registry_read().export())))
+            }
+            Request::Import { data, dry_run } => self.import(data, dry_run),
+            Request::TagInfo { tag, top } => self.tag_info(tag, top),
+            Request::Doctor => self.doctor(),
+            Request::Suggest { path, limit } => self.suggest(path, limit),
+            Request::MaterializeView {
+                path,
+                query,
+                dry_run,
+            } => self.materialize_view(path, query, dry_run),
+            Request::CleanView { path, dry_run } => self.clean_view(path, dry_run),
+            Request::AutoTagFiles {
+                files,
+                options,
+                dry_run,
+            } => self.auto_tag(files, options, dry_run),
+            Request::AutoTagFilesPattern {
+                glob,
+                options,
+                dry_run,
+            } => match glob.glob_paths() {
+                Ok(files) => self.auto_tag(files, options, dry_run),
+                Err(e) => Response::AutoTag(PayloadResult::Error(vec![e.to_string()])),
+            },
+            Request::Part { operation, request } => {
+                self.operation = Some(operation);
+                let response = self.process_request(*request);
+                self.operation = None;
+                response
+            }
         }
     }
 
-    fn tag_files(&mut self, files: Vec<PathBuf>, tags: Vec<Tag>) -> Response {
+    /// Applies the `changes` to the files and the registry. Returns the changes that were
+    /// applied successfully and errors of those that failed.
+    fn apply_changes(&mut self, changes: Vec<TagChange>) -> (Vec<TagChange>, Vec<String>) {
+        let (applied, errors, events) = apply_changes(changes);
+        for event in events {
+            self.push_event(event);
+        }
+        (applied, errors)
+    }
+
+    /// Applies the `changes` made by the `request`, audits the applied ones and records them in
+    /// the journal so they can be undone. Returns the applied changes and errors of the rest.
+    fn apply_and_record(
+        &mut self,
+        request: &str,
+        changes: Vec<TagChange>,
+    ) -> (Vec<TagChange>, Vec<String>) {
+        let (applied, errors) = self.apply_changes(changes);
+        if !applied.is_empty() {
+            self.audit_changes(request, &applied);
+            self.journal
+                .record(JournalEntry::new(request, applied.clone()).with_operation(self.operation));
+            self.save_journal();
+        }
+        (applied, errors)
+    }
+
+    /// Applies the planned `changes` and records them in the journal as made by the `request`
+    /// unless this is a `dry_run` in which case the changes are only returned.
+    fn apply_or_plan(
+        &mut self,
+        request: &str,
+        changes: Vec<TagChange>,
+        dry_run: bool,
+    ) -> PayloadResult<Vec<TagChange>, Vec<String>> {
+        if dry_run {
+            return PayloadResult::Ok(changes);
+        }
+        let (applied, errors) = self.apply_and_record(request, changes);
+        if errors.is_empty() {
+            PayloadResult::Ok(applied)
+        } else {
+            PayloadResult::Error(errors)
+        }
+    }
+
+    fn tag_files(
+        &mut self,
+        files: Vec<PathBuf>,
+        tags: Vec<Tag>,
+        expires: Option<DateTime<Utc>>,
+        implied: bool,
+        dry_run: bool,
+    ) -> Response {
+        let files = canonical_paths(files);
         if files.is_empty() {
             return Response::TagFiles(PayloadResult::Error(vec!["no files to tag".into()]));
         }
         if tags.is_empty() {
             return Response::TagFiles(PayloadResult::Error(vec!["no tags provided".into()]));
         }
-        let mut errors = vec![];
-        let mut new_entries = vec![];
-        let mut registry = get_registry_write();
-
-        for file in &files {
-            log::trace!("processing file {}", file.display());
-            let entry = EntryData::new(file);
-            let (id, added) = registry.add_or_update_entry(entry);
-            if added {
-                if let Err(e) = clear_tags(file) {
-                    log::error!(
-                        "failed to clear tags of file `{}`, reason: {e}",
-                        file.display()
-                    );
-                }
-                new_entries.push(file.to_path_buf());
-            }
-            for tag in &tags {
-                log::trace!("tagging file {}, tag {tag}", file.display());
-                if let Err(e) = tag.save_to(file) {
-                    errors.push(format!(
-                        "Error for `{}` tag: `{tag}`, reason: {e}",
-                        file.display()
-                    ));
-                } else {
-                    registry.tag_entry(tag, id);
-                }
-            }
-            if registry.list_entry_tags(id).unwrap_or_default().is_empty() {
-                registry.remove_entry(id);
-            }
-        }
+        let tags = match plan::normalize_tags(&CONFIG.tag_names, tags) {
+            Ok(tags) => tags,
+            Err(errors) => return Response::TagFiles(PayloadResult::Error(errors)),
+        };
+        let registry = get_registry_read();
+        let tags = if implied {
+            plan::with_implied(&registry, &tags)
+        } else {
+            tags
+        };
+        let changes = plan::tag_files(&registry, &files, &tags, expires);
+        drop(registry);
+        Response::TagFiles(self.apply_or_plan("tag_files", changes, dry_run))
+    }
 
-        if let Err(e) = registry.save() {
-            log::error!("{e}")
+    fn auto_tag(
+        &mut self,
+        files: Vec<PathBuf>,
+        options: AutoTagOptions,
+        dry_run: bool,
+    ) -> Response {
+        let files = canonical_paths(files);
+        if files.is_empty() {
+            return Response::AutoTag(PayloadResult::Error(vec!["no files to tag".into()]));
         }
-
-        if !new_entries.is_empty() {
-            self.push_event(EntryEvent::Add(new_entries));
+        if options.is_empty() {
+            return Response::AutoTag(PayloadResult::Error(vec![
+                "no method of deriving tags selected".into(),
+            ]));
         }
-
-        if errors.is_empty() {
-            Response::TagFiles(PayloadResult::Ok(()))
-        } else {
-            Response::TagFiles(PayloadResult::Error(errors))
+        let (changes, mut errors) =
+            plan::auto_tag(&get_registry_read(), &CONFIG.tag_names, &files, &options);
+        if dry_run {
+            return Response::AutoTag(PayloadResult::Ok(AutoTagReport { changes, errors }));
         }
+        let (changes, failed) = self.apply_and_record("auto_tag", changes);
+        errors.extend(failed);
+        Response::AutoTag(PayloadResult::Ok(AutoTagReport { changes, errors }))
     }
 
-    fn untag_files(&mut self, files: Vec<PathBuf>, tags: Vec<Tag>) -> Response {
+    fn untag_files(&mut self, files: Vec<PathBuf>, tags: Vec<Tag>, dry_run: bool) -> Response {
+        let files = canonical_paths(files);
         if files.is_empty() {
             return Response::UntagFiles(PayloadResult::Error(vec!["no files to untag".into()]));
         }
         if tags.is_empty() {
             return Response::UntagFiles(PayloadResult::Error(vec!["no tags provided".into()]));
         }
-        let mut registry = get_registry_write();
-        let mut errors = vec![];
-        let mut removed = vec![];
-
-        for file in &files {
-            if let Some(id) = registry.find_entry(file) {
-                for tag in &tags {
-                    if let Err(e) = tag.remove_from(file) {
-                        errors.push(format!("{} tag: {tag}, error: {e}", file.display()));
-                    } else if let Some(entry) = registry.untag_entry(tag, id) {
-                        removed.push(entry.into_path_buf());
-                    }
-                }
+        let tags: Vec<_> = tags
+            .into_iter()
+            .map(|tag| {
+                Tag::new(
+                    plan::normalize_name(&CONFIG.tag_names, tag.name()),
+                    *tag.color(),
+                )
+            })
+            .collect();
+        let changes = plan::untag_files(&get_registry_read(), &files, &tags);
+        Response::UntagFiles(self.apply_or_plan("untag_files", changes, dry_run))
+    }
+
+    fn edit_tag(&mut self, tag: String, edit: TagEdit, dry_run: bool) -> Response {
+        if edit.is_empty() {
+            return Response::EditTag(PayloadResult::Error("nothing to edit".into()));
+        }
+        let name = match get_registry_read().get_tag(&tag) {
+            Some(found) => found.name().to_string(),
+            None => {
+                return Response::EditTag(PayloadResult::Error(format!("tag {tag} doesn't exist")))
             }
+        };
+        let details: Vec<_> = [
+            edit.color.as_ref().map(|color| format!("color: {color:?}")),
+            edit.description
+                .as_ref()
+                .map(|description| format!("description: {description}")),
+            edit.icon.as_ref().map(|icon| format!("icon: {icon}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let edits = vec![
+            AuditEntry::new("edit_tag", AuditAction::Edit, None, name.as_str())
+                .with_details(details.join(", ")),
+        ];
+        if dry_run {
+            return Response::EditTag(PayloadResult::Ok(edits));
         }
 
+        let mut registry = get_registry_write();
+        registry.update_tag(&name, |tag| {
+            if let Some(color) = &edit.color {
+                tag.set_color(color);
+            }
+            if let Some(description) = &edit.description {
+                tag.set_description(description.as_str());
+            }
+            if let Some(icon) = &edit.icon {
+                tag.set_icon(icon.as_str());
+            }
+        });
         if let Err(e) = registry.save() {
             log::error!("{e}")
         }
+        drop(registry);
+        self.audit(edits.clone());
+        Response::EditTag(PayloadResult::Ok(edits))
+    }
 
-        if !removed.is_empty() {
-            self.push_event(EntryEvent::Remove(removed));
+    fn doctor(&mut self) -> Response {
+        let registry = get_registry_read();
+        let names: Vec<_> = registry.list_tags().map(|tag| tag.name()).collect();
+        let invalid = names
+            .iter()
+            .filter_map(|name| match CONFIG.tag_names.normalize(name) {
+                Ok(normalized) if normalized == *name => None,
+                Ok(normalized) => Some((name.to_string(), format!("should be `{normalized}`"))),
+                Err(e) => Some((name.to_string(), e.to_string())),
+            })
+            .collect();
+        let mut duplicates = near_duplicates(names);
+        for group in &mut duplicates {
+            group.sort_by_cached_key(|name| {
+                Reverse(registry.list_entries_with_any_tags([name.as_str()]).len())
+            });
         }
+        Response::Doctor(PayloadResult::Ok(DoctorReport {
+            near_duplicates: duplicates,
+            invalid,
+        }))
+    }
 
-        if errors.is_empty() {
-            Response::UntagFiles(PayloadResult::Ok(()))
-        } else {
-            Response::UntagFiles(PayloadResult::Error(errors))
+    fn suggest(&mut self, path: PathBuf, limit: usize) -> Response {
+        Response::Suggest(PayloadResult::Ok(
+            get_registry_read().suggest_tags(canonical_path(path), limit),
+        ))
+    }
+
+    fn materialize_view(
+        &mut self,
+        path: PathBuf,
+        query: Option<String>,
+        dry_run: bool,
+    ) -> Response {
+        let descendants = query.as_deref().map(query_descendants).unwrap_or_default();
+        let view = build_view(&get_registry_read(), path, query.as_deref(), &descendants);
+        Response::MaterializeView(
+            match view.and_then(|view| view.materialize(dry_run).map_err(|e| e.to_string())) {
+                Ok(report) => PayloadResult::Ok(Box::new(report)),
+                Err(e) => PayloadResult::Error(e),
+            },
+        )
+    }
+
+    fn clean_view(&mut self, path: PathBuf, dry_run: bool) -> Response {
+        Response::CleanView(match View::clean(path, dry_run) {
+            Ok(report) => PayloadResult::Ok(Box::new(report)),
+            Err(e) => PayloadResult::Error(e.to_string()),
+        })
+    }
+
+    fn tag_info(&mut self, tag: String, top: usize) -> Response {
+        match get_registry_read().tag_info(&tag, top) {
+            Some(info) => Response::TagInfo(PayloadResult::Ok(Box::new(info))),
+            None => Response::TagInfo(PayloadResult::Error(format!("tag {tag} doesn't exist"))),
         }
     }
 
-    fn edit_tag(&mut self, tag: String, color: Color) -> Response {
+    fn add_implication(&mut self, tag: String, implies: String, dry_run: bool) -> Response {
+        let tag = plan::normalize_name(&CONFIG.tag_names, &tag);
+        let implies = plan::normalize_name(&CONFIG.tag_names, &implies);
+        let registry = get_registry_read();
+        if let Err(e) = registry.check_implication(&tag, &implies) {
+            return Response::AddImplication(PayloadResult::Error(e.to_string()));
+        }
+        if plan::has_implication(&registry, &tag, &implies) {
+            return Response::AddImplication(PayloadResult::Ok(vec![]));
+        }
+        drop(registry);
+        let edits = vec![
+            AuditEntry::new("add_implication", AuditAction::Edit, None, tag.as_str())
+                .with_details(format!("implies: {implies}")),
+        ];
+        if dry_run {
+            return Response::AddImplication(PayloadResult::Ok(edits));
+        }
+
         let mut registry = get_registry_write();
-        if registry.get_tag(&tag).is_none() {
-            return Response::EditTag(PayloadResult::Error(format!("tag {tag} doesn't exist")));
+        if let Err(e) = registry.add_implication(tag, implies) {
+            return Response::AddImplication(PayloadResult::Error(e.to_string()));
         }
-        registry.update_tag_color(tag, color);
         if let Err(e) = registry.save() {
             log::error!("{e}")
         }
-        Response::EditTag(PayloadResult::Ok(()))
+        drop(registry);
+        self.audit(edits.clone());
+        Response::AddImplication(PayloadResult::Ok(edits))
     }
 
-    fn copy_tags(&mut self, source: PathBuf, target: Vec<PathBuf>) -> Response {
-        let tags = match list_tags(&source) {
-            Ok(tags) => tags,
-            Err(e) => {
-                return Response::CopyTags(PayloadResult::Error(vec![format!(
-                    "faile to copy tags - {e}"
-                )]))
-            }
-        };
-        if tags.is_empty() {
-            return Response::CopyTags(PayloadResult::Ok(()));
+    fn remove_implication(&mut self, tag: String, implies: String, dry_run: bool) -> Response {
+        let tag = plan::normalize_name(&CONFIG.tag_names, &tag);
+        let implies = plan::normalize_name(&CONFIG.tag_names, &implies);
+        if !plan::has_implication(&get_registry_read(), &tag, &implies) {
+            return Response::RemoveImplication(PayloadResult::Error(format!(
+                "tag {tag} doesn't imply {implies}"
+            )));
+        }
+        let edits =
+            vec![
+                AuditEntry::new("remove_implication", AuditAction::Edit, None, tag.as_str())
+                    .with_details(format!("no longer implies: {implies}")),
+            ];
+        if dry_run {
+            return Response::RemoveImplication(PayloadResult::Ok(edits));
         }
 
-        let mut errors = vec![];
-        let mut new_entries = vec![];
         let mut registry = get_registry_write();
+        registry.remove_implication(&tag, &implies);
+        if let Err(e) = registry.save() {
+            log::error!("{e}")
+        }
+        drop(registry);
+        self.audit(edits.clone());
+        Response::RemoveImplication(PayloadResult::Ok(edits))
+    }
 
-        for path in target {
-            let (id, added) = registry.add_or_update_entry(EntryData::new(&path));
-            if added {
-                if let Err(e) = clear_tags(&path) {
-                    log::error!(
-                        "failed to clear tags of file `{}`, reason: {e}",
-                        path.display()
-                    );
-                }
-                new_entries.push(path.to_path_buf());
-            }
-            for tag in &tags {
-                if let Err(e) = tag.save_to(&path) {
-                    errors.push(e.to_string());
-                } else {
-                    registry.tag_entry(tag, id);
-                }
-            }
-            if registry.list_entry_tags(id).unwrap_or_default().is_empty() {
-                registry.remove_entry(id);
-            }
+    fn list_implications(&mut self) -> Response {
+        let implications = get_registry_read()
+            .list_implications()
+            .map(|(tag, implied)| (tag.to_string(), implied.to_string()))
+            .collect();
+        Response::ListImplications(PayloadResult::Ok(implications))
+    }
+
+    fn add_alias(&mut self, alias: String, tag: String, dry_run: bool) -> Response {
+        let registry = get_registry_read();
+        // existing tags that break the naming rules can still be merged into other tags
+        let alias = match CONFIG.tag_names.normalize(&alias) {
+            Ok(_) if registry.get_tag(&alias).is_some() => alias,
+            Ok(alias) => alias,
+            Err(_) if registry.get_tag(&alias).is_some() => alias,
+            Err(e) => return Response::AddAlias(PayloadResult::Error(vec![e.to_string()])),
+        };
+        let tag = plan::normalize_name(&CONFIG.tag_names, &tag);
+        if let Err(e) = registry.check_alias(&alias, &tag) {
+            return Response::AddAlias(PayloadResult::Error(vec![e.to_string()]));
+        }
+        let changes = plan::merge_alias(&registry, &alias, &tag);
+        drop(registry);
+        // entries have to be retagged before the alias hides the tag they are tagged with
+        let changes = match self.apply_or_plan("add_alias", changes, dry_run) {
+            PayloadResult::Ok(changes) => changes,
+            error => return Response::AddAlias(error),
+        };
+        if dry_run {
+            return Response::AddAlias(PayloadResult::Ok(changes));
         }
 
+        let mut registry = get_registry_write();
+        match registry.add_alias(alias.clone(), tag.clone()) {
+            Ok(true) => {}
+            Ok(false) => return Response::AddAlias(PayloadResult::Ok(changes)),
+            Err(e) => return Response::AddAlias(PayloadResult::Error(vec![e.to_string()])),
+        }
+        let canonical = registry.resolve_alias(&alias).to_string();
         if let Err(e) = registry.save() {
             log::error!("{e}")
         }
+        drop(registry);
+        self.audit(vec![AuditEntry::new(
+            "add_alias",
+            AuditAction::Edit,
+            None,
+            canonical,
+        )
+        .with_details(format!("alias: {alias}"))]);
+        Response::AddAlias(PayloadResult::Ok(changes))
+    }
 
-        if !new_entries.is_empty() {
-            self.push_event(EntryEvent::Add(new_entries));
+    fn remove_alias(&mut self, alias: String, dry_run: bool) -> Response {
+        let canonical = match get_registry_read()
+            .list_aliases()
+            .find(|(a, _)| *a == alias)
+        {
+            Some((_, canonical)) => canonical.to_string(),
+            None => {
+                return Response::RemoveAlias(PayloadResult::Error(format!(
+                    "{alias} is not an alias"
+                )))
+            }
+        };
+        let edits = vec![
+            AuditEntry::new("remove_alias", AuditAction::Edit, None, canonical)
+                .with_details(format!("no longer aliased by: {alias}")),
+        ];
+        if dry_run {
+            return Response::RemoveAlias(PayloadResult::Ok(edits));
         }
 
-        if errors.is_empty() {
-            Response::CopyTags(PayloadResult::Ok(()))
-        } else {
-            Response::CopyTags(PayloadResult::Error(errors))
+        let mut registry = get_registry_write();
+        registry.remove_alias(&alias);
+        if let Err(e) = registry.save() {
+            log::error!("{e}")
         }
+        drop(registry);
+        self.audit(edits.clone());
+        Response::RemoveAlias(PayloadResult::Ok(edits))
     }
 
-    fn clear_files(&mut self, files: Vec<PathBuf>) -> Response {
-        if files.is_empty() {
-            return Response::ClearFiles(PayloadResult::Error(vec!["no files to clear".into()]));
+    fn list_aliases(&mut self) -> Response {
+        let aliases = get_registry_read()
+            .list_aliases()
+            .map(|(alias, tag)| (alias.to_string(), tag.to_string()))
+            .collect();
+        Response::ListAliases(PayloadResult::Ok(aliases))
+    }
+
+    fn add_saved_search(&mut self, name: String, query: String, dry_run: bool) -> Response {
+        let name = match CONFIG.tag_names.normalize(&name) {
+            Ok(name) => name,
+            Err(e) => return Response::AddSavedSearch(PayloadResult::Error(e.to_string())),
+        };
+        let registry = get_registry_read();
+        if let Err(e) = registry.check_saved_search(&name, &query) {
+            return Response::AddSavedSearch(PayloadResult::Error(e.to_string()));
+        }
+        let details = match registry.saved_search(&name) {
+            Some(saved) if saved == query => {
+                return Response::AddSavedSearch(PayloadResult::Ok(vec![]))
+            }
+            Some(saved) => format!("query: {query}, replaced: {saved}"),
+            None => format!("query: {query}"),
+        };
+        drop(registry);
+        let edits =
+            vec![
+                AuditEntry::new("add_saved_search", AuditAction::Edit, None, name.as_str())
+                    .with_details(details),
+            ];
+        if dry_run {
+            return Response::AddSavedSearch(PayloadResult::Ok(edits));
         }
 
-        let mut errors = vec![];
         let mut registry = get_registry_write();
+        if let Err(e) = registry.add_saved_search(name, query) {
+            return Response::AddSavedSearch(PayloadResult::Error(e.to_string()));
+        }
+        if let Err(e) = registry.save() {
+            log::error!("{e}")
+        }
+        drop(registry);
+        self.audit(edits.clone());
+        Response::AddSavedSearch(PayloadResult::Ok(edits))
+    }
 
-        for file in &files {
-            if let Some(id) = registry.find_entry(file) {
-                let entry = registry.get_entry(id).unwrap();
-                if let Err(e) = clear_tags(entry.path()) {
-                    errors.push(format!(
-                        "failed to clear tags from `{}`, reason: {e}",
-                        entry.path().display()
-                    ));
-                } else {
-                    registry.clear_entry(id);
-                }
+    fn remove_saved_search(&mut self, name: String, dry_run: bool) -> Response {
+        let name = plan::normalize_name(&CONFIG.tag_names, &name);
+        let query = match get_registry_read().saved_search(&name) {
+            Some(query) => query.to_string(),
+            None => {
+                return Response::RemoveSavedSearch(PayloadResult::Error(format!(
+                    "{name} is not a saved search"
+                )))
             }
+        };
+        let edits = vec![AuditEntry::new(
+            "remove_saved_search",
+            AuditAction::Edit,
+            None,
+            name.as_str(),
+        )
+        .with_details(format!("query: {query}"))];
+        if dry_run {
+            return Response::RemoveSavedSearch(PayloadResult::Ok(edits));
         }
 
+        let mut registry = get_registry_write();
+        registry.remove_saved_search(&name);
         if let Err(e) = registry.save() {
             log::error!("{e}")
         }
+        drop(registry);
+        self.audit(edits.clone());
+        Response::RemoveSavedSearch(PayloadResult::Ok(edits))
+    }
 
-        self.push_event(EntryEvent::Remove(files));
-
-        if errors.is_empty() {
-            Response::ClearFiles(PayloadResult::Ok(()))
-        } else {
-            Response::ClearFiles(PayloadResult::Error(errors))
-        }
+    fn list_saved_searches(&mut self) -> Response {
+        let saved = get_registry_read()
+            .list_saved_searches()
+            .map(|(name, query)| (name.to_string(), query.to_string()))
+            .collect();
+        Response::ListSavedSearches(PayloadResult::Ok(saved))
     }
 
-    fn clear_tags(&mut self, tags: Vec<String>) -> Response {
-        if tags.is_empty() {
-            return Response::ClearTags(PayloadResult::Error(vec!["no tags to clear".into()]));
+    fn import(&mut self, data: RegistryExport, dry_run: bool) -> Response {
+        let registry = get_registry_read();
+        let mut changes = plan::import(&registry, &data);
+        let (rules, merges, mut errors) = plan::import_rules(&registry, &data);
+        drop(registry);
+        // entries have to be retagged before the aliases hide the tags they are tagged with
+        changes.extend(merges);
+        if dry_run {
+            return Response::Import(PayloadResult::Ok(ImportReport {
+                changes,
+                edits: rules.iter().map(|rule| rule.describe("import")).collect(),
+                errors,
+            }));
         }
+        let (changes, failed) = self.apply_and_record("import", changes);
+        errors.extend(failed);
 
-        let mut removed = vec![];
+        let mut edits = vec![];
         let mut registry = get_registry_write();
-
-        for tag in &tags {
-            let tag = Tag::random(tag, DEFAULT_COLORS);
-            let cleared = registry.clear_tag(&tag);
-            if let Some(cleared) = cleared {
-                for entry in &cleared {
-                    if let Err(e) = tag.remove_from(entry.path()) {
-                        log::error!(
-                            "failed to untag {tag} entry `{}`, reason: {e}",
-                            entry.path().display()
-                        );
-                    }
+        for tag in &data.tags {
+            registry.update_tag(tag.name(), |registered| {
+                if registered.description().is_none() {
+                    registered.set_description(tag.description().unwrap_or_default());
                 }
-                cleared
-                    .into_iter()
-                    .map(|e| e.into_path_buf())
-                    .for_each(|e| removed.push(e));
+                if registered.icon().is_none() {
+                    registered.set_icon(tag.icon().unwrap_or_default());
+                }
+            });
+        }
+        for rule in &rules {
+            match rule.apply(&mut registry) {
+                Ok(()) => edits.push(rule.describe("import")),
+                Err(e) => errors.push(e),
             }
         }
-
         if let Err(e) = registry.save() {
             log::error!("{e}")
         }
+        drop(registry);
+        self.audit(edits.clone());
 
-        if !removed.is_empty() {
-            self.push_event(EntryEvent::Remove(removed));
-        }
+        Response::Import(PayloadResult::Ok(ImportReport {
+            changes,
+            edits,
+            errors,
+        }))
+    }
 
-        Response::ClearFiles(PayloadResult::Ok(()))
+    fn copy_tags(&mut self, source: PathBuf, target: Vec<PathBuf>, dry_run: bool) -> Response {
+        let (source, target) = (canonical_path(source), canonical_paths(target));
+        let changes = plan::copy_tags(&get_registry_read(), &source, &target);
+        Response::CopyTags(self.apply_or_plan("copy_tags", changes, dry_run))
     }
 
-    fn list_tags(&mut self, with_files: bool) -> Response {
-        let registry = get_registry_read();
-        if with_files {
-            Response::ListTags(PayloadResult::Ok(
-                registry.list_tags_and_entries().collect(),
-            ))
-        } else {
-            Response::ListTags(PayloadResult::Ok(
-                registry.list_tags().map(|t| (t.clone(), vec![])).collect(),
-            ))
+    fn clear_files(&mut self, files: Vec<PathBuf>, dry_run: bool) -> Response {
+        let files = canonical_paths(files);
+        if files.is_empty() {
+            return Response::ClearFiles(PayloadResult::Error(vec!["no files to clear".into()]));
         }
+        let changes = plan::clear_files(&get_registry_read(), &files);
+        Response::ClearFiles(self.apply_or_plan("clear_files", changes, dry_run))
     }
 
-    fn list_files(&mut self, with_tags: bool) -> Response {
+    fn clear_tags(&mut self, tags: Vec<String>, dry_run: bool) -> Response {
+        if tags.is_empty() {
+            return Response::ClearTags(PayloadResult::Error(vec!["no tags to clear".into()]));
+        }
+        let tags: Vec<_> = tags
+            .iter()
+            .map(|tag| plan::normalize_name(&CONFIG.tag_names, tag))
+            .collect();
+        let changes = plan::clear_tags(&get_registry_read(), &tags);
+        Response::ClearTags(self.apply_or_plan("clear_tags", changes, dry_run))
+    }
+
+    fn list_tags(&mut self, with_files: bool, page: Page) -> Response {
         let registry = get_registry_read();
-        let entries = if with_tags {
-            registry.list_entries_and_tags().collect()
-        } else {
+        let mut tags: Vec<_> = if with_files {
             registry
-                .list_entries()
-                .map(|e| (e.clone(), vec![]))
+                .list_tags_and_entries()
+                .map(|(tag, mut entries)| {
+                    entries.sort_by(|a, b| a.path().cmp(b.path()));
+                    (tag, entries)
+                })
                 .collect()
+        } else {
+            registry.list_tags().map(|t| (t.clone(), vec![])).collect()
         };
+        tags.sort_by(|(a, _), (b, _)| a.name().cmp(b.name()));
+        Response::ListTags(PayloadResult::Ok(page.apply(tags)))
+    }
+
+    fn list_files(&mut self, with_tags: bool, sort: Sort, page: Page) -> Response {
+        let registry = get_registry_read();
+        let entries: Vec<_> = registry
+            .list_entries_and_tags()
+            .map(|(entry, mut tags)| {
+                tags.sort_unstable();
+                (entry, tags)
+            })
+            .collect();
+        let entries = page
+            .apply(sort.sort(entries, |(entry, _)| entry, |(_, tags)| tags.len()))
+            .into_iter()
+            .map(|(entry, tags)| (entry, if with_tags { tags } else { vec![] }))
+            .collect();
         Response::ListFiles(PayloadResult::Ok(entries))
     }
 
     fn inspect_files(&mut self, files: Vec<PathBuf>) -> Response {
+        let files = canonical_paths(files);
         if files.is_empty() {
             return Response::InspectFiles(PayloadResult::Error("no files to inspect".into()));
         }
@@ -394,35 +1197,186 @@ impl WutagDaemon {
         Response::InspectFiles(PayloadResult::Ok(entries))
     }
 
-    fn search(&mut self, tags: Vec<String>, any: bool) -> Response {
+    fn inspect_effective(&mut self, files: Vec<PathBuf>) -> Response {
+        let files = canonical_paths(files);
+        if files.is_empty() {
+            return Response::InspectEffective(PayloadResult::Error("no files to inspect".into()));
+        }
+        let registry = get_registry_read();
+        let index = registry.entry_index();
+        let entries = files
+            .into_iter()
+            .map(|file| {
+                let tags = registry.effective_tags(&file, &index);
+                (file, tags)
+            })
+            .collect();
+        Response::InspectEffective(PayloadResult::Ok(entries))
+    }
+
+    fn search(&mut self, tags: Vec<String>, options: SearchOptions) -> Response {
         if tags.is_empty() {
             return Response::Search(PayloadResult::Error("no tags to search for".into()));
         }
+        let descendants = search_descendants(&tags, &options);
         let registry = get_registry_read();
-        let entries = if any {
-            registry.list_entries_with_any_tags(tags)
+        match find_matches(&registry, &tags, &options, &descendants, &mut vec![]) {
+            Ok(found) => Response::Search(PayloadResult::Ok(sort_matches(
+                found,
+                options.sort,
+                options.page,
+            ))),
+            Err(e) => Response::Search(PayloadResult::Error(e)),
+        }
+    }
+
+    fn save_journal(&self) {
+        if let Err(e) = self.journal.save() {
+            log::error!("{e}")
+        }
+    }
+
+    fn undo(&mut self, count: usize, dry_run: bool) -> Response {
+        if dry_run {
+            let changes = self
+                .journal
+                .list_applied()
+                .take(count)
+                .flat_map(JournalEntry::inverse_changes)
+                .collect();
+            return Response::Undo(PayloadResult::Ok(changes));
+        }
+
+        let mut undone = vec![];
+        let mut errors = vec![];
+        for _ in 0..count {
+            let entry = match self.journal.pop_applied() {
+                Some(entry) => entry,
+                None => break,
+            };
+            log::trace!("undoing {} from {}", entry.request, entry.timestamp);
+            let (applied, entry_errors) = self.apply_changes(entry.inverse_changes());
+            self.audit_changes("undo", &applied);
+            undone.extend(applied);
+            errors.extend(entry_errors);
+            self.journal.push_undone(entry);
+        }
+        self.save_journal();
+
+        if errors.is_empty() {
+            Response::Undo(PayloadResult::Ok(undone))
         } else {
-            registry.list_entries_with_all_tags(tags)
-        };
-        let mut found = vec![];
-        for entry in entries {
-            if let Some(entry) = registry.get_entry(entry) {
-                found.push(entry.clone());
-            }
+            Response::Undo(PayloadResult::Error(errors))
+        }
+    }
+
+    fn redo(&mut self, count: usize, dry_run: bool) -> Response {
+        if dry_run {
+            let changes = self
+                .journal
+                .list_undone()
+                .take(count)
+                .flat_map(|entry| entry.changes.clone())
+                .collect();
+            return Response::Redo(PayloadResult::Ok(changes));
+        }
+
+        let mut redone = vec![];
+        let mut errors = vec![];
+        for _ in 0..count {
+            let entry = match self.journal.pop_undone() {
+                Some(entry) => entry,
+                None => break,
+            };
+            log::trace!("redoing {} from {}", entry.request, entry.timestamp);
+            let (applied, entry_errors) = self.apply_changes(entry.changes.clone());
+            self.audit_changes("redo", &applied);
+            redone.extend(applied);
+            errors.extend(entry_errors);
+            self.journal.push_redone(entry);
+        }
+        self.save_journal();
+
+        if errors.is_empty() {
+            Response::Redo(PayloadResult::Ok(redone))
+        } else {
+            Response::Redo(PayloadResult::Error(errors))
+        }
+    }
+
+    fn history(&mut self) -> Response {
+        Response::History(PayloadResult::Ok((
+            self.journal.list_applied().cloned().collect(),
+            self.journal.list_undone().cloned().collect(),
+        )))
+    }
+
+    /// Appends the `entries` to the audit log marking them with credentials of the current client.
+    fn audit(&self, entries: Vec<AuditEntry>) {
+        let peer = self.listener.peer().unwrap_or_default();
+        let entries: Vec<_> = entries
+            .into_iter()
+            .map(|entry| entry.with_client(peer.pid, peer.uid))
+            .collect();
+        if let Err(e) = self.audit.append(&entries) {
+            log::error!("{e}")
+        }
+    }
+
+    fn audit_changes(&self, request: &str, changes: &[TagChange]) {
+        self.audit(
+            changes
+                .iter()
+                .map(|change| AuditEntry::change(request, change))
+                .collect(),
+        );
+    }
+
+    fn audit_log(&mut self, filter: AuditFilter) -> Response {
+        match self.audit.read(&filter) {
+            Ok(entries) => Response::AuditLog(PayloadResult::Ok(entries)),
+            Err(e) => Response::AuditLog(PayloadResult::Error(e.to_string())),
+        }
+    }
+
+    fn test_rules(&mut self, path: PathBuf) -> Response {
+        match match_rules(&CONFIG.watch, &path) {
+            Ok(matches) => Response::TestRules(PayloadResult::Ok(matches)),
+            Err(e) => Response::TestRules(PayloadResult::Error(e.to_string())),
         }
-        Response::Search(PayloadResult::Ok(found))
     }
 
     fn ping(&mut self) -> Response {
         Response::Ping(PayloadResult::Ok(()))
     }
 
-    fn clean_cache(&mut self) -> Response {
+    fn clean_cache(&mut self, dry_run: bool) -> Response {
+        let dropped: Vec<_> = get_registry_read()
+            .list_entries()
+            .flat_map(|entry| {
+                entry.tagged().keys().map(|tag| {
+                    AuditEntry::new(
+                        "clear_cache",
+                        AuditAction::Remove,
+                        Some(entry.path().to_path_buf()),
+                        tag.as_str(),
+                    )
+                    .with_details("registry only")
+                })
+            })
+            .collect();
+        if dry_run {
+            return Response::ClearCache(PayloadResult::Ok(dropped));
+        }
+
         let mut registry = get_registry_write();
         registry.clear();
         if let Err(e) = registry.save() {
             log::error!("{e}")
         }
-        Response::ClearCache(PayloadResult::Ok(()))
+        drop(registry);
+        // the files keep their tags so the clear isn't journaled, redoing it would remove them
+        self.audit(dropped.clone());
+        Response::ClearCache(PayloadResult::Ok(dropped))
     }
 }
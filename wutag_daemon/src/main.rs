@@ -1,6 +1,9 @@
+mod config;
 mod daemon;
 mod notifyd;
+mod plan;
 mod registry;
+mod views;
 
 use daemon::WutagDaemon;
 use notifyd::NotifyDaemon;
@@ -8,6 +11,7 @@ use once_cell::sync::Lazy;
 use std::path::PathBuf;
 use std::sync::RwLock;
 use thiserror::Error as ThisError;
+use wutag_core::audit::AuditLog;
 use wutag_ipc::{default_socket, IpcServer};
 
 pub static ENTRIES_EVENTS: Lazy<RwLock<Vec<EntryEvent>>> = Lazy::new(|| RwLock::new(Vec::new()));
@@ -33,6 +37,21 @@ pub enum Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Returns the path of the file with `name` in the data directory of the daemon. Creates the data
+/// directory if it doesn't exist.
+pub fn data_path(name: &str) -> PathBuf {
+    let data_dir = dirs::data_dir().expect("valid data directory");
+    if !data_dir.exists() {
+        std::fs::create_dir_all(&data_dir).expect("failed to initialize data directory");
+    }
+    data_dir.join(name)
+}
+
+/// Returns a handle to the audit log of the daemon.
+pub fn audit_log() -> AuditLog {
+    AuditLog::new(data_path("wutag.audit"))
+}
+
 #[derive(Debug)]
 pub enum EntryEvent {
     Add(Vec<PathBuf>),
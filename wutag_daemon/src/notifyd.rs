@@ -1,12 +1,24 @@
-use crate::registry::try_get_registry_write_loop;
-use crate::{EntryEvent, Error, Result, ENTRIES_EVENTS, NOTIFY_EVENTS};
+use crate::config::CONFIG;
+use crate::daemon::apply_changes;
+use crate::plan;
+use crate::registry::{get_registry_read, try_get_registry_write_loop};
+use crate::views::{build_views, refresh_views, views_descendants};
+use crate::{audit_log, EntryEvent, Error, Result, ENTRIES_EVENTS, NOTIFY_EVENTS};
+use chrono::Utc;
 use notify::{
-    self, event::RemoveKind, Event, EventHandler, EventKind, RecommendedWatcher, RecursiveMode,
-    Watcher,
+    self,
+    event::{CreateKind, ModifyKind, RemoveKind, RenameMode},
+    Event, EventHandler, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
 };
 use std::mem;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use thiserror::Error as ThisError;
+use wutag_core::audit::{AuditAction, AuditEntry};
+use wutag_core::color::DEFAULT_COLORS;
+use wutag_core::registry::{EntryId, TagChange, TagRegistry};
+use wutag_core::rules::match_rules;
+use wutag_core::tag::Tag;
 
 #[derive(Debug, ThisError)]
 pub enum NotifyDaemonError {
@@ -18,8 +30,106 @@ pub enum NotifyDaemonError {
     RemoveWatchEntry(notify::Error),
 }
 
+/// Returns audit log entries recording removal of all tags of the entry with `id` after its file
+/// was removed.
+fn removal_audit(registry: &TagRegistry, id: EntryId) -> Vec<AuditEntry> {
+    let path = registry
+        .get_entry(id)
+        .map(|entry| entry.path().to_path_buf());
+    registry
+        .list_entry_tags(id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|tag| {
+            AuditEntry::new(
+                "entry_removed",
+                AuditAction::Remove,
+                path.clone(),
+                tag.name(),
+            )
+        })
+        .collect()
+}
+
+fn append_audit(entries: &[AuditEntry]) {
+    if let Err(e) = audit_log().append(entries) {
+        log::error!("{e}");
+    }
+}
+
+fn audit_changes(request: &str, changes: &[TagChange]) {
+    append_audit(
+        &changes
+            .iter()
+            .map(|change| AuditEntry::change(request, change))
+            .collect::<Vec<_>>(),
+    );
+}
+
+fn emit_events(events: Vec<EntryEvent>) -> Result<()> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    match ENTRIES_EVENTS.try_write() {
+        Ok(mut entries_events) => {
+            entries_events.extend(events);
+            Ok(())
+        }
+        Err(e) => Err(Error::EntriesEventsLock(e.to_string())),
+    }
+}
+
+/// Returns tags of the files at `paths` according to the rules of watched folders. Matching the
+/// rules reads the files so this is done without holding the registry lock.
+fn rule_tags(paths: &[PathBuf]) -> Vec<(PathBuf, Vec<Tag>)> {
+    let mut tagged = vec![];
+    for path in paths.iter().filter(|path| path.is_file()) {
+        let matches = match match_rules(&CONFIG.watch, path) {
+            Ok(matches) => matches,
+            Err(e) => {
+                log::error!(
+                    "failed to match rules for `{}`, reason: {e}",
+                    path.display()
+                );
+                continue;
+            }
+        };
+        let mut tags: Vec<Tag> = vec![];
+        for tag in matches.into_iter().flat_map(|m| m.tags) {
+            let tag = match CONFIG.tag_names.normalize(&tag) {
+                Ok(tag) => tag,
+                Err(e) => {
+                    log::error!("rule for `{}` skipped a tag, reason: {e}", path.display());
+                    continue;
+                }
+            };
+            if !tags.iter().any(|t| t.name() == tag) {
+                tags.push(Tag::random(tag, DEFAULT_COLORS));
+            }
+        }
+        if !tags.is_empty() {
+            tagged.push((path.to_path_buf(), tags));
+        }
+    }
+    tagged
+}
+
+/// Returns changes that tag the files with the tags returned by [rule_tags](rule_tags).
+fn auto_tag_changes(registry: &TagRegistry, tagged: Vec<(PathBuf, Vec<Tag>)>) -> Vec<TagChange> {
+    tagged
+        .into_iter()
+        .flat_map(|(path, tags)| plan::tag_files(registry, &[path], &tags, None))
+        .collect()
+}
+
+/// How often the registry is checked for expired tags.
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
 pub struct NotifyDaemon {
     notify: RecommendedWatcher,
+    last_expiry_check: Option<Instant>,
+    /// Revision of the registry the configured views were last updated with.
+    views_revision: Option<u64>,
 }
 
 struct Handler;
@@ -31,10 +141,17 @@ impl EventHandler for Handler {
                 EventKind::Remove(RemoveKind::File)
                 | EventKind::Remove(RemoveKind::Any)
                 | EventKind::Remove(RemoveKind::Folder)
-                | EventKind::Remove(RemoveKind::Other) => match NOTIFY_EVENTS.try_write() {
-                    Ok(mut events) => events.push(event),
-                    Err(e) => log::error!("failed to lock notify events, reason: {e}"),
-                },
+                | EventKind::Remove(RemoveKind::Other)
+                | EventKind::Create(CreateKind::File)
+                | EventKind::Create(CreateKind::Any)
+                | EventKind::Modify(ModifyKind::Name(RenameMode::To))
+                | EventKind::Modify(ModifyKind::Name(RenameMode::Both))
+                | EventKind::Modify(ModifyKind::Name(RenameMode::Any)) => {
+                    match NOTIFY_EVENTS.try_write() {
+                        Ok(mut events) => events.push(event),
+                        Err(e) => log::error!("failed to lock notify events, reason: {e}"),
+                    }
+                }
                 _ => {}
             },
             Err(e) => {
@@ -49,11 +166,32 @@ impl NotifyDaemon {
         let mut d = Self {
             notify: RecommendedWatcher::new(Handler, Default::default())
                 .map_err(NotifyDaemonError::NotifyWatcherInit)?,
+            last_expiry_check: None,
+            views_revision: None,
         };
 
+        d.watch_folders();
         d.rebuild_watch_entries().map(|_| d)
     }
 
+    /// Starts watching folders in which new files are tagged automatically.
+    fn watch_folders(&mut self) {
+        for folder in &CONFIG.watch {
+            log::trace!("adding watch folder {}", folder.path.display());
+            let mode = if folder.recursive {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            if let Err(e) = self.notify.watch(&folder.path, mode) {
+                log::error!(
+                    "failed to watch folder `{}`, reason: {e}",
+                    folder.path.display()
+                );
+            }
+        }
+    }
+
     pub fn work_loop(mut self) {
         loop {
             if let Err(e) = self.handle_entries_events() {
@@ -62,6 +200,12 @@ impl NotifyDaemon {
             if let Err(e) = self.handle_notify_events() {
                 log::error!("{e}");
             }
+            if let Err(e) = self.handle_expired_tags() {
+                log::error!("{e}");
+            }
+            if let Err(e) = self.handle_views() {
+                log::error!("{e}");
+            }
             std::thread::sleep(std::time::Duration::from_millis(200));
         }
     }
@@ -69,6 +213,7 @@ impl NotifyDaemon {
     fn rebuild_watch_entries(&mut self) -> Result<()> {
         let mut registry = try_get_registry_write_loop()?;
         let mut to_remove = vec![];
+        let mut audit = vec![];
         for entry in registry.list_entries().cloned() {
             if let Err(e) = self.add_watch_entry(entry.path()) {
                 log::error!("{e}");
@@ -89,10 +234,12 @@ impl NotifyDaemon {
                 entry.path().display()
             );
             if let Some(id) = registry.find_entry(entry.path()) {
+                audit.extend(removal_audit(&registry, id));
                 registry.remove_entry(id);
             }
         }
         registry.save().map_err(Error::RegistrySave)?;
+        append_audit(&audit);
         Ok(())
     }
 
@@ -127,15 +274,80 @@ impl NotifyDaemon {
         let events = mem::take(&mut *events_handle);
         mem::drop(events_handle);
         let mut registry = try_get_registry_write_loop()?;
+        let mut audit = vec![];
+        let mut created = vec![];
         for event in events {
-            for path in event.paths {
-                if let Some(id) = registry.find_entry(&path) {
-                    log::trace!("removing entry {}, id: {id}", path.display());
-                    registry.clear_entry(id);
+            match event.kind {
+                EventKind::Remove(_) => {
+                    for path in event.paths {
+                        if let Some(id) = registry.find_entry(&path) {
+                            log::trace!("removing entry {}, id: {id}", path.display());
+                            audit.extend(removal_audit(&registry, id));
+                            registry.clear_entry(id);
+                        }
+                    }
+                }
+                // the first path is the source of the rename
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                    created.extend(event.paths.into_iter().skip(1))
                 }
+                _ => created.extend(event.paths),
             }
         }
         registry.save().map_err(Error::RegistrySave)?;
+        mem::drop(registry);
+        append_audit(&audit);
+
+        let tagged = rule_tags(&created);
+        if tagged.is_empty() {
+            return Ok(());
+        }
+        let changes = auto_tag_changes(&get_registry_read(), tagged);
+        let (applied, errors, events) = apply_changes(changes);
+        for e in errors {
+            log::error!("failed to tag new file, reason: {e}");
+        }
+        audit_changes("auto_tag", &applied);
+        emit_events(events)
+    }
+
+    /// Removes tags that expired from files and the registry. Runs at most once every
+    /// [EXPIRY_CHECK_INTERVAL](EXPIRY_CHECK_INTERVAL).
+    fn handle_expired_tags(&mut self) -> Result<()> {
+        if let Some(last_check) = self.last_expiry_check {
+            if last_check.elapsed() < EXPIRY_CHECK_INTERVAL {
+                return Ok(());
+            }
+        }
+        self.last_expiry_check = Some(Instant::now());
+
+        let expired = get_registry_read().list_expired(&Utc::now());
+        if expired.is_empty() {
+            return Ok(());
+        }
+        let (applied, errors, events) = apply_changes(expired);
+
+        for e in errors {
+            log::error!("failed to remove expired tag, reason: {e}");
+        }
+        audit_changes("tag_expired", &applied);
+        emit_events(events)
+    }
+
+    /// Updates the configured views if the registry was saved since they were last updated.
+    fn handle_views(&mut self) -> Result<()> {
+        if CONFIG.views.is_empty() {
+            return Ok(());
+        }
+        if self.views_revision == Some(get_registry_read().revision()) {
+            return Ok(());
+        }
+        let descendants = views_descendants();
+        let registry = get_registry_read();
+        self.views_revision = Some(registry.revision());
+        let views = build_views(&registry, &descendants);
+        mem::drop(registry);
+        refresh_views(views);
         Ok(())
     }
 
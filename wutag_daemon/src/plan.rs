@@ -0,0 +1,558 @@
+//! Functions computing the changes that a mutating request would make without applying them.
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use wutag_core::audit::{AuditAction, AuditEntry};
+use wutag_core::color::DEFAULT_COLORS;
+use wutag_core::metadata::{self, NamePattern, Template};
+use wutag_core::mime;
+use wutag_core::registry::{RegistryExport, TagChange, TagRegistry};
+use wutag_core::tag::{NameRules, Tag};
+use wutag_ipc::AutoTagOptions;
+
+/// Returns the tags of the entry at `path` or an empty list if there is no such entry.
+fn entry_tags(registry: &TagRegistry, path: &Path) -> Vec<Tag> {
+    registry
+        .find_entry(path)
+        .and_then(|id| registry.list_entry_tags(id))
+        .unwrap_or_default()
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+/// Returns the tag as it is saved in the registry so that the change contains the actual color of
+/// the tag. Aliases are resolved to their canonical tag.
+fn registered_tag(registry: &TagRegistry, tag: &Tag) -> Tag {
+    registry.resolve_tag(tag)
+}
+
+/// Returns a change removing the `tag` from the entry at `path` that remembers the expiry of the
+/// tag so that it is restored when the removal is undone.
+fn removal(registry: &TagRegistry, path: &Path, tag: Tag) -> TagChange {
+    let expires = registry
+        .find_entry(path)
+        .and_then(|id| registry.get_entry(id))
+        .and_then(|entry| entry.expires_at(tag.name()).cloned());
+    TagChange::remove(path, tag).with_expiry(expires)
+}
+
+/// Normalizes names of the `tags` according to the `rules`. Returns errors of all invalid names.
+pub fn normalize_tags(rules: &NameRules, tags: Vec<Tag>) -> Result<Vec<Tag>, Vec<String>> {
+    let mut normalized: Vec<Tag> = vec![];
+    let mut errors = vec![];
+    for tag in tags {
+        match rules.normalize_tag(tag) {
+            Ok(tag) if normalized.contains(&tag) => {}
+            Ok(tag) => normalized.push(tag),
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+    if errors.is_empty() {
+        Ok(normalized)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Returns the normalized `name` or the `name` itself if it isn't valid, used to look up tags that
+/// may have been created before the rules were configured.
+pub fn normalize_name(rules: &NameRules, name: &str) -> String {
+    rules.normalize(name).unwrap_or_else(|_| name.to_string())
+}
+
+pub fn tag_files(
+    registry: &TagRegistry,
+    files: &[PathBuf],
+    tags: &[Tag],
+    expires: Option<DateTime<Utc>>,
+) -> Vec<TagChange> {
+    let mut changes = vec![];
+    for file in files {
+        let present = entry_tags(registry, file);
+        for tag in tags {
+            let tag = registered_tag(registry, tag);
+            if !present.contains(&tag) {
+                changes.push(TagChange::add(file, tag).with_expiry(expires));
+            }
+        }
+    }
+    changes
+}
+
+/// Returns the `tags` followed by all tags implied by them that aren't already present.
+pub fn with_implied(registry: &TagRegistry, tags: &[Tag]) -> Vec<Tag> {
+    let mut all = tags.to_vec();
+    for tag in tags {
+        for implied in registry.implied_tags(tag.name()) {
+            if !all.iter().any(|t| t.name() == implied) {
+                let tag = registry
+                    .get_tag(&implied)
+                    .cloned()
+                    .unwrap_or_else(|| Tag::random(implied, DEFAULT_COLORS));
+                all.push(tag);
+            }
+        }
+    }
+    all
+}
+
+pub fn untag_files(registry: &TagRegistry, files: &[PathBuf], tags: &[Tag]) -> Vec<TagChange> {
+    let tags: Vec<_> = tags
+        .iter()
+        .map(|tag| registered_tag(registry, tag))
+        .collect();
+    let mut changes = vec![];
+    for file in files {
+        for tag in entry_tags(registry, file) {
+            if tags.contains(&tag) {
+                changes.push(removal(registry, file, tag));
+            }
+        }
+    }
+    changes
+}
+
+pub fn copy_tags(registry: &TagRegistry, source: &Path, target: &[PathBuf]) -> Vec<TagChange> {
+    tag_files(registry, target, &entry_tags(registry, source), None)
+}
+
+pub fn clear_files(registry: &TagRegistry, files: &[PathBuf]) -> Vec<TagChange> {
+    let mut changes = vec![];
+    for file in files {
+        for tag in entry_tags(registry, file) {
+            changes.push(removal(registry, file, tag));
+        }
+    }
+    changes
+}
+
+pub fn clear_tags(registry: &TagRegistry, tags: &[String]) -> Vec<TagChange> {
+    let mut changes = vec![];
+    for name in tags {
+        if let Some(tag) = registry.get_tag(name) {
+            for id in registry.list_entries_with_any_tags([name]) {
+                if let Some(entry) = registry.get_entry(id) {
+                    changes.push(removal(registry, entry.path(), tag.clone()));
+                }
+            }
+        }
+    }
+    changes
+}
+
+/// Plans retagging entries tagged with `alias` with the canonical `tag` before `alias` becomes
+/// its alias. Expiry times of the replaced tags are kept.
+pub fn merge_alias(registry: &TagRegistry, alias: &str, tag: &str) -> Vec<TagChange> {
+    let mut changes = vec![];
+    // an existing alias has no entries of its own
+    if registry.resolve_alias(alias) != alias {
+        return changes;
+    }
+    let aliased = match registry.get_tag(alias) {
+        Some(aliased) => aliased,
+        None => return changes,
+    };
+    let canonical = registry
+        .get_tag(tag)
+        .cloned()
+        .unwrap_or_else(|| Tag::new(registry.resolve_alias(tag), *aliased.color()));
+    for id in registry.list_entries_with_any_tags([alias]) {
+        if let Some(entry) = registry.get_entry(id) {
+            let removal = removal(registry, entry.path(), aliased.clone());
+            let expires = removal.expires;
+            changes.push(removal);
+            if !entry.tagged().contains_key(canonical.name()) {
+                changes.push(TagChange::add(entry.path(), canonical.clone()).with_expiry(expires));
+            }
+        }
+    }
+    changes
+}
+
+/// Plans tagging the entries of an exported registry with their tags. Tags that aren't registered
+/// yet keep their exported color and the expiry times of the tags are kept.
+pub fn import(registry: &TagRegistry, export: &RegistryExport) -> Vec<TagChange> {
+    let mut changes = vec![];
+    for exported in &export.entries {
+        let path = exported.entry.path();
+        let present = entry_tags(registry, path);
+        for name in &exported.tags {
+            let tag = export
+                .tags
+                .iter()
+                .find(|tag| tag.name() == name)
+                .cloned()
+                .unwrap_or_else(|| Tag::random(name, DEFAULT_COLORS));
+            let tag = registered_tag(registry, &tag);
+            if !present.contains(&tag) {
+                let expires = exported.entry.expires_at(name).cloned();
+                changes.push(TagChange::add(path, tag).with_expiry(expires));
+            }
+        }
+    }
+    changes
+}
+
+/// Checks whether the rule that `tag` implies `implied` exists, with aliases resolved.
+pub fn has_implication(registry: &TagRegistry, tag: &str, implied: &str) -> bool {
+    let (tag, implied) = (registry.resolve_alias(tag), registry.resolve_alias(implied));
+    registry
+        .list_implications()
+        .any(|(t, i)| t == tag && i == implied)
+}
+
+/// An implication, alias or saved search that an import adds to the registry.
+pub enum RuleEdit {
+    Implication {
+        tag: String,
+        implied: String,
+    },
+    /// `tag` is the canonical tag that the alias resolves to.
+    Alias {
+        alias: String,
+        tag: String,
+    },
+    SavedSearch {
+        name: String,
+        query: String,
+        replaced: Option<String>,
+    },
+}
+
+impl RuleEdit {
+    /// Describes the edit as made by the `request` for the audit log and dry-run plans.
+    pub fn describe(&self, request: &str) -> AuditEntry {
+        let (tag, details) = match self {
+            RuleEdit::Implication { tag, implied } => (tag, format!("implies: {implied}")),
+            RuleEdit::Alias { alias, tag } => (tag, format!("alias: {alias}")),
+            RuleEdit::SavedSearch {
+                name,
+                query,
+                replaced: Some(replaced),
+            } => (name, format!("query: {query}, replaced: {replaced}")),
+            RuleEdit::SavedSearch { name, query, .. } => (name, format!("query: {query}")),
+        };
+        AuditEntry::new(request, AuditAction::Edit, None, tag.as_str()).with_details(details)
+    }
+
+    /// Applies the edit to the `registry`.
+    pub fn apply(&self, registry: &mut TagRegistry) -> Result<(), String> {
+        match self {
+            RuleEdit::Implication { tag, implied } => registry
+                .add_implication(tag.as_str(), implied.as_str())
+                .map(drop),
+            RuleEdit::Alias { alias, tag } => {
+                registry.add_alias(alias.as_str(), tag.as_str()).map(drop)
+            }
+            RuleEdit::SavedSearch { name, query, .. } => registry
+                .add_saved_search(name.as_str(), query.as_str())
+                .map(drop),
+        }
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// Plans adding the implications, aliases and saved searches of an exported registry. Rules that
+/// already exist are skipped. Returns the edits, changes retagging entries tagged with the
+/// imported aliases, which have to be applied before the aliases, and errors of the rules that
+/// can't be added.
+pub fn import_rules(
+    registry: &TagRegistry,
+    export: &RegistryExport,
+) -> (Vec<RuleEdit>, Vec<TagChange>, Vec<String>) {
+    let (mut edits, mut changes, mut errors) = (vec![], vec![], vec![]);
+    for (alias, tag) in &export.aliases {
+        let canonical = registry.resolve_alias(tag);
+        if registry.resolve_alias(alias) == canonical {
+            continue;
+        }
+        if let Err(e) = registry.check_alias(alias, tag) {
+            errors.push(e.to_string());
+            continue;
+        }
+        changes.extend(merge_alias(registry, alias, tag));
+        edits.push(RuleEdit::Alias {
+            alias: alias.clone(),
+            tag: canonical.to_string(),
+        });
+    }
+    for (tag, implied) in &export.implications {
+        for implied in implied {
+            if has_implication(registry, tag, implied) {
+                continue;
+            }
+            if let Err(e) = registry.check_implication(tag, implied) {
+                errors.push(e.to_string());
+                continue;
+            }
+            edits.push(RuleEdit::Implication {
+                tag: tag.clone(),
+                implied: implied.clone(),
+            });
+        }
+    }
+    for (name, query) in &export.saved_searches {
+        let replaced = match registry.saved_search(name) {
+            Some(saved) if saved == query => continue,
+            saved => saved.map(str::to_string),
+        };
+        if let Err(e) = registry.check_saved_search(name, query) {
+            errors.push(e.to_string());
+            continue;
+        }
+        edits.push(RuleEdit::SavedSearch {
+            name: name.clone(),
+            query: query.clone(),
+            replaced,
+        });
+    }
+    (edits, changes, errors)
+}
+
+/// Derives tags of the `files` according to the `options` and plans tagging the files with them.
+/// Returns the changes together with errors of files whose tags couldn't be derived in some of the
+/// ways, the tags derived in the other ways are still applied.
+pub fn auto_tag(
+    registry: &TagRegistry,
+    rules: &NameRules,
+    files: &[PathBuf],
+    options: &AutoTagOptions,
+) -> (Vec<TagChange>, Vec<String>) {
+    let mut changes = vec![];
+    let mut errors = vec![];
+    let templates = match options
+        .metadata
+        .iter()
+        .map(|template| Template::parse(template))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(templates) => templates,
+        Err(e) => return (changes, vec![e.to_string()]),
+    };
+    let name_pattern = match options.from_name.as_deref().map(NamePattern::parse) {
+        Some(Err(e)) => return (changes, vec![e.to_string()]),
+        Some(Ok(pattern)) => Some(pattern),
+        None => None,
+    };
+
+    let colors = match options.colors.as_slice() {
+        [] => DEFAULT_COLORS,
+        colors => colors,
+    };
+
+    for file in files.iter().filter(|file| file.is_file()) {
+        let mut tags = vec![];
+        if options.by_type {
+            match mime::detect(file) {
+                Ok(content) => tags.push(content.kind.tag()),
+                Err(e) => errors.push(format!(
+                    "failed to detect content type of `{}` - {e}",
+                    file.display()
+                )),
+            }
+        }
+        if !templates.is_empty() {
+            match metadata::read(file) {
+                Ok(metadata) => tags.extend(
+                    templates
+                        .iter()
+                        .filter_map(|template| template.render(&metadata)),
+                ),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        if let Some(pattern) = &name_pattern {
+            tags.extend(pattern.tags(file));
+        }
+        let mut tags: Vec<_> = tags
+            .into_iter()
+            .filter_map(|tag| match rules.normalize(&tag) {
+                Ok(tag) => Some(tag),
+                Err(e) => {
+                    errors.push(format!("`{}` - {e}", file.display()));
+                    None
+                }
+            })
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        let tags: Vec<_> = tags
+            .into_iter()
+            .map(|tag| Tag::random(tag, colors))
+            .collect();
+        changes.extend(tag_files(registry, &[file.to_path_buf()], &tags, None));
+    }
+    (changes, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::record_changes;
+    use chrono::Duration;
+    use wutag_core::color::Color;
+    use wutag_core::registry::{ChangeKind, EntryData, ExportedEntry};
+
+    fn tag(registry: &mut TagRegistry, path: &str, tag: &Tag, expires: Option<DateTime<Utc>>) {
+        let (id, _) = registry.add_or_update_entry(EntryData::new(path));
+        registry.tag_entry(tag, id);
+        registry.set_tag_expiry(tag.name(), id, expires);
+    }
+
+    fn tag_names(registry: &TagRegistry, path: &str) -> Vec<String> {
+        let mut names: Vec<_> = entry_tags(registry, Path::new(path))
+            .iter()
+            .map(|tag| tag.name().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn expiry(registry: &TagRegistry, path: &str, tag: &str) -> Option<DateTime<Utc>> {
+        let id = registry.find_entry(path)?;
+        registry.get_entry(id)?.expires_at(tag).cloned()
+    }
+
+    #[test]
+    fn clears_files() {
+        let mut registry = TagRegistry::default();
+        let expires = Some(Utc::now() + Duration::days(1));
+        let (draft, done) = (Tag::new("draft", Color::Red), Tag::new("done", Color::Blue));
+        tag(&mut registry, "/docs/a", &draft, expires);
+        tag(&mut registry, "/docs/a", &done, None);
+        tag(&mut registry, "/docs/b", &draft, None);
+
+        let changes = clear_files(&registry, &[PathBuf::from("/docs/a")]);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|c| c.kind == ChangeKind::Remove));
+        let removal = changes.iter().find(|c| c.tag == draft).unwrap();
+        assert_eq!(removal.expires, expires);
+
+        record_changes(&mut registry, &changes);
+        assert!(registry.find_entry("/docs/a").is_none());
+        assert_eq!(tag_names(&registry, "/docs/b"), vec!["draft"]);
+        assert!(clear_files(&registry, &[PathBuf::from("/docs/a")]).is_empty());
+    }
+
+    #[test]
+    fn clears_tags() {
+        let mut registry = TagRegistry::default();
+        let (draft, done) = (Tag::new("draft", Color::Red), Tag::new("done", Color::Blue));
+        tag(&mut registry, "/docs/a", &draft, None);
+        tag(&mut registry, "/docs/a", &done, None);
+        tag(&mut registry, "/docs/b", &draft, None);
+
+        let changes = clear_tags(&registry, &["draft".to_string(), "missing".to_string()]);
+        let mut paths: Vec<_> = changes.iter().map(|c| c.path.clone()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("/docs/a"), PathBuf::from("/docs/b")]
+        );
+
+        record_changes(&mut registry, &changes);
+        assert!(registry.get_tag("draft").is_none());
+        assert_eq!(tag_names(&registry, "/docs/a"), vec!["done"]);
+        assert!(registry.find_entry("/docs/b").is_none());
+    }
+
+    #[test]
+    fn merges_entries_of_alias() {
+        let mut registry = TagRegistry::default();
+        let expires = Some(Utc::now() + Duration::days(1));
+        let (old, new) = (Tag::new("old", Color::Red), Tag::new("new", Color::Blue));
+        tag(&mut registry, "/docs/a", &old, expires);
+        tag(&mut registry, "/docs/b", &old, None);
+        tag(&mut registry, "/docs/b", &new, None);
+
+        assert!(merge_alias(&registry, "missing", "new").is_empty());
+        let changes = merge_alias(&registry, "old", "new");
+        assert_eq!(changes.len(), 3);
+        let added = changes.iter().find(|c| c.kind == ChangeKind::Add).unwrap();
+        assert_eq!(added.path, PathBuf::from("/docs/a"));
+        assert_eq!(*added.tag.color(), Color::Blue);
+        assert_eq!(added.expires, expires);
+
+        record_changes(&mut registry, &changes);
+        registry.add_alias("old", "new").unwrap();
+        assert_eq!(tag_names(&registry, "/docs/a"), vec!["new"]);
+        assert_eq!(tag_names(&registry, "/docs/b"), vec!["new"]);
+        assert_eq!(expiry(&registry, "/docs/a", "new"), expires);
+        assert!(merge_alias(&registry, "old", "new").is_empty());
+    }
+
+    #[test]
+    fn imports_entries() {
+        let expires = Some(Utc::now() + Duration::days(1));
+        let mut source = TagRegistry::default();
+        tag(
+            &mut source,
+            "/docs/a",
+            &Tag::new("draft", Color::Red),
+            expires,
+        );
+        tag(
+            &mut source,
+            "/docs/a",
+            &Tag::new("done", Color::Green),
+            None,
+        );
+        let mut export = source.export();
+        export.entries.push(ExportedEntry {
+            entry: EntryData::new("/docs/b"),
+            tags: vec!["unexported".to_string()],
+        });
+
+        let mut registry = TagRegistry::default();
+        tag(
+            &mut registry,
+            "/docs/a",
+            &Tag::new("done", Color::Blue),
+            None,
+        );
+        let changes = import(&registry, &export);
+        assert_eq!(changes.len(), 2);
+        let draft = changes.iter().find(|c| c.tag.name() == "draft").unwrap();
+        assert_eq!(*draft.tag.color(), Color::Red);
+        assert_eq!(draft.expires, expires);
+
+        record_changes(&mut registry, &changes);
+        assert_eq!(tag_names(&registry, "/docs/a"), vec!["done", "draft"]);
+        assert_eq!(*registry.get_tag("done").unwrap().color(), Color::Blue);
+        assert_eq!(expiry(&registry, "/docs/a", "draft"), expires);
+        assert_eq!(tag_names(&registry, "/docs/b"), vec!["unexported"]);
+        assert!(import(&registry, &export).is_empty());
+    }
+
+    #[test]
+    fn auto_tags_files() {
+        let dir = tempdir::TempDir::new("wutag_auto_tag").unwrap();
+        let text = dir.path().join("2024-notes.txt");
+        let photo = dir.path().join("2023-trip.jpg");
+        std::fs::write(&text, "some notes").unwrap();
+        std::fs::write(&photo, b"\xff\xd8\xff\xe0broken").unwrap();
+        let files = vec![text.clone(), photo.clone(), dir.path().join("missing")];
+        let options = AutoTagOptions {
+            metadata: vec!["year={exif.year}".to_string()],
+            from_name: Some(r"^(\d+)-".to_string()),
+            colors: vec![Color::Magenta],
+            ..Default::default()
+        };
+
+        let mut registry = TagRegistry::default();
+        let (changes, errors) = auto_tag(&registry, &NameRules::default(), &files, &options);
+        // the tags derived from the name are kept when the metadata can't be read
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("2023-trip.jpg"));
+        assert_eq!(changes.len(), 2);
+        assert!(changes
+            .iter()
+            .all(|c| c.kind == ChangeKind::Add && *c.tag.color() == Color::Magenta));
+
+        record_changes(&mut registry, &changes);
+        assert_eq!(tag_names(&registry, text.to_str().unwrap()), vec!["2024"]);
+        assert_eq!(tag_names(&registry, photo.to_str().unwrap()), vec!["2023"]);
+        let (changes, _) = auto_tag(&registry, &NameRules::default(), &files, &options);
+        assert!(changes.is_empty());
+    }
+}
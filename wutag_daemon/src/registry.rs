@@ -1,4 +1,4 @@
-use crate::Result;
+use crate::{data_path, Result};
 use once_cell::sync::Lazy;
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
 use thiserror::Error as ThisError;
@@ -13,18 +13,15 @@ pub enum RegistryError {
 }
 
 static REGISTRY: Lazy<RwLock<TagRegistry>> = Lazy::new(|| {
-    let data_dir = dirs::data_dir().expect("valid data directory");
-    if !data_dir.exists() {
-        std::fs::create_dir_all(&data_dir).expect("failed to initialize data directory");
-    }
-    let registry_file = data_dir.join("wutag.db");
+    let registry_file = data_path("wutag.db");
     RwLock::new(
         TagRegistry::load(&registry_file).unwrap_or_else(|_| TagRegistry::new(registry_file)),
     )
 });
 
+/// Locks the registry for writing, waiting until other holders of the lock release it.
 pub fn get_registry_write() -> RwLockWriteGuard<'static, TagRegistry> {
-    match REGISTRY.try_write() {
+    match REGISTRY.write() {
         Ok(registry) => registry,
         Err(e) => {
             eprintln!("failed to lock registry for writing, reason: {e}");
@@ -32,8 +29,10 @@ pub fn get_registry_write() -> RwLockWriteGuard<'static, TagRegistry> {
         }
     }
 }
+
+/// Locks the registry for reading, waiting until a writer releases the lock.
 pub fn get_registry_read() -> RwLockReadGuard<'static, TagRegistry> {
-    match REGISTRY.try_read() {
+    match REGISTRY.read() {
         Ok(registry) => registry,
         Err(e) => {
             eprintln!("failed to lock registry for reading, reason: {e}");
@@ -0,0 +1,103 @@
+use crate::config::{ViewConfig, CONFIG};
+use crate::daemon::{find_query_matches, query_descendants, Descendants};
+use std::path::PathBuf;
+use wutag_core::registry::TagRegistry;
+use wutag_core::view::View;
+
+/// Builds a view in the directory at `root` containing the entries found by the `query` or with
+/// a directory for each tag if there is no query. Paths below tagged directories are taken from
+/// the `descendants` found by [query_descendants](query_descendants).
+pub fn build_view(
+    registry: &TagRegistry,
+    root: PathBuf,
+    query: Option<&str>,
+    descendants: &Descendants,
+) -> Result<View, String> {
+    let view = match query {
+        Some(query) => {
+            let found = find_query_matches(registry, query, descendants)?;
+            View::flat(
+                root,
+                found
+                    .into_iter()
+                    .map(|found| found.entry.path().to_path_buf()),
+            )
+        }
+        None => View::by_tags(
+            root,
+            registry.list_tags_and_entries().map(|(tag, entries)| {
+                (
+                    tag.name().to_string(),
+                    entries
+                        .into_iter()
+                        .map(|entry| entry.path().to_path_buf())
+                        .collect::<Vec<_>>(),
+                )
+            }),
+        ),
+    };
+    view.map_err(|e| e.to_string())
+}
+
+/// Finds the paths below tagged directories needed by the queries of the configured views. Has to
+/// be called without holding the registry lock, see [query_descendants](query_descendants).
+pub fn views_descendants() -> Vec<Descendants> {
+    CONFIG
+        .views
+        .iter()
+        .map(|config| {
+            config
+                .query
+                .as_deref()
+                .map(query_descendants)
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Builds all views configured to be kept up to date with the registry using the `descendants`
+/// found by [views_descendants](views_descendants). The views hold copies of the paths so they can
+/// be materialized after the registry lock is released.
+pub fn build_views(
+    registry: &TagRegistry,
+    descendants: &[Descendants],
+) -> Vec<(&'static ViewConfig, Result<View, String>)> {
+    CONFIG
+        .views
+        .iter()
+        .zip(descendants)
+        .map(|(config, descendants)| {
+            let view = build_view(
+                registry,
+                config.path.clone(),
+                config.query.as_deref(),
+                descendants,
+            );
+            (config, view)
+        })
+        .collect()
+}
+
+/// Updates the directories of the `views` built by [build_views](build_views).
+pub fn refresh_views(views: Vec<(&ViewConfig, Result<View, String>)>) {
+    for (config, view) in views {
+        let report = view.and_then(|view| view.materialize(false).map_err(|e| e.to_string()));
+        match report {
+            Ok(report) => {
+                for e in report.errors {
+                    log::error!("{e}");
+                }
+                log::trace!(
+                    "updated view {}, {} created, {} removed",
+                    config.path.display(),
+                    report.created.len(),
+                    report.removed.len()
+                );
+            }
+            Err(e) => log::error!(
+                "failed to update view `{}`, reason: {e}",
+                config.path.display()
+            ),
+        }
+    }
+}
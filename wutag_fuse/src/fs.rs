@@ -0,0 +1,194 @@
+use crate::tree::{Node, Tree};
+use fuser::{
+    FileAttr, FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, Request,
+};
+use libc::{EIO, ENOENT, ENOTDIR};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How long the kernel caches attributes and names of nodes.
+const TTL: Duration = Duration::from_secs(1);
+const ROOT_INODE: u64 = 1;
+
+/// Read-only filesystem presenting tags as directories.
+pub struct TagFs {
+    tree: Tree,
+    /// Paths of nodes relative to the root indexed by their inode number minus one.
+    paths: Vec<PathBuf>,
+    inodes: HashMap<PathBuf, u64>,
+    uid: u32,
+    gid: u32,
+    mounted: SystemTime,
+}
+
+impl TagFs {
+    pub fn new(tree: Tree) -> Self {
+        Self {
+            tree,
+            paths: vec![PathBuf::new()],
+            inodes: HashMap::from([(PathBuf::new(), ROOT_INODE)]),
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            mounted: SystemTime::now(),
+        }
+    }
+
+    /// Returns the inode number of the node at `path`, assigning a new one to paths seen for the
+    /// first time.
+    fn inode(&mut self, path: &Path) -> u64 {
+        if let Some(ino) = self.inodes.get(path) {
+            return *ino;
+        }
+        self.paths.push(path.to_path_buf());
+        let ino = self.paths.len() as u64;
+        self.inodes.insert(path.to_path_buf(), ino);
+        ino
+    }
+
+    fn path(&self, ino: u64) -> Option<PathBuf> {
+        self.paths.get(ino.checked_sub(1)? as usize).cloned()
+    }
+
+    /// Returns the node with inode number `ino` or the error code to reply with.
+    fn node(&mut self, ino: u64) -> Result<(PathBuf, Node), i32> {
+        let path = self.path(ino).ok_or(ENOENT)?;
+        match self.tree.node(&path) {
+            Ok(Some(node)) => Ok((path, node)),
+            Ok(None) => Err(ENOENT),
+            Err(e) => {
+                log::error!("failed to read `{}`, reason: {e}", path.display());
+                Err(EIO)
+            }
+        }
+    }
+
+    fn attr(&self, ino: u64, node: &Node) -> FileAttr {
+        let (kind, perm, size) = match node {
+            Node::Dir => (FileType::Directory, 0o555, 0),
+            Node::Link(target) => (FileType::Symlink, 0o777, target.as_os_str().len() as u64),
+            Node::File(content) => (FileType::RegularFile, 0o444, content.len() as u64),
+        };
+        FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: self.mounted,
+            mtime: self.mounted,
+            ctime: self.mounted,
+            crtime: self.mounted,
+            kind,
+            perm,
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+}
+
+fn file_type(node: &Node) -> FileType {
+    match node {
+        Node::Dir => FileType::Directory,
+        Node::Link(_) => FileType::Symlink,
+        Node::File(_) => FileType::RegularFile,
+    }
+}
+
+impl Filesystem for TagFs {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let path = match self.path(parent) {
+            Some(parent) => parent.join(name),
+            None => return reply.error(ENOENT),
+        };
+        match self.tree.node(&path) {
+            Ok(Some(node)) => {
+                let ino = self.inode(&path);
+                reply.entry(&TTL, &self.attr(ino, &node), 0)
+            }
+            Ok(None) => reply.error(ENOENT),
+            Err(e) => {
+                log::error!("failed to look up `{}`, reason: {e}", path.display());
+                reply.error(EIO)
+            }
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.node(ino) {
+            Ok((_, node)) => reply.attr(&TTL, &self.attr(ino, &node)),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn readlink(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyData) {
+        match self.node(ino) {
+            Ok((_, Node::Link(target))) => reply.data(target.as_os_str().as_bytes()),
+            Ok(_) => reply.error(libc::EINVAL),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        match self.node(ino) {
+            Ok((_, Node::File(content))) => {
+                let start = (offset.max(0) as usize).min(content.len());
+                let end = start.saturating_add(size as usize).min(content.len());
+                reply.data(&content[start..end])
+            }
+            Ok(_) => reply.error(libc::EISDIR),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let path = match self.path(ino) {
+            Some(path) => path,
+            None => return reply.error(ENOENT),
+        };
+        let listing = match self.tree.list(&path) {
+            Ok(Some(listing)) => listing,
+            Ok(None) => return reply.error(ENOTDIR),
+            Err(e) => {
+                log::error!("failed to list `{}`, reason: {e}", path.display());
+                return reply.error(EIO);
+            }
+        };
+        let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut entries = vec![
+            (ino, FileType::Directory, ".".into()),
+            (self.inode(&parent), FileType::Directory, "..".into()),
+        ];
+        for (name, node) in listing {
+            entries.push((self.inode(&path.join(&name)), file_type(&node), name));
+        }
+        for (i, (ino, kind, name)) in entries.into_iter().enumerate().skip(offset as usize) {
+            // the offset of an entry is the offset of the entry following it
+            if reply.add(ino, (i + 1) as i64, kind, name) {
+                break;
+            }
+        }
+        reply.ok()
+    }
+}
@@ -0,0 +1,38 @@
+mod fs;
+mod tree;
+
+use clap::Parser;
+use fs::TagFs;
+use fuser::MountOption;
+use std::path::PathBuf;
+use tree::Tree;
+use wutag_ipc::{default_socket, IpcClient};
+
+#[derive(Parser)]
+#[clap(version, author)]
+/// Mounts a read-only filesystem presenting tags of files managed by wutagd as directories.
+///
+/// `tags/<tag>/<tag>...` lists files tagged with all of the tags in the path as symlinks together
+/// with directories of their other tags, `queries/<name>` lists files found by a saved search and
+/// `files/<path>.tags` lists tags of each tagged file. The filesystem is unmounted with
+/// `fusermount -u <mountpoint>`.
+struct Opts {
+    /// Directory to mount the filesystem at
+    mountpoint: PathBuf,
+    #[clap(long)]
+    /// Allow other users to access the filesystem, requires `user_allow_other` in
+    /// `/etc/fuse.conf`
+    allow_other: bool,
+}
+
+fn main() -> std::io::Result<()> {
+    pretty_env_logger::init();
+    let opts = Opts::parse();
+
+    let mut options = vec![MountOption::RO, MountOption::FSName("wutag".into())];
+    if opts.allow_other {
+        options.push(MountOption::AllowOther);
+    }
+    let fs = TagFs::new(Tree::new(IpcClient::new(default_socket())));
+    fuser::mount2(fs, &opts.mountpoint, &options)
+}
@@ -0,0 +1,394 @@
+//! The virtual directory tree of the filesystem built from the registry of the daemon.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+use thiserror::Error as ThisError;
+use wutag_core::view::link_names;
+use wutag_ipc::{IpcClient, IpcError, PayloadResult, Request, Response, SearchOptions};
+
+/// How long data fetched from the daemon is used before fetching it again.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Directory listing all tags, nested directories of tags list entries with all of them.
+pub const TAGS_DIR: &str = "tags";
+/// Directory listing saved searches.
+pub const QUERIES_DIR: &str = "queries";
+/// Directory mirroring paths of tagged entries with a `<name>.tags` file listing tags of each.
+pub const FILES_DIR: &str = "files";
+const TAGS_SUFFIX: &str = ".tags";
+
+/// Directory names can't contain `/` so it is replaced in names of tags like `type/image`.
+const SLASH_REPLACEMENT: char = '\u{2215}';
+
+#[derive(Debug, ThisError)]
+pub enum TreeError {
+    #[error("failed to communicate with the daemon - {0}")]
+    Ipc(#[from] IpcError),
+    #[error("daemon failed to {0} - {1}")]
+    Daemon(&'static str, String),
+    #[error("unexpected response from the daemon {0:?}")]
+    UnexpectedResponse(Response),
+}
+
+pub type Result<T> = std::result::Result<T, TreeError>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    Dir,
+    /// A symlink to a tagged entry.
+    Link(PathBuf),
+    /// A read-only file with the content.
+    File(Vec<u8>),
+}
+
+/// Children of a directory keyed by their names.
+pub type Listing = BTreeMap<OsString, Node>;
+
+#[derive(Default)]
+/// Data fetched from the daemon at one point in time.
+struct Snapshot {
+    tags: Vec<String>,
+    saved: Vec<String>,
+    /// Own tags of all tagged entries.
+    entries: BTreeMap<PathBuf, Vec<String>>,
+    /// Paths of entries found by searches keyed by the searched tags.
+    searches: HashMap<Vec<String>, Vec<PathBuf>>,
+}
+
+pub struct Tree {
+    client: IpcClient,
+    snapshot: Snapshot,
+    fetched: Option<Instant>,
+}
+
+impl Tree {
+    pub fn new(client: IpcClient) -> Self {
+        Self {
+            client,
+            snapshot: Snapshot::default(),
+            fetched: None,
+        }
+    }
+
+    /// Returns the node at the `path` relative to the root of the filesystem.
+    pub fn node(&mut self, path: &Path) -> Result<Option<Node>> {
+        let (parent, name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => (parent, name),
+            _ => return Ok(Some(Node::Dir)),
+        };
+        Ok(self
+            .list(parent)?
+            .and_then(|mut listing| listing.remove(name)))
+    }
+
+    /// Lists the directory at the `path` relative to the root of the filesystem. Returns `None`
+    /// if there is no such directory.
+    pub fn list(&mut self, path: &Path) -> Result<Option<Listing>> {
+        self.refresh()?;
+        let components: Vec<_> = path
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+        let (first, rest) = match components.split_first() {
+            Some((first, rest)) => (*first, rest),
+            None => {
+                return Ok(Some(dirs([TAGS_DIR, QUERIES_DIR, FILES_DIR])));
+            }
+        };
+
+        if first == TAGS_DIR {
+            let tags: Vec<_> = rest.iter().map(|name| decode_tag(name)).collect();
+            self.list_tags(tags)
+        } else if first == QUERIES_DIR {
+            match rest {
+                [] => Ok(Some(dirs(self.snapshot.saved.iter().map(String::as_str)))),
+                [name] => {
+                    let name = name.to_string_lossy().to_string();
+                    if !self.snapshot.saved.contains(&name) {
+                        return Ok(None);
+                    }
+                    let found = self.search(vec![name])?;
+                    Ok(Some(links(found, &BTreeSet::new())))
+                }
+                _ => Ok(None),
+            }
+        } else if first == FILES_DIR {
+            Ok(Some(self.list_files(rest)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Lists entries tagged with all of the `tags` together with directories of other tags of
+    /// these entries. Without `tags` lists all tags. Returns `None` if any of the tags doesn't
+    /// exist.
+    fn list_tags(&mut self, tags: Vec<String>) -> Result<Option<Listing>> {
+        if tags.is_empty() {
+            return Ok(Some(
+                self.snapshot
+                    .tags
+                    .iter()
+                    .map(|tag| (encode_tag(tag), Node::Dir))
+                    .collect(),
+            ));
+        }
+        if !tags.iter().all(|tag| self.snapshot.tags.contains(tag)) {
+            return Ok(None);
+        }
+        let found = self.search(tags.clone())?;
+        let mut listing: Listing = found
+            .iter()
+            .filter_map(|path| self.snapshot.entries.get(path))
+            .flatten()
+            .filter(|tag| !tags.contains(tag))
+            .map(|tag| (encode_tag(tag), Node::Dir))
+            .collect();
+        let reserved = listing.keys().cloned().collect();
+        listing.extend(links(found, &reserved));
+        Ok(Some(listing))
+    }
+
+    /// Lists the directory at `components` below the root mirroring paths of tagged entries.
+    fn list_files(&self, components: &[&OsStr]) -> Listing {
+        let dir: PathBuf = Path::new("/").join(components.iter().collect::<PathBuf>());
+        let mut listing = Listing::new();
+        for (path, tags) in &self.snapshot.entries {
+            let mut rest = match path.strip_prefix(&dir) {
+                Ok(rest) => rest.components(),
+                Err(_) => continue,
+            };
+            let name = match rest.next() {
+                Some(Component::Normal(name)) => name,
+                _ => continue,
+            };
+            if rest.next().is_some() {
+                listing.insert(name.to_os_string(), Node::Dir);
+            } else {
+                let mut content = tags.join("\n");
+                content.push('\n');
+                let mut name = name.to_os_string();
+                name.push(TAGS_SUFFIX);
+                listing.insert(name, Node::File(content.into_bytes()));
+            }
+        }
+        listing
+    }
+
+    /// Finds paths of entries with all of the `tags` or tags implying them.
+    fn search(&mut self, tags: Vec<String>) -> Result<Vec<PathBuf>> {
+        if let Some(found) = self.snapshot.searches.get(&tags) {
+            return Ok(found.clone());
+        }
+        let found: Vec<_> = match self.request(Request::Search {
+            tags: tags.clone(),
+            options: SearchOptions::default(),
+        })? {
+            Response::Search(PayloadResult::Ok(found)) => found
+                .into_iter()
+                .map(|found| found.entry.path().to_path_buf())
+                .collect(),
+            Response::Search(PayloadResult::Error(e)) => {
+                return Err(TreeError::Daemon("search", e))
+            }
+            response => return Err(TreeError::UnexpectedResponse(response)),
+        };
+        self.snapshot.searches.insert(tags, found.clone());
+        Ok(found)
+    }
+
+    /// Fetches tags, entries and saved searches from the daemon if the current data is older than
+    /// [REFRESH_INTERVAL](REFRESH_INTERVAL).
+    fn refresh(&mut self) -> Result<()> {
+        if let Some(fetched) = self.fetched {
+            if fetched.elapsed() < REFRESH_INTERVAL {
+                return Ok(());
+            }
+        }
+        let tags = match self.request(Request::ListTags {
+            with_files: false,
+            page: Default::default(),
+        })? {
+            Response::ListTags(PayloadResult::Ok(tags)) => tags
+                .into_iter()
+                .map(|(tag, _)| tag.name().to_string())
+                .collect(),
+            Response::ListTags(PayloadResult::Error(e)) => {
+                return Err(TreeError::Daemon("list tags", e))
+            }
+            response => return Err(TreeError::UnexpectedResponse(response)),
+        };
+        let entries = match self.request(Request::ListFiles {
+            with_tags: true,
+            sort: Default::default(),
+            page: Default::default(),
+        })? {
+            Response::ListFiles(PayloadResult::Ok(entries)) => entries
+                .into_iter()
+                .map(|(entry, tags)| {
+                    let tags = tags.iter().map(|tag| tag.name().to_string()).collect();
+                    (entry.into_path_buf(), tags)
+                })
+                .collect(),
+            Response::ListFiles(PayloadResult::Error(e)) => {
+                return Err(TreeError::Daemon("list files", e))
+            }
+            response => return Err(TreeError::UnexpectedResponse(response)),
+        };
+        let saved = match self.request(Request::ListSavedSearches)? {
+            Response::ListSavedSearches(PayloadResult::Ok(saved)) => {
+                saved.into_iter().map(|(name, _)| name).collect()
+            }
+            Response::ListSavedSearches(PayloadResult::Error(e)) => {
+                return Err(TreeError::Daemon("list saved searches", e))
+            }
+            response => return Err(TreeError::UnexpectedResponse(response)),
+        };
+
+        self.snapshot = Snapshot {
+            tags,
+            saved,
+            entries,
+            searches: HashMap::new(),
+        };
+        self.fetched = Some(Instant::now());
+        Ok(())
+    }
+
+    fn request(&self, request: Request) -> Result<Response> {
+        self.client.request(request).map_err(TreeError::from)
+    }
+}
+
+fn dirs<'a>(names: impl IntoIterator<Item = &'a str>) -> Listing {
+    names
+        .into_iter()
+        .map(|name| (OsString::from(name), Node::Dir))
+        .collect()
+}
+
+fn links(targets: Vec<PathBuf>, reserved: &BTreeSet<OsString>) -> Listing {
+    link_names(targets, reserved)
+        .into_iter()
+        .map(|(name, target)| (name, Node::Link(target)))
+        .collect()
+}
+
+fn encode_tag(tag: &str) -> OsString {
+    tag.replace('/', &SLASH_REPLACEMENT.to_string()).into()
+}
+
+fn decode_tag(name: &OsStr) -> String {
+    name.to_string_lossy().replace(SLASH_REPLACEMENT, "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a tree with data that is never refreshed from the daemon.
+    fn tree() -> Tree {
+        let tags = ["photo", "type/image", "2021"].map(String::from).to_vec();
+        let entries = BTreeMap::from([
+            (
+                PathBuf::from("/home/user/a.jpg"),
+                vec!["photo".to_string(), "type/image".to_string()],
+            ),
+            (
+                PathBuf::from("/home/user/2021/a.jpg"),
+                vec!["photo".to_string(), "2021".to_string()],
+            ),
+            (PathBuf::from("/home/user/notes"), vec!["2021".to_string()]),
+        ]);
+        let searches = HashMap::from([
+            (
+                vec!["photo".to_string()],
+                vec![
+                    PathBuf::from("/home/user/a.jpg"),
+                    PathBuf::from("/home/user/2021/a.jpg"),
+                ],
+            ),
+            (
+                vec!["photo".to_string(), "type/image".to_string()],
+                vec![PathBuf::from("/home/user/a.jpg")],
+            ),
+        ]);
+        Tree {
+            client: IpcClient::new("unused"),
+            snapshot: Snapshot {
+                tags,
+                saved: vec![],
+                entries,
+                searches,
+            },
+            fetched: Some(Instant::now()),
+        }
+    }
+
+    fn names(listing: Listing) -> Vec<String> {
+        listing
+            .into_keys()
+            .map(|name| name.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn encodes_tags() {
+        assert_eq!(
+            encode_tag("type/image"),
+            OsString::from("type\u{2215}image")
+        );
+        assert_eq!(decode_tag(&encode_tag("type/image")), "type/image");
+        assert_eq!(decode_tag(OsStr::new("photo")), "photo");
+    }
+
+    #[test]
+    fn lists_tags() {
+        let mut tree = tree();
+        let listing = tree.list(Path::new("tags")).unwrap().unwrap();
+        assert_eq!(names(listing), vec!["2021", "photo", "type\u{2215}image"]);
+
+        let listing = tree.list(Path::new("tags/photo")).unwrap().unwrap();
+        assert_eq!(
+            listing.get(OsStr::new("a.jpg")),
+            Some(&Node::Link(PathBuf::from("/home/user/2021/a.jpg")))
+        );
+        assert_eq!(
+            names(listing),
+            vec!["2021", "a (2).jpg", "a.jpg", "type\u{2215}image"]
+        );
+
+        let listing = tree
+            .list(Path::new("tags/photo/type\u{2215}image"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(names(listing), vec!["a.jpg"]);
+
+        assert!(tree.list(Path::new("tags/unknown")).unwrap().is_none());
+        assert!(tree
+            .list(Path::new("tags/photo/unknown"))
+            .unwrap()
+            .is_none());
+        assert!(tree.node(Path::new("tags/unknown")).unwrap().is_none());
+        assert_eq!(tree.node(Path::new("tags/photo")).unwrap(), Some(Node::Dir));
+    }
+
+    #[test]
+    fn lists_files() {
+        let tree = tree();
+        let listing = tree.list_files(&[]);
+        assert_eq!(names(listing), vec!["home"]);
+
+        let listing = tree.list_files(&[OsStr::new("home"), OsStr::new("user")]);
+        assert_eq!(
+            listing.get(OsStr::new("a.jpg.tags")),
+            Some(&Node::File(b"photo\ntype/image\n".to_vec()))
+        );
+        assert_eq!(names(listing), vec!["2021", "a.jpg.tags", "notes.tags"]);
+
+        assert!(tree.list_files(&[OsStr::new("tmp")]).is_empty());
+    }
+}
@@ -4,19 +4,27 @@ mod server;
 
 pub use client::{ClientError, IpcClient};
 pub use payload::{Payload, PayloadError, PayloadResult};
-pub use server::{IpcServer, ServerError};
+pub use server::{IpcServer, PeerCredentials, ServerError};
 
+use chrono::{DateTime, Utc};
 use interprocess::local_socket::NameTypeSupport;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::io;
 use std::path::Path;
 use std::path::PathBuf;
 use thiserror::Error;
+use wutag_core::audit::{AuditEntry, AuditFilter};
 use wutag_core::color::Color;
+use wutag_core::filter::FileFilter;
 use wutag_core::glob::Glob;
-use wutag_core::registry::EntryData;
+use wutag_core::journal::JournalEntry;
+use wutag_core::registry::{
+    EffectiveTag, EntryData, RegistryExport, SearchMatch, Suggestion, TagChange, TagInfo,
+};
+use wutag_core::rules::RuleMatch;
+use wutag_core::sort::{Page, Sort};
 use wutag_core::tag::Tag;
+use wutag_core::view::ViewReport;
 
 pub type Result<T> = std::result::Result<T, IpcError>;
 
@@ -55,50 +63,154 @@ pub enum IpcError {
     Other(String),
 }
 
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// Methods used to derive the tags applied by automatic tagging.
+pub struct AutoTagOptions {
+    /// Tag files with their detected content type like `type/image`.
+    pub by_type: bool,
+    /// Templates like `year={exif.year}` rendered with metadata of files into tags.
+    pub metadata: Vec<String>,
+    /// Regular expression matched against names of files whose capture groups become tags.
+    pub from_name: Option<String>,
+    /// Colors from which the colors of new tags are picked, the default colors if empty.
+    pub colors: Vec<Color>,
+}
+
+impl AutoTagOptions {
+    /// Checks whether any method of deriving tags is enabled.
+    pub fn is_empty(&self) -> bool {
+        !self.by_type && self.metadata.is_empty() && self.from_name.is_none()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// Changes made or planned by automatic tagging. Files whose tags couldn't be derived in some of
+/// the ways are reported in the errors while the tags derived in the other ways and the tags of
+/// the other files are still applied.
+pub struct AutoTagReport {
+    pub changes: Vec<TagChange>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// Conditions that entries found by a search have to match besides having the searched tags.
+pub struct SearchOptions {
+    /// Match entries with any of the searched tags rather than all of them.
+    pub any: bool,
+    /// Only entries tagged with the searched tags at or after this time.
+    pub tagged_since: Option<DateTime<Utc>>,
+    /// Only entries tagged with the searched tags before this time.
+    pub tagged_before: Option<DateTime<Utc>>,
+    /// Whether tags of directories apply to all of their descendants.
+    pub inherit: bool,
+    /// Conditions on the location, type, size and modification time of the entries.
+    pub filter: FileFilter,
+    pub sort: Sort,
+    pub page: Page,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// Changes made to a tag by editing it. Fields that are `None` are left unchanged, empty
+/// description or icon removes it.
+pub struct TagEdit {
+    pub color: Option<Color>,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+}
+
+impl TagEdit {
+    /// Checks whether the edit changes anything.
+    pub fn is_empty(&self) -> bool {
+        self.color.is_none() && self.description.is_none() && self.icon.is_none()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// Problems found in the names of tags saved in the registry.
+pub struct DoctorReport {
+    /// Groups of tags whose names are easy to confuse with each other, the most used tag of each
+    /// group first.
+    pub near_duplicates: Vec<Vec<String>>,
+    /// Tags whose names break the current naming rules or aren't normalized, together with the
+    /// reason.
+    pub invalid: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// Everything an import changed or would change. Items that couldn't be imported are reported in
+/// the errors while the rest of the import is still applied.
+pub struct ImportReport {
+    /// Tags added to the imported entries and entries retagged because of imported aliases.
+    pub changes: Vec<TagChange>,
+    /// Imported implications, aliases and saved searches.
+    pub edits: Vec<AuditEntry>,
+    pub errors: Vec<String>,
+}
+
 #[derive(Deserialize, Debug, Serialize)]
 pub enum Request {
     TagFiles {
         files: Vec<PathBuf>,
         tags: Vec<Tag>,
+        /// When the added tags expire and should be removed.
+        expires: Option<DateTime<Utc>>,
+        /// Whether to also add all tags implied by the added tags.
+        implied: bool,
+        dry_run: bool,
     },
     TagFilesPattern {
         glob: Glob,
         tags: Vec<Tag>,
+        expires: Option<DateTime<Utc>>,
+        implied: bool,
+        dry_run: bool,
     },
     UntagFiles {
         files: Vec<PathBuf>,
         tags: Vec<Tag>,
+        dry_run: bool,
     },
     UntagFilesPattern {
         glob: Glob,
         tags: Vec<Tag>,
+        dry_run: bool,
     },
     EditTag {
         tag: String,
-        color: Color,
+        edit: TagEdit,
+        dry_run: bool,
     },
     ClearFiles {
         files: Vec<PathBuf>,
+        dry_run: bool,
     },
     ClearFilesPattern {
         glob: Glob,
+        dry_run: bool,
     },
     ClearTags {
         tags: Vec<String>,
+        dry_run: bool,
     },
     CopyTags {
         source: PathBuf,
         target: Vec<PathBuf>,
+        dry_run: bool,
     },
     CopyTagsPattern {
         source: PathBuf,
         glob: Glob,
+        dry_run: bool,
     },
+    /// Lists tags ordered by their names.
     ListTags {
         with_files: bool,
+        page: Page,
     },
     ListFiles {
         with_tags: bool,
+        sort: Sort,
+        page: Page,
     },
     InspectFiles {
         files: Vec<PathBuf>,
@@ -106,30 +218,165 @@ pub enum Request {
     InspectFilesPattern {
         glob: Glob,
     },
+    InspectEffective {
+        files: Vec<PathBuf>,
+    },
+    InspectEffectivePattern {
+        glob: Glob,
+    },
     Search {
         tags: Vec<String>,
-        any: bool,
+        options: SearchOptions,
     },
     Ping,
-    ClearCache,
+    /// Removes all entries from the registry.
+    ClearCache {
+        dry_run: bool,
+    },
+    Undo {
+        count: usize,
+        dry_run: bool,
+    },
+    Redo {
+        count: usize,
+        dry_run: bool,
+    },
+    History,
+    AuditLog {
+        filter: AuditFilter,
+    },
+    TestRules {
+        path: PathBuf,
+    },
+    AddImplication {
+        tag: String,
+        implies: String,
+        dry_run: bool,
+    },
+    RemoveImplication {
+        tag: String,
+        implies: String,
+        dry_run: bool,
+    },
+    ListImplications,
+    AutoTagFiles {
+        files: Vec<PathBuf>,
+        options: AutoTagOptions,
+        dry_run: bool,
+    },
+    AutoTagFilesPattern {
+        glob: Glob,
+        options: AutoTagOptions,
+        dry_run: bool,
+    },
+    AddAlias {
+        alias: String,
+        tag: String,
+        dry_run: bool,
+    },
+    RemoveAlias {
+        alias: String,
+        dry_run: bool,
+    },
+    ListAliases,
+    AddSavedSearch {
+        name: String,
+        /// Arguments of the search like `invoice --tagged-since 30d`.
+        query: String,
+        dry_run: bool,
+    },
+    RemoveSavedSearch {
+        name: String,
+        dry_run: bool,
+    },
+    ListSavedSearches,
+    Export,
+    /// Tags the exported entries and merges implication rules, aliases and saved searches into
+    /// the registry.
+    Import {
+        data: RegistryExport,
+        dry_run: bool,
+    },
+    TagInfo {
+        tag: String,
+        /// How many of the most frequently co-occurring tags to return.
+        top: usize,
+    },
+    Doctor,
+    Suggest {
+        path: PathBuf,
+        /// Maximum number of suggested tags.
+        limit: usize,
+    },
+    /// Builds a directory of symlinks to the entries found by the query or to entries of each tag
+    /// if there is no query.
+    MaterializeView {
+        path: PathBuf,
+        query: Option<String>,
+        dry_run: bool,
+    },
+    /// Removes symlinks and directories created in a view.
+    CleanView {
+        path: PathBuf,
+        dry_run: bool,
+    },
+    /// A part of an operation split into multiple requests like tagging paths read from the
+    /// standard input in chunks. Changes made by parts of the same operation are recorded in the
+    /// history as a single entry so they are undone together.
+    Part {
+        operation: u64,
+        request: Box<Request>,
+    },
 }
 
 impl Payload for Request {}
 
 #[derive(Deserialize, Debug, Serialize)]
 pub enum Response {
-    TagFiles(PayloadResult<(), Vec<String>>),
-    UntagFiles(PayloadResult<(), Vec<String>>),
-    EditTag(PayloadResult<(), String>),
-    CopyTags(PayloadResult<(), Vec<String>>),
-    ClearFiles(PayloadResult<(), Vec<String>>),
-    ClearTags(PayloadResult<(), Vec<String>>),
-    ListTags(PayloadResult<HashMap<Tag, Vec<EntryData>>, String>),
+    TagFiles(PayloadResult<Vec<TagChange>, Vec<String>>),
+    UntagFiles(PayloadResult<Vec<TagChange>, Vec<String>>),
+    /// Descriptions of the edits made to the registry, empty if nothing had to change. Also
+    /// returned by the other requests editing implications, aliases and saved searches.
+    EditTag(PayloadResult<Vec<AuditEntry>, String>),
+    CopyTags(PayloadResult<Vec<TagChange>, Vec<String>>),
+    ClearFiles(PayloadResult<Vec<TagChange>, Vec<String>>),
+    ClearTags(PayloadResult<Vec<TagChange>, Vec<String>>),
+    ListTags(PayloadResult<Vec<(Tag, Vec<EntryData>)>, String>),
     ListFiles(PayloadResult<Vec<(EntryData, Vec<Tag>)>, String>),
     InspectFiles(PayloadResult<Vec<(EntryData, Vec<Tag>)>, String>),
-    Search(PayloadResult<Vec<EntryData>, String>),
+    /// Effective tags of each inspected path including tags inherited from parent directories.
+    InspectEffective(PayloadResult<Vec<(PathBuf, Vec<EffectiveTag>)>, String>),
+    Search(PayloadResult<Vec<SearchMatch>, String>),
     Ping(PayloadResult<(), String>),
-    ClearCache(PayloadResult<(), String>),
+    /// Tags of every entry dropped from the registry, the files keep their tags.
+    ClearCache(PayloadResult<Vec<AuditEntry>, String>),
+    Undo(PayloadResult<Vec<TagChange>, Vec<String>>),
+    Redo(PayloadResult<Vec<TagChange>, Vec<String>>),
+    /// Applied entries followed by undone entries, both starting with the most recent one.
+    History(PayloadResult<(Vec<JournalEntry>, Vec<JournalEntry>), String>),
+    AuditLog(PayloadResult<Vec<AuditEntry>, String>),
+    TestRules(PayloadResult<Vec<RuleMatch>, String>),
+    AutoTag(PayloadResult<AutoTagReport, Vec<String>>),
+    AddImplication(PayloadResult<Vec<AuditEntry>, String>),
+    RemoveImplication(PayloadResult<Vec<AuditEntry>, String>),
+    /// Pairs of the implying and implied tag names.
+    ListImplications(PayloadResult<Vec<(String, String)>, String>),
+    /// Changes retagging entries tagged with the alias with the canonical tag.
+    AddAlias(PayloadResult<Vec<TagChange>, Vec<String>>),
+    RemoveAlias(PayloadResult<Vec<AuditEntry>, String>),
+    /// Pairs of the alias and canonical tag names.
+    ListAliases(PayloadResult<Vec<(String, String)>, String>),
+    AddSavedSearch(PayloadResult<Vec<AuditEntry>, String>),
+    RemoveSavedSearch(PayloadResult<Vec<AuditEntry>, String>),
+    /// Pairs of the name and query of each saved search.
+    ListSavedSearches(PayloadResult<Vec<(String, String)>, String>),
+    Export(PayloadResult<Box<RegistryExport>, String>),
+    Import(PayloadResult<ImportReport, String>),
+    TagInfo(PayloadResult<Box<TagInfo>, String>),
+    Doctor(PayloadResult<DoctorReport, String>),
+    Suggest(PayloadResult<Vec<Suggestion>, String>),
+    MaterializeView(PayloadResult<Box<ViewReport>, String>),
+    CleanView(PayloadResult<Box<ViewReport>, String>),
 }
 
 impl Payload for Response {}
@@ -18,11 +18,58 @@ pub enum ServerError {
     Bind(io::Error),
 }
 
+#[derive(Clone, Copy, Debug, Default)]
+/// Credentials of the process on the other end of a connection, if they could be determined.
+pub struct PeerCredentials {
+    pub pid: Option<u32>,
+    pub uid: Option<u32>,
+}
+
+impl PeerCredentials {
+    fn of(conn: &LocalSocketStream) -> Self {
+        Self {
+            pid: conn.peer_pid().ok(),
+            uid: peer_uid(conn),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn peer_uid(conn: &LocalSocketStream) -> Option<u32> {
+    use std::os::unix::io::AsRawFd;
+    let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            conn.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    (ret == 0).then_some(cred.uid)
+}
+
+#[cfg(target_os = "macos")]
+fn peer_uid(conn: &LocalSocketStream) -> Option<u32> {
+    use std::os::unix::io::AsRawFd;
+    let mut uid = 0;
+    let mut gid = 0;
+    let ret = unsafe { libc::getpeereid(conn.as_raw_fd(), &mut uid, &mut gid) };
+    (ret == 0).then_some(uid)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn peer_uid(_: &LocalSocketStream) -> Option<u32> {
+    None
+}
+
 pub struct IpcServer {
     #[allow(dead_code)]
     path: String,
     socket: LocalSocketListener,
-    conns: VecDeque<BufReader<LocalSocketStream>>,
+    conns: VecDeque<(BufReader<LocalSocketStream>, PeerCredentials)>,
 }
 
 impl IpcServer {
@@ -41,19 +88,25 @@ impl IpcServer {
             .socket
             .accept()
             .map_err(ServerError::ConnectionAccept)?;
+        let peer = PeerCredentials::of(&conn);
         let mut conn = BufReader::new(conn);
         let request = REQUEST::read(&mut conn)?;
-        log::debug!("got request: {request:?}");
-        self.conns.push_back(conn);
+        log::debug!("got request: {request:?} from {peer:?}");
+        self.conns.push_back((conn, peer));
         Ok(request)
     }
 
+    /// Returns credentials of the client that awaits the next response.
+    pub fn peer(&self) -> Option<PeerCredentials> {
+        self.conns.front().map(|(_, peer)| *peer)
+    }
+
     pub fn send_response<RESPONSE: Payload>(&mut self, response: RESPONSE) -> Result<()> {
-        if let Some(mut conn) = self.conns.pop_front() {
+        if let Some((mut conn, _)) = self.conns.pop_front() {
             log::debug!("sending response: {response:?}");
             return response.send(&mut conn);
         }
 
-        Err(ServerError::NoActiveConnection).map_err(IpcError::Server)
+        Err(IpcError::Server(ServerError::NoActiveConnection))
     }
 }